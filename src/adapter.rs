@@ -0,0 +1,408 @@
+#![allow(dead_code)]
+//! Adapter selection and device feature negotiation.
+//! Every adapter of the requested backends is checked against the surface, the required features
+//! and the required limits. Of those that pass, the one matching the power preference best is
+//! used. When none pass, the error says what each adapter was missing.
+
+use std::fmt;
+
+/// Which adapters may be used and what the device has to support
+#[derive(Clone, Debug)]
+pub struct AdapterSettings {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Only consider software adapters, such as WARP on DX12
+    pub force_fallback_adapter: bool,
+    /// Adapters without all of these are rejected
+    pub required_features: wgpu::Features,
+    /// Enabled when the adapter supports them
+    pub optional_features: wgpu::Features,
+    /// Adapters whose limits are worse than these are rejected
+    pub limits: wgpu::Limits,
+}
+
+impl Default for AdapterSettings {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            required_features: wgpu::Features::empty(),
+            // Timestamps are only used for frame statistics
+            optional_features: wgpu::Features::TIMESTAMP_QUERY,
+            limits: wgpu::Limits::default(),
+        }
+    }
+}
+
+impl AdapterSettings {
+    /// Settings for old hardware and software adapters, limits are lowered to what every
+    /// backend including GL can provide
+    pub fn downlevel() -> Self {
+        Self {
+            limits: wgpu::Limits::downlevel_defaults(),
+            ..Default::default()
+        }
+    }
+
+    /// Settings for running without a gpu, e.g. in CI
+    pub fn software() -> Self {
+        Self {
+            force_fallback_adapter: true,
+            ..Self::downlevel()
+        }
+    }
+}
+
+/// Why an adapter was not used
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rejection {
+    /// The adapter cannot present to the window
+    SurfaceUnsupported,
+    /// A hardware adapter while only fallback adapters were allowed
+    NotFallback,
+    MissingFeatures(wgpu::Features),
+    /// Names of the limits the adapter does not reach
+    LimitsNotMet(Vec<&'static str>),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SurfaceUnsupported => write!(f, "cannot present to the window"),
+            Self::NotFallback => write!(f, "not a fallback adapter"),
+            Self::MissingFeatures(features) => write!(f, "missing features {:?}", features),
+            Self::LimitsNotMet(limits) => write!(f, "limits not met: {}", limits.join(", ")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum AdapterError {
+    /// No adapter exists for the requested backends
+    NoAdapters { backends: wgpu::Backends },
+    /// Every adapter was rejected, with the reason for each
+    NoSuitableAdapter {
+        rejected: Vec<(wgpu::AdapterInfo, Rejection)>,
+    },
+    /// The adapter was chosen but creating the device failed
+    RequestDevice {
+        adapter: wgpu::AdapterInfo,
+        error: wgpu::RequestDeviceError,
+    },
+    /// The surface reports no texture format it can present with on the adapter
+    NoSurfaceFormat { adapter: wgpu::AdapterInfo },
+    /// Querying the window the surface is created for failed
+    #[cfg(windows)]
+    Window(windows::core::Error),
+}
+
+impl fmt::Display for AdapterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapters { backends } => {
+                write!(f, "no graphics adapters found for backends {:?}", backends)
+            }
+            Self::NoSuitableAdapter { rejected } => {
+                write!(f, "no suitable graphics adapter found")?;
+                for (info, rejection) in rejected {
+                    write!(
+                        f,
+                        "\n  {} ({:?}, {:?}): {}",
+                        info.name, info.backend, info.device_type, rejection
+                    )?;
+                }
+                Ok(())
+            }
+            Self::RequestDevice { adapter, error } => write!(
+                f,
+                "could not create a device on {} ({:?}): {}",
+                adapter.name, adapter.backend, error
+            ),
+            Self::NoSurfaceFormat { adapter } => write!(
+                f,
+                "the surface has no format {} ({:?}) can present with",
+                adapter.name, adapter.backend
+            ),
+            #[cfg(windows)]
+            Self::Window(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for AdapterError {}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for AdapterError {
+    fn from(error: windows::core::Error) -> Self {
+        Self::Window(error)
+    }
+}
+
+/// Information about every adapter of the given backends
+pub fn enumerate_adapters(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+) -> Vec<wgpu::AdapterInfo> {
+    instance
+        .enumerate_adapters(backends)
+        .map(|adapter| adapter.get_info())
+        .collect()
+}
+
+/// Picks the adapter that best matches the settings and can present to `surface`
+pub fn select_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface,
+    settings: &AdapterSettings,
+) -> Result<wgpu::Adapter, AdapterError> {
+    let mut candidates = Vec::new();
+    let mut rejected = Vec::new();
+    for adapter in instance.enumerate_adapters(settings.backends) {
+        let info = adapter.get_info();
+        let rejection = if !adapter.is_surface_supported(surface) {
+            Some(Rejection::SurfaceUnsupported)
+        } else {
+            check_adapter(&info, adapter.features(), &adapter.limits(), settings)
+        };
+        match rejection {
+            Some(rejection) => rejected.push((info, rejection)),
+            None => candidates.push((adapter_rank(&info, settings.power_preference), adapter)),
+        }
+    }
+
+    if candidates.is_empty() && rejected.is_empty() {
+        return Err(AdapterError::NoAdapters {
+            backends: settings.backends,
+        });
+    }
+    // Lowest rank wins, ties keep enumeration order
+    candidates
+        .into_iter()
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, adapter)| adapter)
+        .ok_or(AdapterError::NoSuitableAdapter { rejected })
+}
+
+/// Creates the device with the required features and whichever optional ones are supported
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+    settings: &AdapterSettings,
+) -> Result<(wgpu::Device, wgpu::Queue), AdapterError> {
+    let features = settings.required_features | (adapter.features() & settings.optional_features);
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features,
+                // Never ask for less than the adapter has for textures, larger windows need it
+                limits: settings.limits.clone().using_resolution(adapter.limits()),
+                label: None,
+            },
+            None, // Trace path
+        )
+        .await
+        .map_err(|error| AdapterError::RequestDevice {
+            adapter: adapter.get_info(),
+            error,
+        })
+}
+
+/// Why an adapter with these capabilities cannot be used, None if it can
+pub fn check_adapter(
+    info: &wgpu::AdapterInfo,
+    features: wgpu::Features,
+    limits: &wgpu::Limits,
+    settings: &AdapterSettings,
+) -> Option<Rejection> {
+    if settings.force_fallback_adapter && info.device_type != wgpu::DeviceType::Cpu {
+        return Some(Rejection::NotFallback);
+    }
+    let missing = settings.required_features - features;
+    if !missing.is_empty() {
+        return Some(Rejection::MissingFeatures(missing));
+    }
+    let unmet = unmet_limits(&settings.limits, limits);
+    if !unmet.is_empty() {
+        return Some(Rejection::LimitsNotMet(unmet));
+    }
+    None
+}
+
+/// Order of preference for an adapter, lower is better
+pub fn adapter_rank(info: &wgpu::AdapterInfo, preference: wgpu::PowerPreference) -> u32 {
+    use wgpu::DeviceType::*;
+    let order: [wgpu::DeviceType; 5] = match preference {
+        wgpu::PowerPreference::LowPower => [IntegratedGpu, DiscreteGpu, VirtualGpu, Other, Cpu],
+        wgpu::PowerPreference::HighPerformance => {
+            [DiscreteGpu, IntegratedGpu, VirtualGpu, Other, Cpu]
+        }
+    };
+    order
+        .iter()
+        .position(|device_type| *device_type == info.device_type)
+        .unwrap_or(order.len()) as u32
+}
+
+/// Names of the limits in `available` that do not reach `required`.
+/// Alignments are better when smaller, every other limit when larger.
+pub fn unmet_limits(required: &wgpu::Limits, available: &wgpu::Limits) -> Vec<&'static str> {
+    let mut unmet = Vec::new();
+    macro_rules! check {
+        (max: $($name:ident),*; min: $($align:ident),*) => {
+            $(if required.$name > available.$name {
+                unmet.push(stringify!($name));
+            })*
+            $(if required.$align < available.$align {
+                unmet.push(stringify!($align));
+            })*
+        };
+    }
+    check!(
+        max: max_texture_dimension_1d,
+        max_texture_dimension_2d,
+        max_texture_dimension_3d,
+        max_texture_array_layers,
+        max_bind_groups,
+        max_dynamic_uniform_buffers_per_pipeline_layout,
+        max_dynamic_storage_buffers_per_pipeline_layout,
+        max_sampled_textures_per_shader_stage,
+        max_samplers_per_shader_stage,
+        max_storage_buffers_per_shader_stage,
+        max_storage_textures_per_shader_stage,
+        max_uniform_buffers_per_shader_stage,
+        max_uniform_buffer_binding_size,
+        max_storage_buffer_binding_size,
+        max_vertex_buffers,
+        max_vertex_attributes,
+        max_vertex_buffer_array_stride,
+        max_push_constant_size,
+        max_inter_stage_shader_components,
+        max_compute_workgroup_storage_size,
+        max_compute_invocations_per_workgroup,
+        max_compute_workgroup_size_x,
+        max_compute_workgroup_size_y,
+        max_compute_workgroup_size_z,
+        max_compute_workgroups_per_dimension;
+        min: min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment
+    );
+    unmet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(device_type: wgpu::DeviceType) -> wgpu::AdapterInfo {
+        wgpu::AdapterInfo {
+            name: format!("{:?}", device_type),
+            vendor: 0,
+            device: 0,
+            device_type,
+            backend: wgpu::Backend::Vulkan,
+        }
+    }
+
+    #[test]
+    fn preferred_device_types_rank_first() {
+        use wgpu::DeviceType::*;
+        let high = wgpu::PowerPreference::HighPerformance;
+        let low = wgpu::PowerPreference::LowPower;
+        assert!(adapter_rank(&info(DiscreteGpu), high) < adapter_rank(&info(IntegratedGpu), high));
+        assert!(adapter_rank(&info(IntegratedGpu), low) < adapter_rank(&info(DiscreteGpu), low));
+        // Software adapters are the last resort either way
+        for preference in [high, low] {
+            for device_type in [DiscreteGpu, IntegratedGpu, VirtualGpu, Other] {
+                assert!(
+                    adapter_rank(&info(device_type), preference)
+                        < adapter_rank(&info(Cpu), preference)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn limits_compare_in_their_own_direction() {
+        let required = wgpu::Limits::downlevel_defaults();
+        assert!(unmet_limits(&required, &required).is_empty());
+        assert!(unmet_limits(&required, &wgpu::Limits::default()).is_empty());
+
+        let available = wgpu::Limits {
+            max_texture_dimension_2d: required.max_texture_dimension_2d - 1,
+            max_bind_groups: required.max_bind_groups + 1,
+            ..required.clone()
+        };
+        assert_eq!(
+            unmet_limits(&required, &available),
+            vec!["max_texture_dimension_2d"]
+        );
+
+        // A coarser alignment is worse, a finer one better
+        let coarse = wgpu::Limits {
+            min_uniform_buffer_offset_alignment: required.min_uniform_buffer_offset_alignment * 2,
+            min_storage_buffer_offset_alignment: required.min_storage_buffer_offset_alignment / 2,
+            ..required.clone()
+        };
+        assert_eq!(
+            unmet_limits(&required, &coarse),
+            vec!["min_uniform_buffer_offset_alignment"]
+        );
+    }
+
+    #[test]
+    fn adapters_are_checked_in_order() {
+        let limits = wgpu::Limits::default();
+        let gpu = info(wgpu::DeviceType::DiscreteGpu);
+        let cpu = info(wgpu::DeviceType::Cpu);
+        let settings = AdapterSettings::default();
+        assert_eq!(
+            check_adapter(&gpu, wgpu::Features::empty(), &limits, &settings),
+            None
+        );
+
+        let software = AdapterSettings::software();
+        assert_eq!(
+            check_adapter(&gpu, wgpu::Features::empty(), &limits, &software),
+            Some(Rejection::NotFallback)
+        );
+        assert_eq!(
+            check_adapter(&cpu, wgpu::Features::empty(), &limits, &software),
+            None
+        );
+
+        let timestamps = AdapterSettings {
+            required_features: wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::POLYGON_MODE_LINE,
+            ..AdapterSettings::default()
+        };
+        assert_eq!(
+            check_adapter(
+                &gpu,
+                wgpu::Features::POLYGON_MODE_LINE,
+                &limits,
+                &timestamps
+            ),
+            Some(Rejection::MissingFeatures(wgpu::Features::TIMESTAMP_QUERY))
+        );
+        // Optional features are never a reason to reject
+        assert_eq!(
+            check_adapter(&gpu, timestamps.required_features, &limits, &timestamps),
+            None
+        );
+
+        let small = wgpu::Limits::downlevel_defaults();
+        assert_eq!(
+            check_adapter(&gpu, wgpu::Features::empty(), &small, &settings),
+            Some(Rejection::LimitsNotMet(unmet_limits(
+                &settings.limits,
+                &small
+            )))
+        );
+        assert!(check_adapter(
+            &gpu,
+            wgpu::Features::empty(),
+            &small,
+            &AdapterSettings::downlevel()
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,58 @@
+#![allow(dead_code)]
+//! Which pixels of a transparent window can be clicked.
+//! The mask is stretched over the whole window, so a mask made from a splash screen image keeps
+//! working when the window is a different size to the image.
+
+/// One bit of opacity per pixel
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlphaMask {
+    width: u32,
+    height: u32,
+    opaque: Vec<bool>,
+}
+
+impl AlphaMask {
+    /// Pixels with an alpha above `threshold` are opaque
+    pub fn from_rgba(image: &image::RgbaImage, threshold: u8) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            opaque: image.pixels().map(|p| p.0[3] > threshold).collect(),
+        }
+    }
+
+    /// A mask where `is_opaque` decides each pixel
+    pub fn from_fn<F: Fn(u32, u32) -> bool>(width: u32, height: u32, is_opaque: F) -> Self {
+        let opaque = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| is_opaque(x, y))
+            .collect();
+        Self {
+            width,
+            height,
+            opaque,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Whether a point of a window of `window_size` is opaque, points outside the window are not
+    pub fn is_opaque(&self, point: (i32, i32), window_size: (u32, u32)) -> bool {
+        let (x, y) = point;
+        if x < 0 || y < 0 || window_size.0 == 0 || window_size.1 == 0 {
+            return false;
+        }
+        let (x, y) = (x as u64, y as u64);
+        if x >= window_size.0 as u64 || y >= window_size.1 as u64 {
+            return false;
+        }
+        let mask_x = x * self.width as u64 / window_size.0 as u64;
+        let mask_y = y * self.height as u64 / window_size.1 as u64;
+        self.opaque
+            .get((mask_y * self.width as u64 + mask_x) as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+}
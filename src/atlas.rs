@@ -0,0 +1,719 @@
+#![allow(dead_code)]
+//! Texture atlases: many small images packed into a few large textures.
+//! Rectangles are packed into horizontal shelves. Each image is surrounded by padding that is
+//! filled with its edge pixels, so bilinear filtering near an edge does not pick up the
+//! neighbouring image. Pages have a single mip level, padding a few pixels wide would not keep
+//! the smaller mips of neighbouring images apart. When a page is full another is added, up to a
+//! limit.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Shelves taller than this multiple of an image's height are not used for it while a better
+/// fitting shelf can still be opened
+const SHELF_WASTE: f32 = 1.5;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// The image and its padding do not fit on an empty page
+    TooLarge {
+        width: u32,
+        height: u32,
+        page_size: u32,
+    },
+    /// Every page is full and no more may be added
+    OutOfPages {
+        max_pages: usize,
+    },
+    Io(std::io::Error),
+    Image(image::ImageError),
+    /// The manifest could not be written or read
+    Manifest(String),
+    /// Bad command line arguments for offline packing
+    InvalidArguments(String),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge {
+                width,
+                height,
+                page_size,
+            } => write!(
+                f,
+                "a {}x{} image does not fit on a {}x{} atlas page",
+                width, height, page_size, page_size
+            ),
+            Self::OutOfPages { max_pages } => {
+                write!(f, "all {} atlas pages are full", max_pages)
+            }
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Image(error) => write!(f, "{}", error),
+            Self::Manifest(error) => write!(f, "invalid atlas manifest: {}", error),
+            Self::InvalidArguments(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<std::io::Error> for AtlasError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<image::ImageError> for AtlasError {
+    fn from(error: image::ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtlasSettings {
+    /// Width and height of every page in pixels
+    pub page_size: u32,
+    /// Pixels kept free around each image
+    pub padding: u32,
+    /// Fill the padding with the image's edge pixels rather than leaving it transparent
+    pub extrude: bool,
+    pub max_pages: usize,
+}
+
+impl Default for AtlasSettings {
+    fn default() -> Self {
+        Self {
+            page_size: 1024,
+            padding: 2,
+            extrude: true,
+            max_pages: 8,
+        }
+    }
+}
+
+/// Identifies an image in an atlas, never reused after the image is removed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AtlasHandle(u32);
+
+/// Where an image is, not including its padding
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Texture coordinates of the top left and bottom right corners
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+#[derive(Clone, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    // Free spans as (x, width), sorted by x
+    free: Vec<(u32, u32)>,
+}
+
+impl Shelf {
+    fn largest_free(&self) -> u32 {
+        self.free.iter().map(|&(_, width)| width).max().unwrap_or(0)
+    }
+
+    fn is_empty(&self, page_width: u32) -> bool {
+        self.free == [(0, page_width)]
+    }
+
+    /// Takes the first span wide enough, returning its x
+    fn allocate(&mut self, width: u32) -> Option<u32> {
+        let index = self.free.iter().position(|&(_, free)| free >= width)?;
+        let (x, free) = self.free[index];
+        if free == width {
+            self.free.remove(index);
+        } else {
+            self.free[index] = (x + width, free - width);
+        }
+        Some(x)
+    }
+
+    /// Returns a span, merging it with the free spans either side
+    fn deallocate(&mut self, x: u32, width: u32) {
+        let index = self.free.partition_point(|&(free_x, _)| free_x < x);
+        self.free.insert(index, (x, width));
+        if index + 1 < self.free.len() && x + width == self.free[index + 1].0 {
+            self.free[index].1 += self.free.remove(index + 1).1;
+        }
+        if index > 0 && self.free[index - 1].0 + self.free[index - 1].1 == x {
+            self.free[index - 1].1 += self.free.remove(index).1;
+        }
+    }
+}
+
+/// Packs rectangles into one square page of shelves
+#[derive(Clone, Debug)]
+pub struct ShelfPacker {
+    size: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// The top left corner of a free rectangle, None if there is no room
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width == 0 || height == 0 || width > self.size || height > self.size {
+            return None;
+        }
+        // The shortest shelf that fits without wasting too much height
+        let limit = (height as f32 * SHELF_WASTE).ceil() as u32;
+        let best = self
+            .shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| shelf.height >= height && shelf.largest_free() >= width)
+            .min_by_key(|(_, shelf)| shelf.height)
+            .map(|(index, shelf)| (index, shelf.height));
+
+        match best {
+            Some((index, shelf_height)) if shelf_height <= limit => self.allocate_in(index, width),
+            _ => self
+                .open_shelf(width, height)
+                .or_else(|| best.and_then(|(index, _)| self.allocate_in(index, width))),
+        }
+    }
+
+    /// Frees a rectangle returned by `allocate`
+    pub fn deallocate(&mut self, x: u32, y: u32, width: u32) {
+        let page_width = self.size;
+        if let Some(shelf) = self.shelves.iter_mut().find(|shelf| shelf.y == y) {
+            shelf.deallocate(x, width);
+        }
+        // Empty shelves at the bottom give their height back
+        while self
+            .shelves
+            .last()
+            .is_some_and(|shelf| shelf.is_empty(page_width))
+        {
+            self.shelves.pop();
+        }
+    }
+
+    /// True if nothing is allocated
+    pub fn is_empty(&self) -> bool {
+        self.shelves.iter().all(|shelf| shelf.is_empty(self.size))
+    }
+
+    /// Fraction of the page that is allocated
+    pub fn occupancy(&self) -> f32 {
+        let used: u64 = self
+            .shelves
+            .iter()
+            .map(|shelf| {
+                let free: u32 = shelf.free.iter().map(|&(_, width)| width).sum();
+                (self.size - free) as u64 * shelf.height as u64
+            })
+            .sum();
+        used as f32 / (self.size as f32 * self.size as f32)
+    }
+
+    fn allocate_in(&mut self, index: usize, width: u32) -> Option<(u32, u32)> {
+        let shelf = &mut self.shelves[index];
+        let x = shelf.allocate(width)?;
+        Some((x, shelf.y))
+    }
+
+    /// Adds a shelf below the others, or reuses an empty one, and allocates from it
+    fn open_shelf(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let page_width = self.size;
+        // An empty shelf in the middle can be taken over if it is tall enough
+        if let Some(index) = self
+            .shelves
+            .iter()
+            .position(|shelf| shelf.is_empty(page_width) && shelf.height >= height)
+        {
+            return self.allocate_in(index, width);
+        }
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if y + height > self.size {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            free: vec![(0, page_width)],
+        });
+        self.allocate_in(self.shelves.len() - 1, width)
+    }
+}
+
+/// Which region of which page each image is in, without any textures.
+/// Used by `TextureAtlas` at runtime and by `atlas_pack` offline
+#[derive(Clone, Debug)]
+pub struct AtlasLayout {
+    settings: AtlasSettings,
+    pages: Vec<ShelfPacker>,
+    regions: HashMap<AtlasHandle, AtlasRegion>,
+    next_handle: u32,
+}
+
+impl AtlasLayout {
+    pub fn new(settings: AtlasSettings) -> Self {
+        Self {
+            settings,
+            pages: Vec::new(),
+            regions: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    pub fn settings(&self) -> &AtlasSettings {
+        &self.settings
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Finds room for an image, on a new page if the existing ones are full
+    pub fn allocate(&mut self, width: u32, height: u32) -> Result<AtlasHandle, AtlasError> {
+        let size = self.settings.page_size;
+        let too_large = AtlasError::TooLarge {
+            width,
+            height,
+            page_size: size,
+        };
+        let padding = self.settings.padding.checked_mul(2);
+        let padded = |length: u32| {
+            padding
+                .and_then(|padding| length.checked_add(padding))
+                .filter(|&padded| length > 0 && padded <= size)
+        };
+        let (padded_width, padded_height) = match (padded(width), padded(height)) {
+            (Some(padded_width), Some(padded_height)) => (padded_width, padded_height),
+            _ => return Err(too_large),
+        };
+
+        let mut position = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page, packer)| {
+                packer
+                    .allocate(padded_width, padded_height)
+                    .map(|(x, y)| (page, x, y))
+            });
+        if position.is_none() {
+            if self.pages.len() >= self.settings.max_pages {
+                return Err(AtlasError::OutOfPages {
+                    max_pages: self.settings.max_pages,
+                });
+            }
+            let mut packer = ShelfPacker::new(size);
+            position = packer
+                .allocate(padded_width, padded_height)
+                .map(|(x, y)| (self.pages.len(), x, y));
+            self.pages.push(packer);
+        }
+        let (page, x, y) = position.expect("an image that fits a page fits an empty page");
+
+        let (x, y) = (x + self.settings.padding, y + self.settings.padding);
+        let handle = AtlasHandle(self.next_handle);
+        self.next_handle += 1;
+        self.regions.insert(
+            handle,
+            AtlasRegion {
+                page,
+                x,
+                y,
+                width,
+                height,
+                uv_min: [x as f32 / size as f32, y as f32 / size as f32],
+                uv_max: [
+                    (x + width) as f32 / size as f32,
+                    (y + height) as f32 / size as f32,
+                ],
+            },
+        );
+        Ok(handle)
+    }
+
+    /// Frees an image's region, returning where it was
+    pub fn remove(&mut self, handle: AtlasHandle) -> Option<AtlasRegion> {
+        let region = self.regions.remove(&handle)?;
+        let padding = self.settings.padding;
+        self.pages[region.page].deallocate(
+            region.x - padding,
+            region.y - padding,
+            region.width + padding * 2,
+        );
+        Some(region)
+    }
+
+    pub fn region(&self, handle: AtlasHandle) -> Option<AtlasRegion> {
+        self.regions.get(&handle).copied()
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (AtlasHandle, AtlasRegion)> + '_ {
+        self.regions
+            .iter()
+            .map(|(handle, region)| (*handle, *region))
+    }
+
+    /// Fraction of each page that is allocated, including padding
+    pub fn occupancy(&self) -> Vec<f32> {
+        self.pages.iter().map(ShelfPacker::occupancy).collect()
+    }
+}
+
+/// The image with `padding` pixels added on every side, copies of the nearest edge pixel when
+/// `extrude` is set and transparent otherwise
+pub fn pad_image(image: &image::RgbaImage, padding: u32, extrude: bool) -> image::RgbaImage {
+    let (width, height) = image.dimensions();
+    if padding == 0 {
+        return image.clone();
+    }
+    image::RgbaImage::from_fn(width + padding * 2, height + padding * 2, |x, y| {
+        let (source_x, source_y) = (x as i64 - padding as i64, y as i64 - padding as i64);
+        let inside =
+            (0..width as i64).contains(&source_x) && (0..height as i64).contains(&source_y);
+        if inside || extrude {
+            let source_x = source_x.clamp(0, width as i64 - 1) as u32;
+            let source_y = source_y.clamp(0, height as i64 - 1) as u32;
+            *image.get_pixel(source_x, source_y)
+        } else {
+            image::Rgba([0, 0, 0, 0])
+        }
+    })
+}
+
+/// An atlas whose pages are textures, images are uploaded as they are added
+pub struct TextureAtlas {
+    layout: AtlasLayout,
+    format: wgpu::TextureFormat,
+    pages: Vec<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl TextureAtlas {
+    /// `format` must be an 8 bit rgba format, such as `Rgba8UnormSrgb`
+    pub fn new(settings: AtlasSettings, format: wgpu::TextureFormat) -> Self {
+        Self {
+            layout: AtlasLayout::new(settings),
+            format,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Packs and uploads an image, creating a page if needed
+    pub fn add(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::RgbaImage,
+    ) -> Result<AtlasHandle, AtlasError> {
+        let handle = self.layout.allocate(image.width(), image.height())?;
+        let region = self.layout.region(handle).expect("just allocated");
+        while self.pages.len() < self.layout.page_count() {
+            self.pages.push(self.create_page(device));
+        }
+
+        let settings = self.layout.settings;
+        let padded = pad_image(image, settings.padding, settings.extrude);
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.pages[region.page].0,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.x - settings.padding,
+                    y: region.y - settings.padding,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &padded,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * padded.width()),
+                rows_per_image: std::num::NonZeroU32::new(padded.height()),
+            },
+            wgpu::Extent3d {
+                width: padded.width(),
+                height: padded.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// Frees an image's region for reuse, its pixels stay until overwritten
+    pub fn remove(&mut self, handle: AtlasHandle) -> Option<AtlasRegion> {
+        self.layout.remove(handle)
+    }
+
+    /// The page and texture coordinates of an image
+    pub fn region(&self, handle: AtlasHandle) -> Option<AtlasRegion> {
+        self.layout.region(handle)
+    }
+
+    pub fn layout(&self) -> &AtlasLayout {
+        &self.layout
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The texture of a page, to bind when drawing the images on it
+    pub fn page_view(&self, page: usize) -> Option<&wgpu::TextureView> {
+        self.pages.get(page).map(|(_, view)| view)
+    }
+
+    fn create_page(&self, device: &wgpu::Device) -> (wgpu::Texture, wgpu::TextureView) {
+        let size = self.layout.settings.page_size;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("atlas_page_texture"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(page_size: u32, padding: u32, max_pages: usize) -> AtlasSettings {
+        AtlasSettings {
+            page_size,
+            padding,
+            extrude: true,
+            max_pages,
+        }
+    }
+
+    #[test]
+    fn shelves_fill_left_to_right_then_downwards() {
+        let mut packer = ShelfPacker::new(100);
+        assert_eq!(packer.allocate(40, 10), Some((0, 0)));
+        assert_eq!(packer.allocate(40, 10), Some((40, 0)));
+        // Too wide for what is left of the first shelf
+        assert_eq!(packer.allocate(40, 10), Some((0, 10)));
+        // Too tall for either shelf
+        assert_eq!(packer.allocate(10, 20), Some((0, 20)));
+        assert_eq!(packer.shelves.len(), 3);
+        assert!(!packer.is_empty());
+    }
+
+    #[test]
+    fn tall_shelves_are_kept_for_tall_images() {
+        let mut packer = ShelfPacker::new(100);
+        assert_eq!(packer.allocate(10, 40), Some((0, 0)));
+        // A 40 pixel shelf wastes too much of a 10 pixel image while a new one can be opened
+        assert_eq!(packer.allocate(10, 10), Some((0, 40)));
+        assert_eq!(packer.allocate(90, 10), Some((10, 40)));
+        assert_eq!(packer.allocate(100, 50), Some((0, 50)));
+        // Once no shelf can be opened the tall one is better than nothing
+        assert_eq!(packer.allocate(10, 10), Some((10, 0)));
+    }
+
+    #[test]
+    fn rejects_what_cannot_fit() {
+        let mut packer = ShelfPacker::new(100);
+        assert_eq!(packer.allocate(0, 10), None);
+        assert_eq!(packer.allocate(10, 0), None);
+        assert_eq!(packer.allocate(101, 10), None);
+        assert_eq!(packer.allocate(10, 101), None);
+        assert_eq!(packer.allocate(100, 100), Some((0, 0)));
+        assert_eq!(packer.allocate(1, 1), None);
+        assert_eq!(packer.occupancy(), 1.0);
+    }
+
+    #[test]
+    fn freed_spans_merge_with_their_neighbours() {
+        let mut packer = ShelfPacker::new(100);
+        let xs: Vec<u32> = (0..3).map(|_| packer.allocate(30, 10).unwrap().0).collect();
+        assert_eq!(xs, [0, 30, 60]);
+        // A second shelf keeps the first from being popped once empty
+        assert_eq!(packer.allocate(100, 10), Some((0, 10)));
+
+        packer.deallocate(0, 0, 30);
+        packer.deallocate(60, 0, 30);
+        assert_eq!(packer.shelves[0].free, [(0, 30), (60, 40)]);
+        // Freeing the middle joins all three spans
+        packer.deallocate(30, 0, 30);
+        assert_eq!(packer.shelves[0].free, [(0, 100)]);
+        assert_eq!(packer.shelves.len(), 2);
+
+        // A freed span is reused
+        assert_eq!(packer.allocate(60, 10), Some((0, 0)));
+        packer.deallocate(0, 0, 60);
+        assert_eq!(packer.allocate(100, 10), Some((0, 0)));
+    }
+
+    #[test]
+    fn empty_bottom_shelves_are_popped() {
+        let mut packer = ShelfPacker::new(100);
+        packer.allocate(50, 10);
+        packer.allocate(50, 20);
+        packer.allocate(50, 30);
+        assert_eq!(packer.shelves.len(), 3);
+        // The middle shelf stays while the one below it is in use
+        packer.deallocate(0, 10, 50);
+        assert_eq!(packer.shelves.len(), 3);
+        packer.deallocate(0, 30, 50);
+        assert_eq!(packer.shelves.len(), 1);
+        packer.deallocate(0, 0, 50);
+        assert!(packer.shelves.is_empty());
+        assert!(packer.is_empty());
+        assert_eq!(packer.occupancy(), 0.0);
+        // The whole height is available again
+        assert_eq!(packer.allocate(100, 100), Some((0, 0)));
+    }
+
+    #[test]
+    fn empty_shelves_in_the_middle_are_taken_over() {
+        let mut packer = ShelfPacker::new(100);
+        packer.allocate(100, 20);
+        packer.allocate(100, 20);
+        packer.deallocate(0, 0, 100);
+        // Too short for the empty shelf to be a good fit, but it is used before opening another
+        assert_eq!(packer.allocate(50, 10), Some((0, 0)));
+        assert_eq!(packer.shelves.len(), 2);
+    }
+
+    #[test]
+    fn regions_are_inside_their_padding() {
+        let mut layout = AtlasLayout::new(settings(64, 2, 1));
+        let first = layout.allocate(10, 20).unwrap();
+        let second = layout.allocate(10, 20).unwrap();
+        let first = layout.region(first).unwrap();
+        let second = layout.region(second).unwrap();
+        assert_eq!((first.page, first.x, first.y), (0, 2, 2));
+        assert_eq!((first.width, first.height), (10, 20));
+        // The padding of both images lies between them
+        assert_eq!((second.x, second.y), (16, 2));
+        assert_eq!(first.uv_min, [2.0 / 64.0, 2.0 / 64.0]);
+        assert_eq!(first.uv_max, [12.0 / 64.0, 22.0 / 64.0]);
+        assert_eq!(second.uv_min[0], 16.0 / 64.0);
+        // Both padded 14x24 images are counted
+        assert_eq!(
+            layout.occupancy(),
+            vec![(28 * 24) as f32 / (64 * 64) as f32]
+        );
+    }
+
+    #[test]
+    fn too_large_counts_the_padding() {
+        let mut layout = AtlasLayout::new(settings(64, 2, 1));
+        assert!(layout.allocate(60, 60).is_ok());
+        for (width, height) in [(61, 10), (10, 61), (0, 10), (10, 0), (u32::MAX, 1)] {
+            assert!(matches!(
+                layout.allocate(width, height),
+                Err(AtlasError::TooLarge { page_size: 64, .. })
+            ));
+        }
+        // Padding so wide that doubling it overflows
+        let mut layout = AtlasLayout::new(settings(64, u32::MAX / 2 + 1, 1));
+        assert!(matches!(
+            layout.allocate(1, 1),
+            Err(AtlasError::TooLarge { .. })
+        ));
+        let mut layout = AtlasLayout::new(settings(64, u32::MAX / 2, 1));
+        assert!(matches!(
+            layout.allocate(2, 2),
+            Err(AtlasError::TooLarge { .. })
+        ));
+        assert!(layout.is_empty());
+    }
+
+    #[test]
+    fn pages_are_added_up_to_the_limit() {
+        let mut layout = AtlasLayout::new(settings(64, 2, 2));
+        let first = layout.allocate(60, 60).unwrap();
+        assert_eq!(layout.page_count(), 1);
+        let second = layout.allocate(40, 40).unwrap();
+        assert_eq!(layout.page_count(), 2);
+        assert_eq!(layout.region(second).unwrap().page, 1);
+        assert!(matches!(
+            layout.allocate(40, 40),
+            Err(AtlasError::OutOfPages { max_pages: 2 })
+        ));
+        assert_eq!(layout.len(), 2);
+
+        // Removing frees the room for the next image, and the handle is not reused
+        let removed = layout.remove(first).unwrap();
+        assert_eq!((removed.page, removed.x, removed.y), (0, 2, 2));
+        assert_eq!(layout.remove(first), None);
+        let third = layout.allocate(40, 40).unwrap();
+        assert_ne!(third, first);
+        assert_eq!(layout.region(third).unwrap().page, 0);
+        assert_eq!(layout.page_count(), 2);
+    }
+
+    fn two_pixels() -> image::RgbaImage {
+        let mut image = image::RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, image::Rgba([0, 255, 0, 255]));
+        image
+    }
+
+    #[test]
+    fn extruded_padding_repeats_the_edges() {
+        let image = two_pixels();
+        let padded = pad_image(&image, 2, true);
+        assert_eq!(padded.dimensions(), (6, 5));
+        for y in 0..5 {
+            for x in 0..6 {
+                let expected = if x < 3 {
+                    image.get_pixel(0, 0)
+                } else {
+                    image.get_pixel(1, 0)
+                };
+                assert_eq!(padded.get_pixel(x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_padding_is_cleared() {
+        let image = two_pixels();
+        let padded = pad_image(&image, 1, false);
+        assert_eq!(padded.dimensions(), (4, 3));
+        for (x, y, pixel) in padded.enumerate_pixels() {
+            let expected = match (x, y) {
+                (1, 1) => *image.get_pixel(0, 0),
+                (2, 1) => *image.get_pixel(1, 0),
+                _ => image::Rgba([0, 0, 0, 0]),
+            };
+            assert_eq!(*pixel, expected, "({}, {})", x, y);
+        }
+        assert_eq!(pad_image(&image, 0, false), image);
+    }
+}
@@ -0,0 +1,236 @@
+#![allow(dead_code)]
+//! Offline atlas packing.
+//! Every PNG in a folder is packed into page images, with a JSON or RON manifest giving the
+//! page and texture coordinates of each image by file name. Run with
+//! `windows_gui pack-atlas <folder> <manifest.json|manifest.ron> [page size]`.
+
+use crate::atlas::{pad_image, AtlasError, AtlasLayout, AtlasSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Ron,
+}
+
+impl ManifestFormat {
+    /// Chosen by the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "ron" => Some(Self::Ron),
+            _ => None,
+        }
+    }
+}
+
+/// Where each image of a packed atlas is
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AtlasManifest {
+    pub page_size: u32,
+    pub padding: u32,
+    /// File names of the page images, relative to the manifest
+    pub pages: Vec<String>,
+    /// Keyed by the image's file name without its extension
+    pub images: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+impl AtlasManifest {
+    pub fn to_string(&self, format: ManifestFormat) -> Result<String, AtlasError> {
+        match format {
+            ManifestFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|error| AtlasError::Manifest(error.to_string())),
+            ManifestFormat::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                    .map_err(|error| AtlasError::Manifest(error.to_string()))
+            }
+        }
+    }
+
+    pub fn parse(text: &str, format: ManifestFormat) -> Result<Self, AtlasError> {
+        match format {
+            ManifestFormat::Json => {
+                serde_json::from_str(text).map_err(|error| AtlasError::Manifest(error.to_string()))
+            }
+            ManifestFormat::Ron => {
+                ron::from_str(text).map_err(|error| AtlasError::Manifest(error.to_string()))
+            }
+        }
+    }
+
+    /// Reads a manifest, as JSON or RON depending on its extension
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, AtlasError> {
+        let path = path.as_ref();
+        let format = manifest_format(path)?;
+        Self::parse(&std::fs::read_to_string(path)?, format)
+    }
+}
+
+/// Page images and the manifest describing them
+pub struct PackedAtlas {
+    pub pages: Vec<image::RgbaImage>,
+    pub manifest: AtlasManifest,
+}
+
+impl PackedAtlas {
+    /// Writes the manifest to `manifest_path` and the pages next to it under the names it lists
+    pub fn save<P: AsRef<Path>>(&self, manifest_path: P) -> Result<(), AtlasError> {
+        let manifest_path = manifest_path.as_ref();
+        let format = manifest_format(manifest_path)?;
+        let folder = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+        for (page, name) in self.pages.iter().zip(&self.manifest.pages) {
+            page.save(folder.join(name))?;
+        }
+        std::fs::write(manifest_path, self.manifest.to_string(format)?)?;
+        Ok(())
+    }
+}
+
+/// Packs named images, largest first so the shelves are filled evenly.
+/// Pages are named `<page_prefix>_<index>.png`
+pub fn pack_images(
+    mut images: Vec<(String, image::RgbaImage)>,
+    settings: AtlasSettings,
+    page_prefix: &str,
+) -> Result<PackedAtlas, AtlasError> {
+    images.sort_by(|(a_name, a), (b_name, b)| {
+        (b.height(), b.width())
+            .cmp(&(a.height(), a.width()))
+            .then_with(|| a_name.cmp(b_name))
+    });
+
+    let mut layout = AtlasLayout::new(settings);
+    let mut pages: Vec<image::RgbaImage> = Vec::new();
+    let mut entries = BTreeMap::new();
+    for (name, image) in &images {
+        let handle = layout.allocate(image.width(), image.height())?;
+        let region = layout.region(handle).expect("just allocated");
+        while pages.len() < layout.page_count() {
+            pages.push(image::RgbaImage::new(
+                settings.page_size,
+                settings.page_size,
+            ));
+        }
+        let padded = pad_image(image, settings.padding, settings.extrude);
+        image::imageops::replace(
+            &mut pages[region.page],
+            &padded,
+            (region.x - settings.padding) as i64,
+            (region.y - settings.padding) as i64,
+        );
+        entries.insert(
+            name.clone(),
+            ManifestEntry {
+                page: region.page,
+                x: region.x,
+                y: region.y,
+                width: region.width,
+                height: region.height,
+                uv_min: region.uv_min,
+                uv_max: region.uv_max,
+            },
+        );
+    }
+
+    Ok(PackedAtlas {
+        manifest: AtlasManifest {
+            page_size: settings.page_size,
+            padding: settings.padding,
+            pages: (0..pages.len())
+                .map(|index| format!("{}_{}.png", page_prefix, index))
+                .collect(),
+            images: entries,
+        },
+        pages,
+    })
+}
+
+/// Every PNG directly inside `folder`, named by file name without the extension
+pub fn load_folder<P: AsRef<Path>>(
+    folder: P,
+) -> Result<Vec<(String, image::RgbaImage)>, AtlasError> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok((name, image::open(&path)?.into_rgba8()))
+        })
+        .collect()
+}
+
+/// Packs a folder of PNGs and saves the result, the pages are named after the manifest
+pub fn pack_folder<P: AsRef<Path>, Q: AsRef<Path>>(
+    folder: P,
+    manifest_path: Q,
+    settings: AtlasSettings,
+) -> Result<AtlasManifest, AtlasError> {
+    let manifest_path = manifest_path.as_ref();
+    manifest_format(manifest_path)?;
+    let prefix = manifest_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "atlas".to_string());
+    let packed = pack_images(load_folder(folder)?, settings, &prefix)?;
+    packed.save(manifest_path)?;
+    Ok(packed.manifest)
+}
+
+/// The `pack-atlas` command, `args` are those after the command name
+pub fn run(args: &[String]) -> Result<(), AtlasError> {
+    let (folder, manifest_path) = match args {
+        [folder, manifest_path, ..] => (folder, manifest_path),
+        _ => {
+            return Err(AtlasError::InvalidArguments(
+                "usage: pack-atlas <folder> <manifest.json|manifest.ron> [page size]".to_string(),
+            ))
+        }
+    };
+    let mut settings = AtlasSettings {
+        // Offline atlases are only limited by the texture size
+        max_pages: usize::MAX,
+        ..AtlasSettings::default()
+    };
+    if let Some(page_size) = args.get(2) {
+        settings.page_size = page_size.parse().map_err(|_| {
+            AtlasError::InvalidArguments(format!("invalid page size {}", page_size))
+        })?;
+    }
+    let manifest = pack_folder(folder, manifest_path, settings)?;
+    println!(
+        "Packed {} images into {} pages",
+        manifest.images.len(),
+        manifest.pages.len()
+    );
+    Ok(())
+}
+
+fn manifest_format(path: &Path) -> Result<ManifestFormat, AtlasError> {
+    ManifestFormat::from_path(path).ok_or_else(|| {
+        AtlasError::Manifest(format!("{} should end in .json or .ron", path.display()))
+    })
+}
@@ -0,0 +1,591 @@
+#![allow(dead_code)]
+//! Copying and pasting text, images and data in the application's own formats.
+//! The clipboard is opened for as long as a `Clipboard` lives, and the first thing set after
+//! opening replaces everything on it, so several formats of the same content can be offered
+//! together.
+//!
+//! Images are written both as PNG, which keeps the alpha channel for the applications that read
+//! it, and as a `BITMAPV5HEADER` DIB, which Windows converts to the older bitmap formats. Reading
+//! prefers PNG and falls back to a DIB, which is parsed here rather than through GDI.
+
+use crate::utils::{read_i32, read_u16, read_u32};
+#[cfg(windows)]
+use crate::winapi_utils::{
+    close_clipboard, empty_clipboard, get_clipboard_data, is_clipboard_format_available,
+    open_clipboard, register_clipboard_format, set_clipboard_data,
+};
+#[cfg(windows)]
+use image::ImageEncoder;
+use image::RgbaImage;
+use std::fmt;
+#[cfg(windows)]
+use windows::Win32::Foundation::HWND;
+#[cfg(windows)]
+use windows::Win32::System::SystemServices::{CF_DIB, CF_DIBV5, CF_UNICODETEXT};
+
+/// Times opening is tried while another application has the clipboard open
+#[cfg(windows)]
+const OPEN_ATTEMPTS: u32 = 5;
+#[cfg(windows)]
+const OPEN_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(10);
+
+const BITMAPINFOHEADER_SIZE: usize = 40;
+const BITMAPV5HEADER_SIZE: usize = 124;
+// biCompression
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+/// bV5CSType of a bitmap in sRGB, 'sRGB' as a little endian u32
+const LCS_SRGB: u32 = 0x7352_4742;
+/// bV5Intent for photographs and images
+const LCS_GM_IMAGES: u32 = 4;
+/// Largest width or height read from a DIB, far past any real image but small enough that the
+/// pixel buffer cannot be too large to allocate
+const MAX_DIB_SIZE: u32 = 1 << 15;
+/// Channel masks of the BGRA pixels written
+const WRITE_MASKS: [u32; 4] = [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000];
+
+#[derive(Debug)]
+pub enum ClipboardError {
+    /// The clipboard could not be opened, read or written
+    #[cfg(windows)]
+    Window(windows::core::Error),
+    Image(image::ImageError),
+    /// A bitmap on the clipboard is malformed or in a format other than 24 or 32 bits a pixel
+    InvalidDib(String),
+    /// Data in a custom format is shorter than the length it starts with
+    InvalidData,
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(windows)]
+            Self::Window(error) => write!(f, "{}", error),
+            Self::Image(error) => write!(f, "{}", error),
+            Self::InvalidDib(error) => write!(f, "invalid bitmap on the clipboard: {}", error),
+            Self::InvalidData => write!(f, "truncated data on the clipboard"),
+        }
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for ClipboardError {
+    fn from(error: windows::core::Error) -> Self {
+        Self::Window(error)
+    }
+}
+
+impl From<image::ImageError> for ClipboardError {
+    fn from(error: image::ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+/// A format registered by name, the same name gives the same format in every process
+#[cfg(windows)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClipboardFormat(u32);
+
+#[cfg(windows)]
+impl ClipboardFormat {
+    pub fn register(name: &str) -> Result<Self, ClipboardError> {
+        Ok(Self(register_clipboard_format(name)?))
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+/// The clipboard while it is open, closed when dropped.
+/// Nothing else can use the clipboard meanwhile, so it should be dropped as soon as possible.
+#[cfg(windows)]
+#[derive(Debug)]
+pub struct Clipboard {
+    // Set once the clipboard has been emptied for the first format set
+    owned: bool,
+}
+
+#[cfg(windows)]
+impl Clipboard {
+    /// Opens the clipboard for a window, which becomes its owner once something is set.
+    /// Another application may have it open for a moment, so opening is retried briefly.
+    pub fn open(owner: HWND) -> Result<Self, ClipboardError> {
+        let mut attempt = 1;
+        loop {
+            match open_clipboard(owner) {
+                Ok(()) => return Ok(Self { owned: false }),
+                Err(error) if attempt >= OPEN_ATTEMPTS => return Err(error.into()),
+                Err(_) => {
+                    attempt += 1;
+                    std::thread::sleep(OPEN_RETRY_DELAY);
+                }
+            }
+        }
+    }
+
+    /// Removes everything from the clipboard
+    pub fn clear(&mut self) -> Result<(), ClipboardError> {
+        empty_clipboard()?;
+        self.owned = true;
+        Ok(())
+    }
+
+    /// The clipboard is emptied before the first format is set, later formats are added to it
+    fn set(&mut self, format: u32, bytes: &[u8]) -> Result<(), ClipboardError> {
+        if !self.owned {
+            self.clear()?;
+        }
+        Ok(set_clipboard_data(format, bytes)?)
+    }
+
+    fn get(&self, format: u32) -> Result<Option<Vec<u8>>, ClipboardError> {
+        if !is_clipboard_format_available(format) {
+            return Ok(None);
+        }
+        Ok(Some(get_clipboard_data(format)?))
+    }
+
+    pub fn has_text(&self) -> bool {
+        is_clipboard_format_available(CF_UNICODETEXT.0)
+    }
+
+    /// None if there is no text on the clipboard
+    pub fn text(&self) -> Result<Option<String>, ClipboardError> {
+        Ok(self.get(CF_UNICODETEXT.0)?.map(|bytes| decode_text(&bytes)))
+    }
+
+    pub fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.set(CF_UNICODETEXT.0, &encode_text(text))
+    }
+
+    pub fn has_image(&self) -> bool {
+        let png = png_format().map(|format| is_clipboard_format_available(format.0));
+        png.unwrap_or(false) || is_clipboard_format_available(CF_DIB.0)
+    }
+
+    /// None if there is no image on the clipboard
+    pub fn image(&self) -> Result<Option<RgbaImage>, ClipboardError> {
+        if let Ok(format) = png_format() {
+            if let Some(png) = self.get(format.0)? {
+                let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)?;
+                return Ok(Some(image.into_rgba8()));
+            }
+        }
+        // Windows converts between the bitmap formats, so CF_DIBV5 is there for any bitmap
+        match self.get(CF_DIBV5.0)? {
+            Some(dib) => Ok(Some(decode_dib(&dib)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_image(&mut self, image: &RgbaImage) -> Result<(), ClipboardError> {
+        self.set(CF_DIBV5.0, &encode_dib(image))?;
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png).write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            image::ColorType::Rgba8,
+        )?;
+        self.set(png_format()?.0, &png)
+    }
+
+    pub fn has_format(&self, format: ClipboardFormat) -> bool {
+        is_clipboard_format_available(format.0)
+    }
+
+    /// The bytes set in a custom format, None if the clipboard has none in that format
+    pub fn custom(&self, format: ClipboardFormat) -> Result<Option<Vec<u8>>, ClipboardError> {
+        match self.get(format.0)? {
+            Some(bytes) => Ok(Some(decode_custom(&bytes)?.to_vec())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_custom(
+        &mut self,
+        format: ClipboardFormat,
+        bytes: &[u8],
+    ) -> Result<(), ClipboardError> {
+        self.set(format.0, &encode_custom(bytes))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Clipboard {
+    fn drop(&mut self) {
+        close_clipboard();
+    }
+}
+
+/// The format browsers, image editors and Office use for images with transparency
+#[cfg(windows)]
+fn png_format() -> Result<ClipboardFormat, ClipboardError> {
+    ClipboardFormat::register("PNG")
+}
+
+/// `CF_UNICODETEXT`: null terminated UTF-16
+pub fn encode_text(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Text up to the first null, the clipboard memory can be larger than the text
+pub fn decode_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Clipboard memory is allocated in blocks and reports its rounded up size, so custom data is
+/// stored after its length to be read back exactly
+pub fn encode_custom(bytes: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + bytes.len());
+    data.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    data.extend_from_slice(bytes);
+    data
+}
+
+pub fn decode_custom(data: &[u8]) -> Result<&[u8], ClipboardError> {
+    let length = data
+        .get(..8)
+        .and_then(|length| length.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(ClipboardError::InvalidData)?;
+    usize::try_from(length)
+        .ok()
+        .and_then(|length| data.get(8..length.checked_add(8)?))
+        .ok_or(ClipboardError::InvalidData)
+}
+
+/// A `BITMAPV5HEADER` followed by bottom up, 32 bit BGRA rows with the alpha channel described
+/// by the masks
+pub fn encode_dib(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let image_size = width as usize * height as usize * 4;
+    let mut dib = Vec::with_capacity(BITMAPV5HEADER_SIZE + image_size);
+    dib.extend_from_slice(&(BITMAPV5HEADER_SIZE as u32).to_le_bytes());
+    dib.extend_from_slice(&(width as i32).to_le_bytes());
+    // A positive height is bottom up, which more applications read correctly than top down
+    dib.extend_from_slice(&(height as i32).to_le_bytes());
+    // Planes and bits per pixel
+    dib.extend_from_slice(&1u16.to_le_bytes());
+    dib.extend_from_slice(&32u16.to_le_bytes());
+    dib.extend_from_slice(&BI_BITFIELDS.to_le_bytes());
+    dib.extend_from_slice(&(image_size as u32).to_le_bytes());
+    // Pixels per metre, colours used and colours important
+    dib.extend_from_slice(&[0; 16]);
+    for mask in WRITE_MASKS {
+        dib.extend_from_slice(&mask.to_le_bytes());
+    }
+    dib.extend_from_slice(&LCS_SRGB.to_le_bytes());
+    // Endpoints and gamma, unused with sRGB
+    dib.extend_from_slice(&[0; 48]);
+    dib.extend_from_slice(&LCS_GM_IMAGES.to_le_bytes());
+    // Profile offset, profile size and reserved
+    dib.extend_from_slice(&[0; 12]);
+
+    for row in image.rows().rev() {
+        for pixel in row {
+            dib.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+    dib
+}
+
+/// A packed DIB as `CF_DIB` or `CF_DIBV5` hold it: any of the `BITMAPINFOHEADER` versions,
+/// then the masks or colour table, then the pixels. Reads 24 and 32 bit uncompressed pixels
+/// and 32 bit pixels with channel masks, top down or bottom up.
+pub fn decode_dib(dib: &[u8]) -> Result<RgbaImage, ClipboardError> {
+    let invalid = |message: &str| ClipboardError::InvalidDib(message.to_string());
+    let truncated = || invalid("truncated header");
+    let header_size = read_u32(dib, 0).ok_or_else(truncated)? as usize;
+    if header_size < BITMAPINFOHEADER_SIZE {
+        return Err(invalid("unsupported header"));
+    }
+    let width = read_i32(dib, 4).ok_or_else(truncated)?;
+    let height = read_i32(dib, 8).ok_or_else(truncated)?;
+    let bit_count = read_u16(dib, 14).ok_or_else(truncated)?;
+    let compression = read_u32(dib, 16).ok_or_else(truncated)?;
+    let colours_used = read_u32(dib, 32).ok_or_else(truncated)? as usize;
+    if width <= 0 || height == 0 {
+        return Err(invalid("empty bitmap"));
+    }
+    let top_down = height < 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    if width > MAX_DIB_SIZE || height > MAX_DIB_SIZE {
+        return Err(invalid("bitmap too large"));
+    }
+
+    let mut offset = header_size;
+    let masks = match (compression, bit_count) {
+        (BI_RGB, 24) => None,
+        // The fourth byte is reserved, but used for alpha by some applications, see below
+        (BI_RGB, 32) => Some(WRITE_MASKS),
+        (BI_BITFIELDS, 32) => {
+            // Later headers hold the masks, the first follows itself with the colour ones
+            let masks_offset = if header_size > BITMAPINFOHEADER_SIZE {
+                BITMAPINFOHEADER_SIZE
+            } else {
+                offset += 12;
+                header_size
+            };
+            let mask = |index: usize| read_u32(dib, masks_offset + index * 4);
+            let alpha = if header_size >= BITMAPINFOHEADER_SIZE + 16 {
+                mask(3).ok_or_else(truncated)?
+            } else {
+                0
+            };
+            Some([
+                mask(0).ok_or_else(truncated)?,
+                mask(1).ok_or_else(truncated)?,
+                mask(2).ok_or_else(truncated)?,
+                alpha,
+            ])
+        }
+        _ => {
+            return Err(invalid(&format!(
+                "{} bit pixels with compression {} are not supported",
+                bit_count, compression
+            )))
+        }
+    };
+    // A colour table is allowed for high colour bitmaps as a palette hint, and skipped
+    let offset = colours_used
+        .checked_mul(4)
+        .and_then(|table| offset.checked_add(table))
+        .ok_or_else(|| invalid("colour table too large"))?;
+
+    let stride = (width as usize * bit_count as usize).div_ceil(32) * 4;
+    let pixels = stride
+        .checked_mul(height as usize)
+        .and_then(|size| offset.checked_add(size))
+        .and_then(|end| dib.get(offset..end))
+        .ok_or_else(|| invalid("truncated pixels"))?;
+
+    let mut image = RgbaImage::new(width, height);
+    for (row_index, row) in pixels.chunks_exact(stride).enumerate() {
+        let y = if top_down {
+            row_index as u32
+        } else {
+            height - 1 - row_index as u32
+        };
+        for x in 0..width {
+            let pixel = match masks {
+                None => {
+                    let bgr = &row[x as usize * 3..x as usize * 3 + 3];
+                    [bgr[2], bgr[1], bgr[0], 255]
+                }
+                Some(masks) => {
+                    let start = x as usize * 4;
+                    let value = u32::from_le_bytes(row[start..start + 4].try_into().unwrap());
+                    [
+                        channel(value, masks[0]),
+                        channel(value, masks[1]),
+                        channel(value, masks[2]),
+                        channel(value, masks[3]),
+                    ]
+                }
+            };
+            image.put_pixel(x, y, image::Rgba(pixel));
+        }
+    }
+
+    // Uncompressed 32 bit pixels leave the fourth byte reserved, most applications fill it with
+    // zero, which would make the image invisible
+    let alpha_mask = masks.map_or(0, |masks| masks[3]);
+    if alpha_mask == 0 || (compression == BI_RGB && image.pixels().all(|pixel| pixel[3] == 0)) {
+        for pixel in image.pixels_mut() {
+            pixel[3] = 255;
+        }
+    }
+    Ok(image)
+}
+
+/// A channel picked out with a mask and scaled to 8 bits, 0 for an empty mask
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = (value & mask) >> mask.trailing_zeros();
+    let max = mask >> mask.trailing_zeros();
+    if max == 0xff {
+        bits as u8
+    } else {
+        (bits as u64 * 255 / max as u64) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `BITMAPINFOHEADER` DIB, followed by `extra` such as masks, then the pixel rows
+    fn info_dib(
+        width: i32,
+        height: i32,
+        bit_count: u16,
+        compression: u32,
+        extra: &[u32],
+        rows: &[&[u8]],
+    ) -> Vec<u8> {
+        let mut dib = Vec::new();
+        dib.extend_from_slice(&(BITMAPINFOHEADER_SIZE as u32).to_le_bytes());
+        dib.extend_from_slice(&width.to_le_bytes());
+        dib.extend_from_slice(&height.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&bit_count.to_le_bytes());
+        dib.extend_from_slice(&compression.to_le_bytes());
+        dib.resize(BITMAPINFOHEADER_SIZE, 0);
+        for value in extra {
+            dib.extend_from_slice(&value.to_le_bytes());
+        }
+        for row in rows {
+            dib.extend_from_slice(row);
+        }
+        dib
+    }
+
+    fn rgba(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+        image.get_pixel(x, y).0
+    }
+
+    #[test]
+    fn round_trips_through_a_dib() {
+        let image = RgbaImage::from_fn(3, 2, |x, y| {
+            image::Rgba([x as u8 * 80, y as u8 * 200, 30, 64 + x as u8])
+        });
+        let dib = encode_dib(&image);
+        assert_eq!(dib.len(), BITMAPV5HEADER_SIZE + 3 * 2 * 4);
+        assert_eq!(decode_dib(&dib).unwrap(), image);
+    }
+
+    #[test]
+    fn reads_24_bit_rows_with_padding() {
+        // Two pixels of three bytes are padded to eight, bottom row first
+        let bottom: &[u8] = &[255, 0, 0, 0, 255, 0, 0, 0];
+        let top: &[u8] = &[0, 0, 255, 10, 20, 30, 0, 0];
+        let image = decode_dib(&info_dib(2, 2, 24, BI_RGB, &[], &[bottom, top])).unwrap();
+        assert_eq!(rgba(&image, 0, 1), [0, 0, 255, 255]);
+        assert_eq!(rgba(&image, 1, 1), [0, 255, 0, 255]);
+        assert_eq!(rgba(&image, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(rgba(&image, 1, 0), [30, 20, 10, 255]);
+    }
+
+    #[test]
+    fn reads_32_bit_rgb_alpha_only_when_set() {
+        // The reserved byte left at zero is opaque
+        let image = decode_dib(&info_dib(1, 1, 32, BI_RGB, &[], &[&[1, 2, 3, 0]])).unwrap();
+        assert_eq!(rgba(&image, 0, 0), [3, 2, 1, 255]);
+        // And used as alpha when any pixel sets it
+        let rows: &[&[u8]] = &[&[1, 2, 3, 0], &[4, 5, 6, 128]];
+        let image = decode_dib(&info_dib(1, 2, 32, BI_RGB, &[], rows)).unwrap();
+        assert_eq!(rgba(&image, 0, 1), [3, 2, 1, 0]);
+        assert_eq!(rgba(&image, 0, 0), [6, 5, 4, 128]);
+    }
+
+    #[test]
+    fn reads_masks_after_an_info_header() {
+        // RGBA byte order, without an alpha mask so every pixel is opaque
+        let masks = [0x0000_00ff, 0x0000_ff00, 0x00ff_0000];
+        let dib = info_dib(1, 1, 32, BI_BITFIELDS, &masks, &[&[10, 20, 30, 40]]);
+        let image = decode_dib(&dib).unwrap();
+        assert_eq!(rgba(&image, 0, 0), [10, 20, 30, 255]);
+
+        // Masks narrower than a byte are scaled up
+        let masks = [0x0000_001f, 0x0000_03e0, 0x0000_7c00];
+        let dib = info_dib(1, 1, 32, BI_BITFIELDS, &masks, &[&[0x1f, 0, 0, 0]]);
+        assert_eq!(rgba(&decode_dib(&dib).unwrap(), 0, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn reads_top_down_and_bottom_up() {
+        let rows: &[&[u8]] = &[&[0, 0, 255, 255], &[255, 0, 0, 255]];
+        let bottom_up = decode_dib(&info_dib(1, 2, 32, BI_RGB, &[], rows)).unwrap();
+        assert_eq!(rgba(&bottom_up, 0, 0), [0, 0, 255, 255]);
+        let top_down = decode_dib(&info_dib(1, -2, 32, BI_RGB, &[], rows)).unwrap();
+        assert_eq!(rgba(&top_down, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(rgba(&top_down, 0, 1), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_malformed_dibs() {
+        let invalid = |dib: &[u8]| matches!(decode_dib(dib), Err(ClipboardError::InvalidDib(_)));
+        let pixel: &[&[u8]] = &[&[0; 4]];
+        let dib = info_dib(1, 1, 32, BI_RGB, &[], pixel);
+        assert!(!invalid(&dib));
+        assert!(invalid(&[]));
+        assert!(invalid(&dib[..20]));
+        assert!(invalid(&dib[..dib.len() - 1]));
+        // Masks cut off
+        assert!(invalid(&info_dib(1, 1, 32, BI_BITFIELDS, &[0xff], &[])));
+
+        assert!(invalid(&info_dib(0, 1, 32, BI_RGB, &[], pixel)));
+        assert!(invalid(&info_dib(-1, 1, 32, BI_RGB, &[], pixel)));
+        assert!(invalid(&info_dib(1, 0, 32, BI_RGB, &[], pixel)));
+        assert!(invalid(&info_dib(
+            i32::MAX,
+            i32::MAX,
+            32,
+            BI_RGB,
+            &[],
+            pixel
+        )));
+        assert!(invalid(&info_dib(1, i32::MIN, 32, BI_RGB, &[], pixel)));
+        assert!(invalid(&info_dib(1, 1, 8, BI_RGB, &[], pixel)));
+
+        let mut dib = info_dib(1, 1, 32, BI_RGB, &[], pixel);
+        dib[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(invalid(&dib));
+        let mut dib = info_dib(1, 1, 32, BI_RGB, &[], pixel);
+        dib[0..4].copy_from_slice(&12u32.to_le_bytes());
+        assert!(invalid(&dib));
+    }
+
+    #[test]
+    fn custom_data_keeps_its_length() {
+        let data = encode_custom(b"data");
+        assert_eq!(decode_custom(&data).unwrap(), b"data");
+        // The clipboard rounds its memory up
+        let mut padded = data.clone();
+        padded.resize(32, 0xcc);
+        assert_eq!(decode_custom(&padded).unwrap(), b"data");
+        assert_eq!(decode_custom(&encode_custom(&[])).unwrap(), b"");
+
+        assert!(matches!(
+            decode_custom(&data[..9]),
+            Err(ClipboardError::InvalidData)
+        ));
+        assert!(matches!(
+            decode_custom(&[1, 0]),
+            Err(ClipboardError::InvalidData)
+        ));
+        let mut huge = u64::MAX.to_le_bytes().to_vec();
+        huge.push(0);
+        assert!(matches!(
+            decode_custom(&huge),
+            Err(ClipboardError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn text_is_null_terminated_utf16() {
+        let bytes = encode_text("héllo 🦀");
+        assert_eq!(&bytes[bytes.len() - 2..], &[0, 0]);
+        assert_eq!(decode_text(&bytes), "héllo 🦀");
+        // Anything after the null is ignored, as is an odd byte
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[b'x', 0, 7]);
+        assert_eq!(decode_text(&padded), "héllo 🦀");
+        assert_eq!(decode_text(&[b'h', 0, b'i', 0]), "hi");
+        assert_eq!(decode_text(&[]), "");
+    }
+}
@@ -0,0 +1,196 @@
+#![allow(dead_code)]
+//! Points in the three coordinate spaces windows messages use, so one cannot be passed where
+//! another is expected:
+//! - screen, from the top left of the primary monitor (`WM_NCHITTEST`, `GetCursorPos`)
+//! - window, from the top left of the outer window rect including any resize border
+//! - client, from the top left of the client area (mouse messages, the swapchain)
+//!
+//! Each space has a physical point in pixels and a logical point in pixels at 96 DPI, see `dpi`.
+//! Converting between spaces needs the window's rects, so those conversions are on `Window`.
+
+macro_rules! coordinate_space {
+    ($(#[$doc:meta])* $physical:ident, $logical:ident) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+        pub struct $physical {
+            pub x: i32,
+            pub y: i32,
+        }
+
+        $(#[$doc])*
+        /// In pixels at 96 DPI
+        #[derive(Copy, Clone, Debug, Default, PartialEq)]
+        pub struct $logical {
+            pub x: f64,
+            pub y: f64,
+        }
+
+        impl $physical {
+            pub fn new(x: i32, y: i32) -> Self {
+                Self { x, y }
+            }
+
+            /// The same point moved by an offset in pixels
+            pub fn offset(self, dx: i32, dy: i32) -> Self {
+                Self::new(self.x + dx, self.y + dy)
+            }
+
+            /// A scale factor of 0 is treated as 1
+            pub fn to_logical(self, scale_factor: f64) -> $logical {
+                let scale_factor = crate::dpi::valid_scale_factor(scale_factor);
+                $logical {
+                    x: self.x as f64 / scale_factor,
+                    y: self.y as f64 / scale_factor,
+                }
+            }
+        }
+
+        impl $logical {
+            pub fn new(x: f64, y: f64) -> Self {
+                Self { x, y }
+            }
+
+            /// Rounded to whole pixels
+            pub fn to_physical(self, scale_factor: f64) -> $physical {
+                let scale_factor = crate::dpi::valid_scale_factor(scale_factor);
+                $physical {
+                    x: (self.x * scale_factor).round() as i32,
+                    y: (self.y * scale_factor).round() as i32,
+                }
+            }
+        }
+    };
+}
+
+coordinate_space!(
+    /// A point relative to the top left of the primary monitor
+    ScreenPoint,
+    LogicalScreenPoint
+);
+coordinate_space!(
+    /// A point relative to the top left of the outer window rect
+    WindowPoint,
+    LogicalWindowPoint
+);
+coordinate_space!(
+    /// A point relative to the top left of the client area
+    ClientPoint,
+    LogicalClientPoint
+);
+
+impl ClientPoint {
+    /// The pixel under the point in a client area of `size`, None outside of it such as while
+    /// the mouse is captured and dragged past the edge
+    pub fn to_pixel(self, size: crate::dpi::PhysicalSize) -> Option<(u32, u32)> {
+        let inside = self.x >= 0
+            && self.y >= 0
+            && (self.x as u32) < size.width
+            && (self.y as u32) < size.height;
+        inside.then_some((self.x as u32, self.y as u32))
+    }
+}
+
+/// A rectangle on the screen, such as a monitor's bounds or a window's outer rect
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScreenRect {
+    /// Top left corner
+    pub position: ScreenPoint,
+    pub size: crate::dpi::PhysicalSize,
+}
+
+impl ScreenRect {
+    pub fn new(position: ScreenPoint, size: crate::dpi::PhysicalSize) -> Self {
+        Self { position, size }
+    }
+
+    /// The corner opposite `position`, just outside the rectangle
+    pub fn bottom_right(&self) -> ScreenPoint {
+        self.position
+            .offset(self.size.width as i32, self.size.height as i32)
+    }
+
+    pub fn contains(&self, point: ScreenPoint) -> bool {
+        let end = self.bottom_right();
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < end.x
+            && point.y < end.y
+    }
+
+    pub fn centre(&self) -> ScreenPoint {
+        self.position
+            .offset(self.size.width as i32 / 2, self.size.height as i32 / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpi::PhysicalSize;
+
+    #[test]
+    fn pixels_inside_the_client_area() {
+        let size = PhysicalSize::new(800, 600);
+        assert_eq!(ClientPoint::new(0, 0).to_pixel(size), Some((0, 0)));
+        assert_eq!(ClientPoint::new(799, 599).to_pixel(size), Some((799, 599)));
+        // Captured drags report points past every edge
+        for point in [
+            (800, 10),
+            (10, 600),
+            (-1, 10),
+            (10, -1),
+            (i32::MIN, i32::MIN),
+        ] {
+            assert_eq!(ClientPoint::new(point.0, point.1).to_pixel(size), None);
+        }
+        assert_eq!(
+            ClientPoint::new(0, 0).to_pixel(PhysicalSize::new(0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn rects_contain_their_top_left_but_not_bottom_right() {
+        let rect = ScreenRect::new(ScreenPoint::new(-100, 50), PhysicalSize::new(200, 100));
+        assert_eq!(rect.bottom_right(), ScreenPoint::new(100, 150));
+        assert!(rect.contains(ScreenPoint::new(-100, 50)));
+        assert!(rect.contains(ScreenPoint::new(99, 149)));
+        assert!(!rect.contains(ScreenPoint::new(100, 149)));
+        assert!(!rect.contains(ScreenPoint::new(99, 150)));
+        assert!(!rect.contains(ScreenPoint::new(-101, 50)));
+        assert!(!rect.contains(ScreenPoint::new(-100, 49)));
+        let empty = ScreenRect::new(ScreenPoint::new(5, 5), PhysicalSize::new(0, 0));
+        assert!(!empty.contains(ScreenPoint::new(5, 5)));
+    }
+
+    #[test]
+    fn centres_round_towards_the_top_left() {
+        let rect = ScreenRect::new(ScreenPoint::new(-100, 50), PhysicalSize::new(200, 100));
+        assert_eq!(rect.centre(), ScreenPoint::new(0, 100));
+        let odd = ScreenRect::new(ScreenPoint::new(0, 0), PhysicalSize::new(3, 1));
+        assert_eq!(odd.centre(), ScreenPoint::new(1, 0));
+        assert!(odd.contains(odd.centre()));
+    }
+
+    #[test]
+    fn points_round_trip_through_logical() {
+        for scale_factor in [1.0, 1.25, 1.5, 1.75, 2.0, 3.0] {
+            for (x, y) in [(0, 0), (1, -1), (-250, 333), (1919, 1081)] {
+                let point = ClientPoint::new(x, y);
+                let back = point.to_logical(scale_factor).to_physical(scale_factor);
+                assert_eq!(back, point, "({}, {}) at {}", x, y, scale_factor);
+            }
+        }
+        let logical = ScreenPoint::new(300, -150).to_logical(1.5);
+        assert_eq!(logical, LogicalScreenPoint::new(200.0, -100.0));
+        // A scale factor of 0 is treated as 1
+        assert_eq!(
+            WindowPoint::new(7, 9).to_logical(0.0),
+            LogicalWindowPoint::new(7.0, 9.0)
+        );
+        assert_eq!(
+            LogicalClientPoint::new(2.0, -2.0).to_physical(1.25),
+            ClientPoint::new(3, -3)
+        );
+    }
+}
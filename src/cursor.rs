@@ -0,0 +1,140 @@
+#![allow(dead_code)]
+//! The mouse cursor shown over a window and how it is held by the window.
+//! Over the client area the window shows its chosen cursor, over the borders the resize arrows
+//! that match the hit test.
+
+use crate::icon::to_bgra;
+use crate::winapi_utils::{create_icon_from_bgra, destroy_cursor, system_cursor};
+use windows::core::{Result, PCWSTR};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+/// Cursors provided by the system
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CursorIcon {
+    Arrow,
+    /// I-beam over editable text
+    Text,
+    /// A link or button
+    Hand,
+    Move,
+    Crosshair,
+    /// Busy and not taking input
+    Wait,
+    /// Busy in the background but still taking input
+    Progress,
+    Help,
+    NotAllowed,
+    /// Left and right
+    ResizeHorizontal,
+    /// Up and down
+    ResizeVertical,
+    /// Top left and bottom right
+    ResizeNwse,
+    /// Top right and bottom left
+    ResizeNesw,
+}
+
+impl CursorIcon {
+    fn resource(self) -> PCWSTR {
+        match self {
+            Self::Arrow => IDC_ARROW,
+            Self::Text => IDC_IBEAM,
+            Self::Hand => IDC_HAND,
+            Self::Move => IDC_SIZEALL,
+            Self::Crosshair => IDC_CROSS,
+            Self::Wait => IDC_WAIT,
+            Self::Progress => IDC_APPSTARTING,
+            Self::Help => IDC_HELP,
+            Self::NotAllowed => IDC_NO,
+            Self::ResizeHorizontal => IDC_SIZEWE,
+            Self::ResizeVertical => IDC_SIZENS,
+            Self::ResizeNwse => IDC_SIZENWSE,
+            Self::ResizeNesw => IDC_SIZENESW,
+        }
+    }
+
+    /// The cursor for a `WM_NCHITTEST` result, None for the client area and caption
+    pub fn from_hit_test(hit: u32) -> Option<Self> {
+        match hit {
+            HTLEFT | HTRIGHT => Some(Self::ResizeHorizontal),
+            HTTOP | HTBOTTOM => Some(Self::ResizeVertical),
+            HTTOPLEFT | HTBOTTOMRIGHT => Some(Self::ResizeNwse),
+            HTTOPRIGHT | HTBOTTOMLEFT => Some(Self::ResizeNesw),
+            _ => None,
+        }
+    }
+
+    pub fn handle(self) -> Result<HCURSOR> {
+        system_cursor(self.resource())
+    }
+}
+
+/// A cursor made from an image, destroyed when dropped
+#[derive(Debug)]
+pub struct CustomCursor {
+    handle: HCURSOR,
+}
+
+impl CustomCursor {
+    /// `hotspot` is the pixel that points, from the top left of the image
+    pub fn from_rgba(image: &image::RgbaImage, hotspot: (u32, u32)) -> Result<Self> {
+        let bgra = to_bgra(image);
+        let hotspot = (
+            hotspot.0.min(image.width().saturating_sub(1)),
+            hotspot.1.min(image.height().saturating_sub(1)),
+        );
+        let icon = create_icon_from_bgra(image.width(), image.height(), &bgra, Some(hotspot))?;
+        Ok(Self {
+            handle: HCURSOR(icon.0),
+        })
+    }
+
+    pub fn handle(&self) -> HCURSOR {
+        self.handle
+    }
+}
+
+impl Drop for CustomCursor {
+    fn drop(&mut self) {
+        destroy_cursor(self.handle);
+    }
+}
+
+#[derive(Debug)]
+pub enum Cursor {
+    Icon(CursorIcon),
+    Custom(CustomCursor),
+}
+
+impl Cursor {
+    pub fn handle(&self) -> Result<HCURSOR> {
+        match self {
+            Self::Icon(icon) => icon.handle(),
+            Self::Custom(cursor) => Ok(cursor.handle()),
+        }
+    }
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self::Icon(CursorIcon::Arrow)
+    }
+}
+
+impl From<CursorIcon> for Cursor {
+    fn from(icon: CursorIcon) -> Self {
+        Self::Icon(icon)
+    }
+}
+
+/// How the window holds on to the cursor
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CursorGrab {
+    #[default]
+    None,
+    /// The cursor cannot leave the client area
+    Confined,
+    /// The cursor is hidden and kept in the middle of the client area, mouse movement is
+    /// reported as `Input::MouseDelta` instead, such as for turning a camera
+    Locked,
+}
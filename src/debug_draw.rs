@@ -0,0 +1,439 @@
+#![allow(dead_code)]
+//! Immediate mode debug drawing.
+//! Lines, arrows, boxes, spheres and axes are queued for a single frame, or for a number of
+//! seconds, and drawn unlit over the final image. An optional ground grid follows the camera and
+//! fades out with distance so it looks infinite.
+//!
+//! Nothing is queued or drawn unless the `debug-draw` feature is enabled and the build has debug
+//! assertions, so calls can be left in place for release builds.
+
+use crate::ray::{Aabb, Sphere};
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+use crate::render::DEPTH_FORMAT;
+use cgmath::{InnerSpace, Point3, Vector3};
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+use std::time::Duration;
+use std::time::Instant;
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+use wgpu::include_wgsl;
+
+/// Segments in each of the circles drawn for a sphere
+const SPHERE_SEGMENTS: usize = 32;
+/// Arrow heads are this fraction of the arrow's length
+const ARROW_HEAD_SIZE: f32 = 0.2;
+
+const X_COLOUR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const Y_COLOUR: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+const Z_COLOUR: [f32; 4] = [0.2, 0.4, 1.0, 1.0];
+
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    colour: [f32; 4],
+    fade_distance: f32,
+}
+
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+impl LineVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4, 2 => Float32];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Line {
+    start: Point3<f32>,
+    end: Point3<f32>,
+    colour: [f32; 4],
+    // When to stop drawing the line, None for a line queued for a single frame
+    expires: Option<Instant>,
+}
+
+/// A grid on the y = 0 plane centred under the camera
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grid {
+    /// World units between lines
+    pub spacing: f32,
+    /// Lines either side of the camera, the grid has faded out by the last of them
+    pub extent: u32,
+    pub colour: [f32; 4],
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            extent: 50,
+            colour: [0.5, 0.5, 0.5, 0.5],
+        }
+    }
+}
+
+/// The queue of shapes to draw
+#[derive(Debug)]
+pub struct DebugDraw {
+    lines: Vec<Line>,
+    // Lifetime given to shapes as they are queued
+    lifetime: f32,
+    depth_test: bool,
+    grid: Option<Grid>,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            lifetime: 0.0,
+            depth_test: true,
+            grid: None,
+        }
+    }
+}
+
+impl DebugDraw {
+    /// Shapes queued inside `f` are drawn for `seconds` instead of a single frame
+    pub fn with_lifetime<F: FnOnce(&mut Self)>(&mut self, seconds: f32, f: F) {
+        let previous = std::mem::replace(&mut self.lifetime, seconds.max(0.0));
+        f(self);
+        self.lifetime = previous;
+    }
+
+    #[cfg_attr(
+        not(all(feature = "debug-draw", debug_assertions)),
+        allow(unused_variables)
+    )]
+    pub fn line(&mut self, start: Point3<f32>, end: Point3<f32>, colour: [f32; 4]) {
+        #[cfg(all(feature = "debug-draw", debug_assertions))]
+        self.lines.push(Line {
+            start,
+            end,
+            colour,
+            expires: (self.lifetime > 0.0)
+                .then(|| Instant::now() + Duration::from_secs_f32(self.lifetime)),
+        });
+    }
+
+    /// A line with a four sided head at `end`
+    pub fn arrow(&mut self, start: Point3<f32>, end: Point3<f32>, colour: [f32; 4]) {
+        self.line(start, end, colour);
+        let shaft = end - start;
+        let length = shaft.magnitude();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = shaft / length;
+        let (side, up) = perpendiculars(direction);
+        let head = length * ARROW_HEAD_SIZE;
+        let base = end - direction * head;
+        for offset in [side, -side, up, -up] {
+            self.line(end, base + offset * head * 0.5, colour);
+        }
+    }
+
+    /// The twelve edges of a box
+    pub fn aabb(&mut self, aabb: &Aabb, colour: [f32; 4]) {
+        let (min, max) = (aabb.min, aabb.max);
+        let corner = |i: usize| {
+            Point3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        // Corners that differ in exactly one bit share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), colour);
+                }
+            }
+        }
+    }
+
+    /// A circle around each axis through the centre
+    pub fn sphere(&mut self, sphere: &Sphere, colour: [f32; 4]) {
+        let axes = [
+            (Vector3::unit_x(), Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+            (Vector3::unit_z(), Vector3::unit_x()),
+        ];
+        for (u, v) in axes {
+            self.circle(sphere.centre, u, v, sphere.radius, colour);
+        }
+    }
+
+    /// A circle in the plane of the perpendicular unit vectors `u` and `v`
+    pub fn circle(
+        &mut self,
+        centre: Point3<f32>,
+        u: Vector3<f32>,
+        v: Vector3<f32>,
+        radius: f32,
+        colour: [f32; 4],
+    ) {
+        let point = |i: usize| {
+            let angle = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+            centre + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..SPHERE_SEGMENTS {
+            self.line(point(i), point(i + 1), colour);
+        }
+    }
+
+    /// Red, green and blue arrows along x, y and z
+    pub fn axes(&mut self, origin: Point3<f32>, size: f32) {
+        self.arrow(origin, origin + Vector3::unit_x() * size, X_COLOUR);
+        self.arrow(origin, origin + Vector3::unit_y() * size, Y_COLOUR);
+        self.arrow(origin, origin + Vector3::unit_z() * size, Z_COLOUR);
+    }
+
+    /// Shows or hides the ground grid, it stays until hidden rather than lasting a frame
+    pub fn set_grid(&mut self, grid: Option<Grid>) {
+        self.grid = grid;
+    }
+
+    pub fn grid(&self) -> Option<Grid> {
+        self.grid
+    }
+
+    /// Whether lines are hidden behind the scene, or drawn over everything
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    /// Removes every queued shape, including those with time left
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// True if there is nothing to draw
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.grid.is_none()
+    }
+
+    /// True if shapes queued with a lifetime are still waiting to expire
+    pub fn has_timed_shapes(&self) -> bool {
+        self.lines.iter().any(|line| line.expires.is_some())
+    }
+
+    /// Drops single frame shapes and those whose time has run out.
+    /// Called by the engine after each frame is drawn
+    pub fn end_frame(&mut self) {
+        let now = Instant::now();
+        self.lines
+            .retain(|line| line.expires.is_some_and(|expires| expires > now));
+    }
+
+    /// Vertices for a line list, with the grid centred under `eye`
+    #[cfg(all(feature = "debug-draw", debug_assertions))]
+    fn vertices(&self, eye: Point3<f32>) -> Vec<LineVertex> {
+        let mut vertices = Vec::with_capacity(self.lines.len() * 2);
+        let mut push = |start: Point3<f32>, end: Point3<f32>, colour, fade_distance| {
+            for position in [start, end] {
+                vertices.push(LineVertex {
+                    position: position.into(),
+                    colour,
+                    fade_distance,
+                });
+            }
+        };
+
+        if let Some(grid) = self.grid.filter(|grid| grid.spacing > 0.0) {
+            let half = grid.extent as f32 * grid.spacing;
+            // Snapped to whole cells so the lines stay put as the camera moves
+            let centre_x = (eye.x / grid.spacing).round() as i32;
+            let centre_z = (eye.z / grid.spacing).round() as i32;
+            let (min_x, max_x) = (
+                centre_x as f32 * grid.spacing - half,
+                centre_x as f32 * grid.spacing + half,
+            );
+            let (min_z, max_z) = (
+                centre_z as f32 * grid.spacing - half,
+                centre_z as f32 * grid.spacing + half,
+            );
+            let extent = grid.extent as i32;
+            for i in -extent..=extent {
+                let (cell_x, cell_z) = (centre_x + i, centre_z + i);
+                let x = cell_x as f32 * grid.spacing;
+                let z = cell_z as f32 * grid.spacing;
+                // The world axes are coloured where they cross the grid
+                let x_line = if cell_z == 0 { X_COLOUR } else { grid.colour };
+                let z_line = if cell_x == 0 { Z_COLOUR } else { grid.colour };
+                push(
+                    Point3::new(min_x, 0.0, z),
+                    Point3::new(max_x, 0.0, z),
+                    x_line,
+                    half,
+                );
+                push(
+                    Point3::new(x, 0.0, min_z),
+                    Point3::new(x, 0.0, max_z),
+                    z_line,
+                    half,
+                );
+            }
+        }
+
+        for line in &self.lines {
+            push(line.start, line.end, line.colour, 0.0);
+        }
+        vertices
+    }
+}
+
+/// Two unit vectors perpendicular to `direction` and each other
+fn perpendiculars(direction: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    // Cross with whichever axis is furthest from parallel
+    let axis = if direction.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let side = direction.cross(axis).normalize();
+    (side, direction.cross(side))
+}
+
+/// Draws a `DebugDraw` queue with a line list pipeline
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+pub struct DebugRenderer {
+    depth_tested: wgpu::RenderPipeline,
+    always_on_top: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+impl DebugRenderer {
+    /// `camera_bind_group_layout` is the layout of the engine's camera uniform
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(&include_wgsl!("shaders/debug_lines.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Line Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[LineVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                // Tested against the scene's depth but never written, lines do not hide each other
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let depth_tested = pipeline("Debug Line Pipeline", wgpu::CompareFunction::LessEqual);
+        let always_on_top = pipeline("Debug Line Overlay Pipeline", wgpu::CompareFunction::Always);
+
+        let capacity = 1024;
+        Self {
+            depth_tested,
+            always_on_top,
+            vertex_buffer: create_vertex_buffer(device, capacity),
+            capacity,
+        }
+    }
+
+    /// Draws the queued shapes over `view`, hidden by the scene's depth if depth testing is on
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+        debug_draw: &DebugDraw,
+        eye: Point3<f32>,
+    ) {
+        let vertices = debug_draw.vertices(eye);
+        if vertices.is_empty() {
+            return;
+        }
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Line Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(if debug_draw.depth_test {
+            &self.depth_tested
+        } else {
+            &self.always_on_top
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+    }
+}
+
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug Line Vertex Buffer"),
+        size: (capacity * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
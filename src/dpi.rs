@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+//! Physical sizes are in pixels on the monitor, logical sizes are in pixels at 96 DPI and stay
+//! the same visual size whatever monitor the window is on.
+//! The scale factor is the monitor's DPI over 96, so 1.5 at 144 DPI.
+
+/// The DPI windows treat as a scale factor of 1
+pub const BASE_DPI: u32 = 96;
+
+/// The scale factor for a DPI, a DPI of 0 (from a failed query) is treated as the base DPI
+pub fn scale_factor_from_dpi(dpi: u32) -> f64 {
+    if dpi == 0 {
+        1.0
+    } else {
+        dpi as f64 / BASE_DPI as f64
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PhysicalSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LogicalSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhysicalPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LogicalPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl PhysicalSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// A scale factor of 0 is treated as 1
+    pub fn to_logical(self, scale_factor: f64) -> LogicalSize {
+        let scale_factor = valid_scale_factor(scale_factor);
+        LogicalSize {
+            width: self.width as f64 / scale_factor,
+            height: self.height as f64 / scale_factor,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+impl LogicalSize {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+
+    /// Rounded to whole pixels, negative sizes become 0
+    pub fn to_physical(self, scale_factor: f64) -> PhysicalSize {
+        let scale_factor = valid_scale_factor(scale_factor);
+        PhysicalSize {
+            width: (self.width * scale_factor).round().max(0.0) as u32,
+            height: (self.height * scale_factor).round().max(0.0) as u32,
+        }
+    }
+}
+
+impl PhysicalPosition {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn to_logical(self, scale_factor: f64) -> LogicalPosition {
+        let scale_factor = valid_scale_factor(scale_factor);
+        LogicalPosition {
+            x: self.x as f64 / scale_factor,
+            y: self.y as f64 / scale_factor,
+        }
+    }
+}
+
+impl LogicalPosition {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    /// Rounded to whole pixels
+    pub fn to_physical(self, scale_factor: f64) -> PhysicalPosition {
+        let scale_factor = valid_scale_factor(scale_factor);
+        PhysicalPosition {
+            x: (self.x * scale_factor).round() as i32,
+            y: (self.y * scale_factor).round() as i32,
+        }
+    }
+}
+
+impl From<(u32, u32)> for PhysicalSize {
+    fn from((width, height): (u32, u32)) -> Self {
+        Self { width, height }
+    }
+}
+
+impl From<PhysicalSize> for (u32, u32) {
+    fn from(size: PhysicalSize) -> Self {
+        (size.width, size.height)
+    }
+}
+
+pub(crate) fn valid_scale_factor(scale_factor: f64) -> f64 {
+    if scale_factor.is_finite() && scale_factor > 0.0 {
+        scale_factor
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_factor_from_common_dpis() {
+        assert_eq!(scale_factor_from_dpi(0), 1.0);
+        assert_eq!(scale_factor_from_dpi(96), 1.0);
+        assert_eq!(scale_factor_from_dpi(120), 1.25);
+        assert_eq!(scale_factor_from_dpi(144), 1.5);
+        assert_eq!(scale_factor_from_dpi(192), 2.0);
+    }
+
+    #[test]
+    fn sizes_round_trip() {
+        for scale_factor in [1.0, 1.25, 1.5, 1.75, 2.0, 3.0] {
+            for size in [(0, 0), (1, 1), (800, 600), (1919, 1081)] {
+                let physical = PhysicalSize::from(size);
+                let back = physical.to_logical(scale_factor).to_physical(scale_factor);
+                assert_eq!(back, physical, "{:?} at {}", size, scale_factor);
+            }
+        }
+        let position = PhysicalPosition::new(-250, 333);
+        assert_eq!(position.to_logical(1.5).to_physical(1.5), position);
+    }
+
+    #[test]
+    fn to_physical_rounds_to_nearest() {
+        assert_eq!(
+            LogicalSize::new(10.2, 10.6).to_physical(1.0),
+            PhysicalSize::new(10, 11)
+        );
+        // 1.25 * 2 = 2.5 rounds away from zero
+        assert_eq!(
+            LogicalSize::new(2.0, 3.0).to_physical(1.25),
+            PhysicalSize::new(3, 4)
+        );
+        assert_eq!(
+            LogicalPosition::new(-2.0, 2.0).to_physical(1.25),
+            PhysicalPosition::new(-3, 3)
+        );
+    }
+
+    #[test]
+    fn negative_sizes_clamp_to_zero() {
+        assert_eq!(
+            LogicalSize::new(-5.0, 10.0).to_physical(2.0),
+            PhysicalSize::new(0, 20)
+        );
+        assert!(PhysicalSize::new(0, 20).is_empty());
+        assert!(!PhysicalSize::new(1, 1).is_empty());
+    }
+
+    #[test]
+    fn invalid_scale_factors_are_one() {
+        for scale_factor in [0.0, -1.5, f64::NAN, f64::INFINITY] {
+            assert_eq!(valid_scale_factor(scale_factor), 1.0);
+            assert_eq!(
+                PhysicalSize::new(300, 200).to_logical(scale_factor),
+                LogicalSize::new(300.0, 200.0)
+            );
+            assert_eq!(
+                LogicalPosition::new(3.0, 4.0).to_physical(scale_factor),
+                PhysicalPosition::new(3, 4)
+            );
+        }
+        assert_eq!(valid_scale_factor(1.5), 1.5);
+    }
+}
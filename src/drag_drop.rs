@@ -0,0 +1,251 @@
+#![allow(dead_code)]
+//! Files dragged onto a window from Explorer or another application.
+//! The window is registered as an OLE drop target, which reports the files when the drag
+//! enters, the position as it moves and whether it left or was dropped. The answer to each of
+//! these picks the cursor the drag shows, a copy arrow or a no entry sign.
+//!
+//! windows 0.33 can only implement COM interfaces with its `implement` feature, so the
+//! `IDropTarget` here is a plain struct that starts with a pointer to a static vtable, which is
+//! all a COM object is. Where OLE is not available, such as on a thread already initialised for
+//! a multithreaded apartment, the window falls back to `WM_DROPFILES`, which only reports drops.
+
+use crate::coords::{ClientPoint, ScreenPoint};
+use crate::winapi_utils::get_drop_files;
+use crate::window::Window;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use windows::core::{IUnknown, IUnknownVtbl, Interface, RawPtr, GUID, HRESULT};
+use windows::Win32::Foundation::{E_NOINTERFACE, E_POINTER, POINTL, S_OK};
+use windows::Win32::System::Com::IDataObject;
+use windows::Win32::System::Ole::{
+    IDropTarget, IDropTarget_Vtbl, DROPEFFECT_COPY, DROPEFFECT_NONE,
+};
+
+const TEXTURE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "tga"];
+const MODEL_EXTENSIONS: [&str; 2] = ["gltf", "glb"];
+
+/// What a drag over the window would do if dropped, shown by the cursor
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DropEffect {
+    /// The drop is refused
+    #[default]
+    None,
+    /// The files are read, the source keeps them
+    Copy,
+}
+
+impl DropEffect {
+    /// The `DROPEFFECT` to answer with, refused if the source does not allow it
+    fn to_win32(self, allowed: u32) -> u32 {
+        match self {
+            Self::Copy if allowed & DROPEFFECT_COPY != 0 => DROPEFFECT_COPY,
+            _ => DROPEFFECT_NONE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DragEvent {
+    /// Files were dragged over the window
+    HoverEnter {
+        paths: Vec<PathBuf>,
+        position: ClientPoint,
+    },
+    /// The drag moved, sent repeatedly even when it has not
+    HoverOver { position: ClientPoint },
+    /// The drag left the window or was cancelled
+    Leave,
+    Drop {
+        paths: Vec<PathBuf>,
+        position: ClientPoint,
+    },
+}
+
+/// What the engine can load from a dropped file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Texture,
+    /// A glTF model
+    Model,
+}
+
+impl FileKind {
+    /// The kind of file by its extension, None if it cannot be loaded
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        if TEXTURE_EXTENSIONS.contains(&extension.as_str()) {
+            Some(Self::Texture)
+        } else if MODEL_EXTENSIONS.contains(&extension.as_str()) {
+            Some(Self::Model)
+        } else {
+            None
+        }
+    }
+}
+
+/// Copy if any of the files can be loaded
+pub fn effect_for_paths(paths: &[PathBuf]) -> DropEffect {
+    if paths.iter().any(|path| FileKind::from_path(path).is_some()) {
+        DropEffect::Copy
+    } else {
+        DropEffect::None
+    }
+}
+
+/// An `IDropTarget` that passes events to a window
+#[repr(C)]
+pub(crate) struct DropTarget {
+    // Has to be the first field, COM finds the methods through it
+    vtable: *const IDropTarget_Vtbl,
+    references: AtomicU32,
+    window: *mut Window,
+}
+
+static DROP_TARGET_VTABLE: IDropTarget_Vtbl = IDropTarget_Vtbl {
+    base: IUnknownVtbl {
+        QueryInterface: DropTarget::query_interface,
+        AddRef: DropTarget::add_ref,
+        Release: DropTarget::release,
+    },
+    DragEnter: DropTarget::drag_enter,
+    DragOver: DropTarget::drag_over,
+    DragLeave: DropTarget::drag_leave,
+    Drop: DropTarget::drop_files,
+};
+
+impl DropTarget {
+    /// A new target holding one reference, which is released when the interface is dropped.
+    /// The window has to be revoked as a drop target before it is freed.
+    pub(crate) fn create(window: *mut Window) -> IDropTarget {
+        let target = Box::new(Self {
+            vtable: &DROP_TARGET_VTABLE,
+            references: AtomicU32::new(1),
+            window,
+        });
+        unsafe { std::mem::transmute::<*mut Self, IDropTarget>(Box::into_raw(target)) }
+    }
+
+    unsafe fn window<'a>(this: RawPtr) -> &'a mut Window {
+        &mut *(*(this as *mut Self)).window
+    }
+
+    /// The client point under a drag, which OLE gives in screen coordinates
+    fn position(window: &Window, point: POINTL) -> ClientPoint {
+        window
+            .screen_to_client(ScreenPoint::new(point.x, point.y))
+            .unwrap_or_default()
+    }
+
+    /// Answers a hover or drop from the window's effect and what the source allows
+    unsafe fn respond(effect: DropEffect, allowed: *mut u32) -> HRESULT {
+        if allowed.is_null() {
+            return E_POINTER;
+        }
+        *allowed = effect.to_win32(*allowed);
+        S_OK
+    }
+
+    /// The files being dragged, none if the data is something else, such as text
+    unsafe fn paths(data: RawPtr) -> Vec<PathBuf> {
+        if data.is_null() {
+            return Vec::new();
+        }
+        // Borrowed from the caller, so it must not be released
+        let data = std::mem::ManuallyDrop::new(std::mem::transmute::<RawPtr, IDataObject>(data));
+        get_drop_files(&data).unwrap_or_default()
+    }
+
+    unsafe extern "system" fn query_interface(
+        this: RawPtr,
+        iid: &GUID,
+        interface: *mut RawPtr,
+    ) -> HRESULT {
+        if interface.is_null() {
+            return E_POINTER;
+        }
+        if *iid == IUnknown::IID || *iid == IDropTarget::IID {
+            Self::add_ref(this);
+            *interface = this;
+            S_OK
+        } else {
+            *interface = std::ptr::null_mut();
+            E_NOINTERFACE
+        }
+    }
+
+    unsafe extern "system" fn add_ref(this: RawPtr) -> u32 {
+        (*(this as *mut Self))
+            .references
+            .fetch_add(1, Ordering::Relaxed)
+            + 1
+    }
+
+    unsafe extern "system" fn release(this: RawPtr) -> u32 {
+        let remaining = (*(this as *mut Self))
+            .references
+            .fetch_sub(1, Ordering::Release)
+            - 1;
+        if remaining == 0 {
+            std::sync::atomic::fence(Ordering::Acquire);
+            drop(Box::from_raw(this as *mut Self));
+        }
+        remaining
+    }
+
+    unsafe extern "system" fn drag_enter(
+        this: RawPtr,
+        data: RawPtr,
+        _key_state: u32,
+        point: POINTL,
+        effect: *mut u32,
+    ) -> HRESULT {
+        let window = Self::window(this);
+        let event = DragEvent::HoverEnter {
+            paths: Self::paths(data),
+            position: Self::position(window, point),
+        };
+        Self::respond(window.drag(event), effect)
+    }
+
+    unsafe extern "system" fn drag_over(
+        this: RawPtr,
+        _key_state: u32,
+        point: POINTL,
+        effect: *mut u32,
+    ) -> HRESULT {
+        let window = Self::window(this);
+        let event = DragEvent::HoverOver {
+            position: Self::position(window, point),
+        };
+        Self::respond(window.drag(event), effect)
+    }
+
+    unsafe extern "system" fn drag_leave(this: RawPtr) -> HRESULT {
+        Self::window(this).drag(DragEvent::Leave);
+        S_OK
+    }
+
+    unsafe extern "system" fn drop_files(
+        this: RawPtr,
+        data: RawPtr,
+        _key_state: u32,
+        point: POINTL,
+        effect: *mut u32,
+    ) -> HRESULT {
+        let window = Self::window(this);
+        let event = DragEvent::Drop {
+            paths: Self::paths(data),
+            position: Self::position(window, point),
+        };
+        Self::respond(window.drag(event), effect)
+    }
+}
+
+/// How a window is receiving dropped files
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum FileDrop {
+    /// Registered as an OLE drop target, with hover events
+    Ole,
+    /// `WM_DROPFILES`, only told about drops
+    DropFiles,
+}
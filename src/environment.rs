@@ -0,0 +1,770 @@
+#![allow(dead_code)]
+//! Image based lighting for the PBR shader, baked on the gpu from an equirectangular
+//! (latitude-longitude) environment with the split sum approximation of Brian Karis,
+//! Real Shading in Unreal Engine 4:
+//! - an irradiance map, the cosine weighted light around each normal, for the diffuse term
+//! - a prefiltered map whose mips are the light reflected by GGX lobes from roughness 0 to 1
+//! - a lookup table of the scale and bias the specular BRDF applies to f0, by n.v and roughness,
+//!   which only depends on the BRDF so it is baked once by the `EnvironmentBaker`
+//!
+//! HDR images (`.hdr`, `.exr`) are used as they are. Other images are taken to be sRGB and
+//! linearised, so they light the scene no brighter than a white sky.
+
+use image::DynamicImage;
+use wgpu::include_wgsl;
+
+/// Format of the baked maps and the source they are baked from
+const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+const BRDF_LUT_SIZE: u32 = 128;
+/// Width of the irradiance map, diffuse light changes slowly so it is stored small
+const IRRADIANCE_WIDTH: u32 = 64;
+/// Width of the sharpest prefiltered mip, smaller sources keep their own width
+const PREFILTERED_WIDTH: u32 = 512;
+/// Mips of the prefiltered map, evenly spaced in roughness from 0 to 1
+const PREFILTERED_MIPS: u32 = 6;
+/// Largest half precision float, brighter texels are clamped to it
+const MAX_HALF: f32 = 65504.0;
+
+/// Shader side environment settings
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentUniform {
+    intensity: f32,
+    // The index of the last prefiltered mip, used to map roughness onto the mip chain
+    max_mip: f32,
+    _padding: [f32; 2],
+}
+
+/// Settings of one baking pass
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeUniform {
+    // Roughness of the prefiltered mip being drawn
+    roughness: f32,
+    // The source mip about as wide as the target, read where only one sample is taken
+    source_mip: f32,
+    // Solid angle of a texel of the source's first mip
+    texel_solid_angle: f32,
+    // The index of the source's last mip
+    max_mip: f32,
+}
+
+/// A linear radiance image, one level of the source's mip chain
+#[derive(Clone, Debug, PartialEq)]
+struct RadianceImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+/// The pipelines that bake environments and the BRDF lookup table they share
+pub struct EnvironmentBaker {
+    bake_layout: wgpu::BindGroupLayout,
+    irradiance_pipeline: wgpu::RenderPipeline,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    brdf_lut: wgpu::Texture,
+}
+
+impl EnvironmentBaker {
+    /// Compiles the baking pipelines and bakes the BRDF lookup table
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let shader = device.create_shader_module(&include_wgsl!("shaders/environment.wgsl"));
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let bake_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("environment_bake_bind_group_layout"),
+        });
+
+        let bake_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Environment Bake Pipeline Layout"),
+            bind_group_layouts: &[&bake_layout],
+            push_constant_ranges: &[],
+        });
+        let lut_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("BRDF Lookup Table Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, layout, entry_point, format| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let irradiance_pipeline = pipeline(
+            "Irradiance Pipeline",
+            &bake_pipeline_layout,
+            "fs_irradiance",
+            HDR_FORMAT,
+        );
+        let prefilter_pipeline = pipeline(
+            "Prefilter Pipeline",
+            &bake_pipeline_layout,
+            "fs_prefilter",
+            HDR_FORMAT,
+        );
+        let lut_pipeline = pipeline(
+            "BRDF Lookup Table Pipeline",
+            &lut_pipeline_layout,
+            "fs_brdf_lut",
+            BRDF_LUT_FORMAT,
+        );
+
+        let brdf_lut = create_target(
+            device,
+            "brdf_lut_texture",
+            (BRDF_LUT_SIZE, BRDF_LUT_SIZE),
+            1,
+            BRDF_LUT_FORMAT,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("BRDF Lookup Table Encoder"),
+        });
+        draw_fullscreen(
+            &mut encoder,
+            &lut_pipeline,
+            None,
+            &brdf_lut.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Self {
+            bake_layout,
+            irradiance_pipeline,
+            prefilter_pipeline,
+            brdf_lut,
+        }
+    }
+
+    fn bake_bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        uniform: BakeUniform,
+    ) -> wgpu::BindGroup {
+        let buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Bake Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bake_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("environment_bake_bind_group"),
+        })
+    }
+}
+
+pub struct Environment {
+    irradiance: wgpu::Texture,
+    prefiltered: wgpu::Texture,
+    uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    intensity: f32,
+}
+
+impl Environment {
+    /// A uniform environment of a single sRGB colour, used when no environment image is
+    /// supplied
+    pub fn uniform(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        baker: &EnvironmentBaker,
+        colour: [u8; 4],
+    ) -> Self {
+        let image = image::RgbaImage::from_pixel(1, 1, image::Rgba(colour));
+        Self::from_equirectangular(
+            device,
+            queue,
+            layout,
+            baker,
+            &DynamicImage::ImageRgba8(image),
+        )
+    }
+
+    /// Bakes an environment from an equirectangular image. Images larger than the device
+    /// allows are halved until they fit.
+    pub fn from_equirectangular(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        baker: &EnvironmentBaker,
+        image: &DynamicImage,
+    ) -> Self {
+        let mips = radiance_mips(
+            linear_radiance(image),
+            device.limits().max_texture_dimension_2d,
+        );
+        let (width, height) = (mips[0].width, mips[0].height);
+        let max_mip = (mips.len() - 1) as f32;
+
+        let source = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("environment_source_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mips.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        for (level, mip) in mips.iter().enumerate() {
+            let halves: Vec<u16> = mip.pixels.iter().flatten().map(|&c| to_f16(c)).collect();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &source,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&halves),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(8 * mip.width),
+                    rows_per_image: std::num::NonZeroU32::new(mip.height),
+                },
+                wgpu::Extent3d {
+                    width: mip.width,
+                    height: mip.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = equirectangular_sampler(device);
+
+        let irradiance = create_target(
+            device,
+            "irradiance_texture",
+            (IRRADIANCE_WIDTH, IRRADIANCE_WIDTH / 2),
+            1,
+            HDR_FORMAT,
+        );
+        let prefiltered_width = width.clamp(IRRADIANCE_WIDTH, PREFILTERED_WIDTH);
+        let prefiltered = create_target(
+            device,
+            "prefiltered_environment_texture",
+            (prefiltered_width, prefiltered_width / 2),
+            PREFILTERED_MIPS,
+            HDR_FORMAT,
+        );
+
+        let bake = |roughness, target_width| BakeUniform {
+            roughness,
+            source_mip: source_mip(width, target_width).min(max_mip),
+            texel_solid_angle: texel_solid_angle(width, height),
+            max_mip,
+        };
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Environment Bake Encoder"),
+        });
+        let bind_group =
+            baker.bake_bind_group(device, &source_view, &sampler, bake(0.0, IRRADIANCE_WIDTH));
+        draw_fullscreen(
+            &mut encoder,
+            &baker.irradiance_pipeline,
+            Some(&bind_group),
+            &irradiance.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        for level in 0..PREFILTERED_MIPS {
+            let uniform = bake(
+                prefilter_roughness(level, PREFILTERED_MIPS),
+                prefiltered_width >> level,
+            );
+            let bind_group = baker.bake_bind_group(device, &source_view, &sampler, uniform);
+            let target = prefiltered.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: std::num::NonZeroU32::new(1),
+                ..Default::default()
+            });
+            draw_fullscreen(
+                &mut encoder,
+                &baker.prefilter_pipeline,
+                Some(&bind_group),
+                &target,
+            );
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let intensity = 1.0;
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Buffer"),
+                contents: bytemuck::cast_slice(&[EnvironmentUniform {
+                    intensity,
+                    max_mip: (PREFILTERED_MIPS - 1) as f32,
+                    _padding: [0.0; 2],
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let lut_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let prefiltered_view = prefiltered.create_view(&wgpu::TextureViewDescriptor::default());
+        let irradiance_view = irradiance.create_view(&wgpu::TextureViewDescriptor::default());
+        let lut_view = baker
+            .brdf_lut
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&prefiltered_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&irradiance_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&lut_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&lut_sampler),
+                },
+            ],
+            label: Some("environment_bind_group"),
+        });
+
+        Self {
+            irradiance,
+            prefiltered,
+            uniform_buffer,
+            bind_group,
+            intensity,
+        }
+    }
+
+    /// Scales the light contributed by the environment
+    pub fn set_intensity(&mut self, queue: &wgpu::Queue, intensity: f32) {
+        self.intensity = intensity;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[EnvironmentUniform {
+                intensity,
+                max_mip: (PREFILTERED_MIPS - 1) as f32,
+                _padding: [0.0; 2],
+            }]),
+        );
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                // Prefiltered reflections
+                texture_entry(0),
+                sampler_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Irradiance
+                texture_entry(3),
+                // BRDF lookup table, clamped rather than wrapped
+                texture_entry(4),
+                sampler_entry(5),
+            ],
+            label: Some("environment_bind_group_layout"),
+        })
+    }
+}
+
+/// Wraps horizontally around the sphere and clamps at the poles
+fn equirectangular_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+/// A texture the baking passes draw into and the PBR shader samples
+fn create_target(
+    device: &wgpu::Device,
+    label: &str,
+    (width, height): (u32, u32),
+    mip_level_count: u32,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+    })
+}
+
+fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: Option<&wgpu::BindGroup>,
+    target: &wgpu::TextureView,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Environment Bake Pass"),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    if let Some(bind_group) = bind_group {
+        render_pass.set_bind_group(0, bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
+
+/// The image as linear radiance. Float images are already linear, 8 and 16 bit ones are sRGB.
+fn linear_radiance(image: &DynamicImage) -> RadianceImage {
+    let is_float = matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+    let rgba = image.to_rgba32f();
+    let pixels = rgba
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            if is_float {
+                [r, g, b, a]
+            } else {
+                [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+            }
+        })
+        .collect();
+    RadianceImage {
+        width: rgba.width(),
+        height: rgba.height(),
+        pixels,
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Averages each 2x2 block, an odd last row or column is averaged with itself
+fn downsample(image: &RadianceImage) -> RadianceImage {
+    let width = (image.width / 2).max(1);
+    let height = (image.height / 2).max(1);
+    let at = |x: u32, y: u32| {
+        let (x, y) = (x.min(image.width - 1), y.min(image.height - 1));
+        image.pixels[(y * image.width + x) as usize]
+    };
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let block = [
+                at(2 * x, 2 * y),
+                at(2 * x + 1, 2 * y),
+                at(2 * x, 2 * y + 1),
+                at(2 * x + 1, 2 * y + 1),
+            ];
+            let mut sum = [0.0; 4];
+            for pixel in block {
+                for (total, channel) in sum.iter_mut().zip(pixel) {
+                    *total += channel / 4.0;
+                }
+            }
+            pixels.push(sum);
+        }
+    }
+    RadianceImage {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// The mip chain down to a single pixel wide or high, without the levels larger than
+/// `max_dimension`
+fn radiance_mips(base: RadianceImage, max_dimension: u32) -> Vec<RadianceImage> {
+    let mut mips = vec![base];
+    loop {
+        let last = mips.last().unwrap();
+        if last.width == 1 || last.height == 1 {
+            break;
+        }
+        let next = downsample(last);
+        mips.push(next);
+    }
+    let oversized = mips
+        .iter()
+        .take_while(|mip| mip.width.max(mip.height) > max_dimension)
+        .count()
+        .min(mips.len() - 1);
+    mips.split_off(oversized)
+}
+
+/// The source mip whose width is closest to `target_width`
+fn source_mip(source_width: u32, target_width: u32) -> f32 {
+    (source_width as f32 / target_width as f32).log2().max(0.0)
+}
+
+/// The average solid angle of an equirectangular texel
+fn texel_solid_angle(width: u32, height: u32) -> f32 {
+    4.0 * std::f32::consts::PI / (width as f32 * height as f32)
+}
+
+/// The roughness a prefiltered mip is baked for, the shader picks the mip the same way
+fn prefilter_roughness(level: u32, levels: u32) -> f32 {
+    level as f32 / (levels - 1).max(1) as f32
+}
+
+/// A half precision float for `Rgba16Float`. Radiance is never negative or unbounded, so
+/// negative values and NaN become 0 and anything too bright the largest half.
+fn to_f16(value: f32) -> u16 {
+    if value.is_nan() || value <= 0.0 {
+        return 0;
+    }
+    let bits = value.min(MAX_HALF).to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        // Subnormal, with the implicit leading bit made explicit
+        if exponent < -10 {
+            return 0;
+        }
+        let shift = (14 - exponent) as u32;
+        let mantissa = mantissa | 0x80_0000;
+        return ((mantissa + (1 << (shift - 1))) >> shift) as u16;
+    }
+    // Rounding can carry into the exponent, which is still correct
+    let half = ((exponent as u32) << 10) + ((mantissa + 0x1000) >> 13);
+    half.min(0x7bff) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn radiance(width: u32, height: u32, value: impl Fn(u32, u32) -> f32) -> RadianceImage {
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let v = value(x, y);
+                pixels.push([v, v, v, 1.0]);
+            }
+        }
+        RadianceImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn halves_are_rounded_and_clamped() {
+        assert_eq!(to_f16(1.0), 0x3c00);
+        assert_eq!(to_f16(0.5), 0x3800);
+        assert_eq!(to_f16(2.0), 0x4000);
+        assert_eq!(to_f16(MAX_HALF), 0x7bff);
+        assert_eq!(to_f16(1.0e6), 0x7bff);
+        assert_eq!(to_f16(f32::INFINITY), 0x7bff);
+        // Rounds to nearest, carrying into the exponent
+        assert_eq!(to_f16(1.0 + 1.0 / 1024.0), 0x3c01);
+        assert_eq!(to_f16(1.0 + 1.0 / 4096.0), 0x3c00);
+        assert_eq!(to_f16(1.9999), 0x4000);
+        // Subnormals
+        assert_eq!(to_f16(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(to_f16(2.0f32.powi(-15)), 0x0200);
+        assert_eq!(to_f16(2.0f32.powi(-30)), 0);
+        assert_eq!(to_f16(-1.0), 0);
+        assert_eq!(to_f16(f32::NAN), 0);
+    }
+
+    #[test]
+    fn only_8_bit_images_are_linearised() {
+        let srgb = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255, 188, 0, 128]),
+        ));
+        let linear = linear_radiance(&srgb);
+        let [r, g, b, a] = linear.pixels[0];
+        assert_eq!((r, b), (1.0, 0.0));
+        assert!((g - 0.5).abs() < 0.01, "{}", g);
+        assert!((a - 128.0 / 255.0).abs() < 1e-6);
+
+        let hdr = DynamicImage::ImageRgb32F(image::Rgb32FImage::from_pixel(
+            1,
+            1,
+            image::Rgb([4.0, 0.5, 0.0]),
+        ));
+        assert_eq!(linear_radiance(&hdr).pixels, vec![[4.0, 0.5, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn mips_average_blocks() {
+        let base = radiance(4, 2, |x, _| x as f32);
+        let mips = radiance_mips(base, 8192);
+        let sizes: Vec<(u32, u32)> = mips.iter().map(|mip| (mip.width, mip.height)).collect();
+        assert_eq!(sizes, [(4, 2), (2, 1)]);
+        assert_eq!(mips[1].pixels[0][0], 0.5);
+        assert_eq!(mips[1].pixels[1][0], 2.5);
+
+        // A single column is averaged with itself
+        let column = downsample(&radiance(1, 2, |_, y| y as f32));
+        assert_eq!((column.width, column.height), (1, 1));
+        assert_eq!(column.pixels[0][0], 0.5);
+
+        // The total light is kept
+        let bright = radiance(8, 4, |x, y| if (x, y) == (5, 2) { 64.0 } else { 0.0 });
+        let mips = radiance_mips(bright, 8192);
+        let last = mips.last().unwrap();
+        let total: f32 = last.pixels.iter().map(|pixel| pixel[0]).sum();
+        assert_eq!(total * (8 * 4 / (last.width * last.height)) as f32, 64.0);
+    }
+
+    #[test]
+    fn oversized_mips_are_dropped() {
+        let mips = radiance_mips(radiance(16, 8, |_, _| 1.0), 4);
+        assert_eq!((mips[0].width, mips[0].height), (4, 2));
+        assert_eq!(mips.len(), 2);
+        // The last mip is kept whatever the limit
+        let mips = radiance_mips(radiance(4, 2, |_, _| 1.0), 0);
+        assert_eq!(mips.len(), 1);
+        assert_eq!((mips[0].width, mips[0].height), (2, 1));
+    }
+
+    #[test]
+    fn baking_parameters() {
+        assert_eq!(source_mip(2048, 64), 5.0);
+        assert_eq!(source_mip(32, 64), 0.0);
+        assert_eq!(prefilter_roughness(0, PREFILTERED_MIPS), 0.0);
+        assert_eq!(
+            prefilter_roughness(PREFILTERED_MIPS - 1, PREFILTERED_MIPS),
+            1.0
+        );
+        assert_eq!(prefilter_roughness(0, 1), 0.0);
+        let sphere = texel_solid_angle(64, 32) * 64.0 * 32.0;
+        assert!((sphere - 4.0 * std::f32::consts::PI).abs() < 1e-4);
+    }
+}
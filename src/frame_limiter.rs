@@ -0,0 +1,258 @@
+#![allow(dead_code)]
+//! Present mode selection and CPU side frame rate limiting.
+//! wgpu 0.12 does not expose the present modes a surface supports and silently falls back to
+//! `Fifo` when configured with an unsupported mode, so the fallback is resolved here against a
+//! fixed table of what each backend of wgpu-hal 0.12 can do at best. The table is not a query of
+//! the driver, the mode that ends up presenting can still be `Fifo`.
+
+#[cfg(windows)]
+use crate::winapi_utils::TimerResolution;
+use std::time::{Duration, Instant};
+use wgpu::PresentMode;
+
+/// Time before the deadline at which the limiter stops sleeping and starts spinning.
+/// Even with a 1ms timer resolution a sleep can overshoot by about a millisecond.
+const SPIN_THRESHOLD: Duration = Duration::from_micros(1500);
+
+/// The present modes a backend is able to support, hardcoded rather than asked of the surface.
+/// On Vulkan the driver may support fewer, wgpu-core then still falls back to `Fifo`
+pub fn backend_present_modes(backend: wgpu::Backend) -> &'static [PresentMode] {
+    match backend {
+        wgpu::Backend::Vulkan => &[
+            PresentMode::Fifo,
+            PresentMode::Mailbox,
+            PresentMode::Immediate,
+        ],
+        // Tearing support through DXGI, there is no mailbox equivalent
+        wgpu::Backend::Dx12 | wgpu::Backend::Dx11 => &[PresentMode::Fifo, PresentMode::Immediate],
+        _ => &[PresentMode::Fifo],
+    }
+}
+
+/// The modes to try for a request, in order of preference.
+/// Every chain ends in `Fifo` which every surface has to support
+pub fn fallback_chain(requested: PresentMode) -> &'static [PresentMode] {
+    match requested {
+        PresentMode::Fifo => &[PresentMode::Fifo],
+        // Mailbox is chosen to avoid tearing, so never fall back to Immediate
+        PresentMode::Mailbox => &[PresentMode::Mailbox, PresentMode::Fifo],
+        PresentMode::Immediate => &[
+            PresentMode::Immediate,
+            PresentMode::Mailbox,
+            PresentMode::Fifo,
+        ],
+    }
+}
+
+/// The first mode of the fallback chain that is supported
+pub fn choose_present_mode(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    fallback_chain(requested)
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
+/// Limits how often frames are produced by sleeping until the next frame is due.
+/// Sleeps most of the wait and spins for the last moment for accurate pacing.
+#[derive(Debug)]
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    next_frame: Option<Instant>,
+    // Held while limiting so that sleeping is accurate enough
+    #[cfg(windows)]
+    timer_resolution: Option<TimerResolution>,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl FrameLimiter {
+    /// A limiter for a target frames per second, None leaves the frame rate uncapped
+    pub fn new(target_fps: Option<f32>) -> Self {
+        let mut limiter = Self {
+            frame_time: None,
+            next_frame: None,
+            #[cfg(windows)]
+            timer_resolution: None,
+        };
+        limiter.set_target_fps(target_fps);
+        limiter
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<f32>) {
+        self.frame_time = target_fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.next_frame = None;
+        #[cfg(windows)]
+        {
+            self.timer_resolution = match self.frame_time {
+                Some(_) => self
+                    .timer_resolution
+                    .take()
+                    .or_else(|| TimerResolution::new(1)),
+                None => None,
+            };
+        }
+    }
+
+    pub fn target_fps(&self) -> Option<f32> {
+        self.frame_time.map(|t| 1.0 / t.as_secs_f32())
+    }
+
+    /// Blocks until the next frame is due
+    pub fn wait(&mut self) {
+        let frame_time = match self.frame_time {
+            Some(frame_time) => frame_time,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let deadline = match self.next_frame {
+            Some(deadline) => deadline,
+            None => now,
+        };
+
+        if let Some(sleep) = sleep_duration(now, deadline, SPIN_THRESHOLD) {
+            std::thread::sleep(sleep);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        self.next_frame = Some(next_deadline(deadline, frame_time, Instant::now()));
+    }
+}
+
+/// How long to sleep before spinning up to the deadline, None if there is no time to sleep
+pub fn sleep_duration(
+    now: Instant,
+    deadline: Instant,
+    spin_threshold: Duration,
+) -> Option<Duration> {
+    deadline
+        .checked_duration_since(now)
+        .and_then(|remaining| remaining.checked_sub(spin_threshold))
+        .filter(|sleep| !sleep.is_zero())
+}
+
+/// The deadline of the frame after `deadline`.
+/// When a frame ran late the schedule restarts from now instead of rushing to catch up.
+pub fn next_deadline(deadline: Instant, frame_time: Duration, now: Instant) -> Instant {
+    let next = deadline + frame_time;
+    if next < now {
+        now
+    } else {
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [PresentMode; 3] = [
+        PresentMode::Fifo,
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+    ];
+
+    #[test]
+    fn every_request_can_fall_back_to_fifo() {
+        for requested in ALL {
+            assert_eq!(fallback_chain(requested)[0], requested);
+            assert_eq!(fallback_chain(requested).last(), Some(&PresentMode::Fifo));
+            assert_eq!(
+                choose_present_mode(requested, &[PresentMode::Fifo]),
+                PresentMode::Fifo
+            );
+        }
+        // Even a surface claiming nothing gets Fifo
+        assert_eq!(
+            choose_present_mode(PresentMode::Immediate, &[]),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn fallbacks_by_backend() {
+        let choose =
+            |requested, backend| choose_present_mode(requested, backend_present_modes(backend));
+        assert_eq!(
+            choose(PresentMode::Mailbox, wgpu::Backend::Vulkan),
+            PresentMode::Mailbox
+        );
+        assert_eq!(
+            choose(PresentMode::Immediate, wgpu::Backend::Vulkan),
+            PresentMode::Immediate
+        );
+        // Mailbox never falls back to tearing
+        assert_eq!(
+            choose(PresentMode::Mailbox, wgpu::Backend::Dx12),
+            PresentMode::Fifo
+        );
+        assert_eq!(
+            choose(PresentMode::Immediate, wgpu::Backend::Dx11),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            choose(PresentMode::Immediate, wgpu::Backend::Gl),
+            PresentMode::Fifo
+        );
+        assert_eq!(
+            choose(PresentMode::Mailbox, wgpu::Backend::Metal),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn sleeps_until_the_spin_threshold() {
+        let now = Instant::now();
+        let threshold = Duration::from_millis(2);
+        assert_eq!(
+            sleep_duration(now, now + Duration::from_millis(10), threshold),
+            Some(Duration::from_millis(8))
+        );
+        // Close to or past the deadline only spins
+        assert_eq!(sleep_duration(now, now + threshold, threshold), None);
+        assert_eq!(
+            sleep_duration(now, now + Duration::from_millis(1), threshold),
+            None
+        );
+        assert_eq!(sleep_duration(now + threshold, now, threshold), None);
+    }
+
+    #[test]
+    fn deadlines_keep_pace_or_restart_when_late() {
+        let start = Instant::now();
+        let frame = Duration::from_millis(16);
+        // On time, the schedule does not drift with when the frame finished
+        assert_eq!(
+            next_deadline(start, frame, start + Duration::from_millis(3)),
+            start + frame
+        );
+        assert_eq!(next_deadline(start, frame, start + frame), start + frame);
+        // Late by more than a frame restarts from now rather than rushing
+        let late = start + Duration::from_millis(40);
+        assert_eq!(next_deadline(start, frame, late), late);
+    }
+
+    #[test]
+    fn target_fps_ignores_invalid_rates() {
+        let mut limiter = FrameLimiter::new(Some(50.0));
+        assert_eq!(limiter.frame_time, Some(Duration::from_millis(20)));
+        assert!((limiter.target_fps().unwrap() - 50.0).abs() < 1e-3);
+        for fps in [0.0, -30.0, f32::NAN, f32::INFINITY] {
+            limiter.set_target_fps(Some(fps));
+            assert_eq!(limiter.target_fps(), None);
+        }
+        limiter.set_target_fps(None);
+        assert_eq!(limiter.target_fps(), None);
+        // Uncapped never blocks
+        limiter.wait();
+    }
+}
@@ -0,0 +1,544 @@
+#![allow(dead_code)]
+//! Frame timing statistics.
+//! CPU time is measured per stage of `Engine::render`, GPU time per pass with timestamp queries
+//! when the adapter supports `Features::TIMESTAMP_QUERY`. GPU results are read back a few frames
+//! late without stalling, each readback remembers the frame it was recorded in and its timings
+//! are filled in on that frame once they arrive.
+
+use crate::utils::noop_waker;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Number of frames kept for the rolling statistics and the CSV export
+pub const DEFAULT_HISTORY: usize = 240;
+
+/// CPU side stages of a frame
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrameStage {
+    /// Handing finished picks and dropped files to the engine, then getting the next surface
+    /// texture. Time spent waiting on the frame limiter is not counted
+    Acquire,
+    /// Uploading uniforms for the frame
+    Update,
+    /// Recording the render passes
+    Encode,
+    Submit,
+    Present,
+}
+
+impl FrameStage {
+    pub const ALL: [FrameStage; 5] = [
+        Self::Acquire,
+        Self::Update,
+        Self::Encode,
+        Self::Submit,
+        Self::Present,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Acquire => "acquire",
+            Self::Update => "update",
+            Self::Encode => "encode",
+            Self::Submit => "submit",
+            Self::Present => "present",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// The timings of a single frame
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameTiming {
+    pub cpu: [Duration; 5],
+    /// Time each gpu pass took, empty when timestamps are unsupported or not yet available.
+    /// Filled in a few frames after the frame itself is recorded
+    pub gpu: Vec<(&'static str, Duration)>,
+}
+
+impl FrameTiming {
+    pub fn stage(&self, stage: FrameStage) -> Duration {
+        self.cpu[stage.index()]
+    }
+
+    pub fn cpu_total(&self) -> Duration {
+        self.cpu.iter().sum()
+    }
+
+    pub fn gpu_total(&self) -> Duration {
+        self.gpu.iter().map(|(_, time)| *time).sum()
+    }
+}
+
+/// Measures the CPU stages of a frame, each `lap` ends the stage that was running since the
+/// previous lap
+#[derive(Clone, Debug)]
+pub struct CpuTimer {
+    last: Instant,
+    timing: FrameTiming,
+}
+
+impl CpuTimer {
+    pub fn start() -> Self {
+        Self {
+            last: Instant::now(),
+            timing: FrameTiming::default(),
+        }
+    }
+
+    pub fn lap(&mut self, stage: FrameStage) {
+        let now = Instant::now();
+        self.timing.cpu[stage.index()] += now - self.last;
+        self.last = now;
+    }
+
+    /// Leaves out the time since the last lap, for waits that are not work of the frame
+    pub fn skip(&mut self) {
+        self.last = Instant::now();
+    }
+
+    pub fn finish(self) -> FrameTiming {
+        self.timing
+    }
+}
+
+/// Minimum, average and 99th percentile in milliseconds
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub min: f32,
+    pub avg: f32,
+    pub p99: f32,
+}
+
+/// A fixed size window of samples in milliseconds
+#[derive(Clone, Debug)]
+pub struct RollingStats {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RollingStats {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample.as_secs_f32() * 1000.0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Oldest sample first
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// None until a sample has been pushed
+    pub fn summary(&self) -> Option<Summary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f32> = self.samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        Some(Summary {
+            min: sorted[0],
+            avg: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p99: percentile(&sorted, 0.99),
+        })
+    }
+}
+
+/// Nearest rank percentile of sorted samples
+pub fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p.clamp(0.0, 1.0) * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Timings of the recent frames
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    history: VecDeque<FrameTiming>,
+    capacity: usize,
+    stages: [RollingStats; 5],
+    cpu_total: RollingStats,
+    gpu_total: RollingStats,
+    frames: u64,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY)
+    }
+}
+
+impl FrameStats {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            history: VecDeque::with_capacity(capacity),
+            capacity,
+            stages: [(); 5].map(|_| RollingStats::new(capacity)),
+            cpu_total: RollingStats::new(capacity),
+            gpu_total: RollingStats::new(capacity),
+            frames: 0,
+        }
+    }
+
+    /// The index the next recorded frame will have, for tagging its gpu timings
+    pub fn next_frame(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn record(&mut self, timing: FrameTiming) {
+        for stage in FrameStage::ALL {
+            self.stages[stage.index()].push(timing.stage(stage));
+        }
+        self.cpu_total.push(timing.cpu_total());
+        if !timing.gpu.is_empty() {
+            self.gpu_total.push(timing.gpu_total());
+        }
+
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(timing);
+        self.frames += 1;
+    }
+
+    /// Attaches gpu timings that were read back late to the frame they were measured in.
+    /// Dropped if the frame has already left the history.
+    pub fn record_gpu(&mut self, frame: u64, gpu: Vec<(&'static str, Duration)>) {
+        let first = self.frames - self.history.len() as u64;
+        let timing = match frame
+            .checked_sub(first)
+            .and_then(|i| self.history.get_mut(i as usize))
+        {
+            Some(timing) => timing,
+            None => return,
+        };
+        if !gpu.is_empty() {
+            self.gpu_total
+                .push(gpu.iter().map(|(_, time)| *time).sum::<Duration>());
+        }
+        timing.gpu = gpu;
+    }
+
+    /// Number of frames kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of frames recorded in total
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn last(&self) -> Option<&FrameTiming> {
+        self.history.back()
+    }
+
+    pub fn stage(&self, stage: FrameStage) -> &RollingStats {
+        &self.stages[stage.index()]
+    }
+
+    pub fn cpu_total(&self) -> &RollingStats {
+        &self.cpu_total
+    }
+
+    pub fn gpu_total(&self) -> &RollingStats {
+        &self.gpu_total
+    }
+
+    /// Writes the frames in the history as CSV, one row per frame with times in milliseconds.
+    /// GPU passes get a column each, named after the passes seen in the history.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut passes: Vec<&'static str> = Vec::new();
+        for (pass, _) in self.history.iter().flat_map(|t| &t.gpu) {
+            if !passes.contains(pass) {
+                passes.push(pass);
+            }
+        }
+
+        write!(writer, "frame")?;
+        for stage in FrameStage::ALL {
+            write!(writer, ",cpu_{}_ms", stage.name())?;
+        }
+        write!(writer, ",cpu_total_ms")?;
+        for pass in &passes {
+            write!(writer, ",gpu_{}_ms", pass)?;
+        }
+        writeln!(writer)?;
+
+        let first = self.frames - self.history.len() as u64;
+        for (i, timing) in self.history.iter().enumerate() {
+            write!(writer, "{}", first + i as u64)?;
+            for time in timing
+                .cpu
+                .iter()
+                .chain(std::iter::once(&timing.cpu_total()))
+            {
+                write!(writer, ",{:.4}", time.as_secs_f64() * 1000.0)?;
+            }
+            for pass in &passes {
+                match timing.gpu.iter().find(|(p, _)| p == pass) {
+                    Some((_, time)) => write!(writer, ",{:.4}", time.as_secs_f64() * 1000.0)?,
+                    None => write!(writer, ",")?,
+                }
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// A readback buffer for one frame of timestamps
+struct Readback {
+    buffer: wgpu::Buffer,
+    mapping: Option<MapFuture>,
+    // The frame and passes the timestamps belong to
+    frame: u64,
+    passes: Vec<&'static str>,
+}
+
+/// Number of frames of timestamps in flight before a frame's queries are skipped
+const READBACK_FRAMES: usize = 3;
+
+/// Times gpu passes with timestamp queries.
+/// Each pass writes a timestamp before and after it, results are resolved into one of a ring of
+/// readback buffers so reading them never waits on the gpu.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    readbacks: Vec<Readback>,
+    // The frame being timed
+    frame: u64,
+    // Readback the current frame resolves into, None if all of them are still in flight
+    current: Option<usize>,
+    passes: Vec<&'static str>,
+    max_passes: u32,
+    // Nanoseconds per timestamp tick
+    period: f32,
+}
+
+impl GpuTimer {
+    /// None if the device was created without `Features::TIMESTAMP_QUERY`
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, max_passes: u32) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let count = max_passes * 2;
+        let size = (count * wgpu::QUERY_SIZE) as wgpu::BufferAddress;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu_timer_query_set"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let readbacks = (0..READBACK_FRAMES)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("gpu_timer_readback_buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                mapping: None,
+                frame: 0,
+                passes: Vec::new(),
+            })
+            .collect();
+
+        Some(Self {
+            query_set,
+            readbacks,
+            frame: 0,
+            current: None,
+            passes: Vec::new(),
+            max_passes,
+            period: queue.get_timestamp_period(),
+        })
+    }
+
+    /// Starts timing `frame`, returns false if every readback buffer is still in flight in which
+    /// case nothing is timed this frame
+    pub fn begin_frame(&mut self, frame: u64) -> bool {
+        self.frame = frame;
+        self.passes.clear();
+        self.current = self.readbacks.iter().position(|r| r.mapping.is_none());
+        self.current.is_some()
+    }
+
+    /// Writes the timestamp before a pass
+    pub fn begin_pass(&mut self, encoder: &mut wgpu::CommandEncoder, name: &'static str) {
+        if self.current.is_none() || self.passes.len() as u32 >= self.max_passes {
+            return;
+        }
+        encoder.write_timestamp(&self.query_set, self.passes.len() as u32 * 2);
+        self.passes.push(name);
+    }
+
+    /// Writes the timestamp after the pass started last
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.current.is_none() || self.passes.is_empty() {
+            return;
+        }
+        encoder.write_timestamp(&self.query_set, self.passes.len() as u32 * 2 - 1);
+    }
+
+    /// Copies the timestamps of the frame into its readback buffer
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let current = match self.current {
+            Some(current) if !self.passes.is_empty() => current,
+            _ => return,
+        };
+        let count = self.passes.len() as u32 * 2;
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..count,
+            &self.readbacks[current].buffer,
+            0,
+        );
+    }
+
+    /// Call after the frame was submitted to start reading it back
+    pub fn end_frame(&mut self) {
+        let current = match self.current.take() {
+            Some(current) if !self.passes.is_empty() => current,
+            _ => return,
+        };
+        let readback = &mut self.readbacks[current];
+        let mapping = readback.buffer.slice(..).map_async(wgpu::MapMode::Read);
+        readback.mapping = Some(Box::pin(mapping));
+        readback.frame = self.frame;
+        readback.passes = std::mem::take(&mut self.passes);
+    }
+
+    /// The frames whose pass timings have finished reading back, never blocks
+    pub fn collect(&mut self, device: &wgpu::Device) -> Vec<(u64, Vec<(&'static str, Duration)>)> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let waker = noop_waker();
+        let mut context = Context::from_waker(&waker);
+        let mut finished = Vec::new();
+        for readback in &mut self.readbacks {
+            let ready = match readback.mapping.as_mut() {
+                Some(mapping) => mapping.as_mut().poll(&mut context),
+                None => continue,
+            };
+            let result = match ready {
+                Poll::Ready(result) => result,
+                Poll::Pending => continue,
+            };
+            readback.mapping = None;
+            if result.is_err() {
+                continue;
+            }
+
+            let passes = std::mem::take(&mut readback.passes);
+            let timings = {
+                let data = readback.buffer.slice(..).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                passes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pass)| {
+                        let elapsed = ticks[i * 2 + 1].saturating_sub(ticks[i * 2]);
+                        let nanos = (elapsed as f64 * self.period as f64) as u64;
+                        (*pass, Duration::from_nanos(nanos))
+                    })
+                    .collect()
+            };
+            readback.buffer.unmap();
+            finished.push((readback.frame, timings));
+        }
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn frame(update_ms: u64) -> FrameTiming {
+        FrameTiming {
+            cpu: [ms(0), ms(update_ms), ms(0), ms(0), ms(0)],
+            gpu: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn late_gpu_timings_go_to_their_frame() {
+        let mut stats = FrameStats::new(8);
+        let timed = stats.next_frame();
+        stats.record(frame(1));
+        stats.record(frame(2));
+        stats.record(frame(3));
+        stats.record_gpu(timed, vec![("scene", ms(4))]);
+
+        assert_eq!(stats.history[0].gpu, vec![("scene", ms(4))]);
+        assert!(stats.last().unwrap().gpu.is_empty());
+        assert_eq!(stats.gpu_total().len(), 1);
+
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert!(rows[0].starts_with("frame,cpu_acquire_ms,cpu_update_ms,"));
+        assert!(rows[0].ends_with(",cpu_total_ms,gpu_scene_ms"));
+        assert!(rows[1].starts_with("0,0.0000,1.0000,") && rows[1].ends_with(",4.0000"));
+        assert!(rows[2].ends_with(','));
+    }
+
+    #[test]
+    fn gpu_timings_for_forgotten_frames_are_dropped() {
+        let mut stats = FrameStats::new(2);
+        for i in 0..4 {
+            stats.record(frame(i));
+        }
+        stats.record_gpu(0, vec![("scene", ms(1))]);
+        stats.record_gpu(9, vec![("scene", ms(1))]);
+        assert!(stats.history.iter().all(|t| t.gpu.is_empty()));
+        assert!(stats.gpu_total().is_empty());
+    }
+
+    #[test]
+    fn rolling_stats_summarise_window() {
+        let mut rolling = RollingStats::new(3);
+        assert_eq!(rolling.summary(), None);
+        for millis in [10, 1, 2, 3] {
+            rolling.push(ms(millis));
+        }
+        let summary = rolling.summary().unwrap();
+        assert_eq!((summary.min, summary.avg, summary.p99), (1.0, 2.0, 3.0));
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.0);
+        assert_eq!(percentile(&[], 0.5), 0.0);
+    }
+}
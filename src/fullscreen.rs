@@ -0,0 +1,181 @@
+#![allow(dead_code)]
+//! Which fullscreen mode a window is in and where it goes back to afterwards.
+//! The placement from before the first fullscreen request is kept until fullscreen is left,
+//! so moving between monitors or modes while fullscreen still restores the original window.
+
+use crate::coords::ScreenRect;
+use crate::monitor::{Monitor, VideoMode};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fullscreen {
+    /// A borderless window covering a monitor, the window's current one if None
+    Borderless(Option<Monitor>),
+    /// The monitor's display mode is changed with `ChangeDisplaySettingsEx` and the monitor is
+    /// covered by a borderless window. This is not exclusive fullscreen: wgpu 0.12 cannot make
+    /// the swapchain exclusive, so the surface is resized like any other and DWM still composes
+    /// the window, whether it gets flipped directly is up to the driver.
+    DisplayMode(Monitor, VideoMode),
+}
+
+impl Fullscreen {
+    /// The monitor the mode asks for, None for the window's current monitor
+    pub fn monitor(&self) -> Option<&Monitor> {
+        match self {
+            Self::Borderless(monitor) => monitor.as_ref(),
+            Self::DisplayMode(monitor, _) => Some(monitor),
+        }
+    }
+}
+
+/// Everything changed by going fullscreen
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SavedPlacement {
+    /// Outer rect of the window when it was not maximized
+    pub rect: ScreenRect,
+    /// Window style bits
+    pub style: u32,
+    pub maximized: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FullscreenState {
+    mode: Option<Fullscreen>,
+    saved: Option<SavedPlacement>,
+}
+
+impl FullscreenState {
+    pub fn mode(&self) -> Option<&Fullscreen> {
+        self.mode.as_ref()
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.mode.is_some()
+    }
+
+    /// The placement that leaving fullscreen will restore
+    pub fn saved(&self) -> Option<&SavedPlacement> {
+        self.saved.as_ref()
+    }
+
+    /// Switches to a mode, `current` is only kept if the window was not already fullscreen.
+    /// Returns the mode that was replaced.
+    pub fn enter(&mut self, mode: Fullscreen, current: SavedPlacement) -> Option<Fullscreen> {
+        if self.saved.is_none() {
+            self.saved = Some(current);
+        }
+        self.mode.replace(mode)
+    }
+
+    /// Leaves fullscreen, returning the mode that was left and the placement to restore.
+    /// None if the window was not fullscreen.
+    pub fn exit(&mut self) -> Option<(Fullscreen, SavedPlacement)> {
+        let mode = self.mode.take()?;
+        let saved = self.saved.take()?;
+        Some((mode, saved))
+    }
+}
+
+/// True if leaving `previous` for `next` has to put the display mode back first
+pub fn needs_video_mode_restore(previous: Option<&Fullscreen>, next: Option<&Fullscreen>) -> bool {
+    match (previous, next) {
+        (Some(Fullscreen::DisplayMode(previous, _)), Some(Fullscreen::DisplayMode(next, _))) => {
+            !previous.is_same(next)
+        }
+        (Some(Fullscreen::DisplayMode(..)), _) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ScreenPoint;
+    use crate::dpi::PhysicalSize;
+
+    fn monitor(name: &str) -> Monitor {
+        Monitor::for_tests(
+            name,
+            ScreenRect::new(ScreenPoint::new(0, 0), PhysicalSize::new(1920, 1080)),
+        )
+    }
+
+    fn display_mode(name: &str) -> Fullscreen {
+        let mode = VideoMode {
+            size: PhysicalSize::new(1280, 720),
+            bit_depth: 32,
+            refresh_rate: 60,
+        };
+        Fullscreen::DisplayMode(monitor(name), mode)
+    }
+
+    fn placement(x: i32) -> SavedPlacement {
+        SavedPlacement {
+            rect: ScreenRect::new(ScreenPoint::new(x, 10), PhysicalSize::new(800, 600)),
+            style: 0x00cf_0000,
+            maximized: false,
+        }
+    }
+
+    #[test]
+    fn enter_keeps_the_first_placement() {
+        let mut state = FullscreenState::default();
+        assert!(!state.is_fullscreen());
+        assert_eq!(
+            state.enter(Fullscreen::Borderless(None), placement(1)),
+            None
+        );
+        assert!(state.is_fullscreen());
+
+        // Switching modes while fullscreen must not save the fullscreen window itself
+        let replaced = state.enter(display_mode(r"\\.\DISPLAY1"), placement(2));
+        assert_eq!(replaced, Some(Fullscreen::Borderless(None)));
+        assert_eq!(state.saved(), Some(&placement(1)));
+        assert_eq!(state.mode(), Some(&display_mode(r"\\.\DISPLAY1")));
+    }
+
+    #[test]
+    fn exit_returns_the_saved_placement_once() {
+        let mut state = FullscreenState::default();
+        assert_eq!(state.exit(), None);
+        state.enter(Fullscreen::Borderless(None), placement(1));
+        assert_eq!(
+            state.exit(),
+            Some((Fullscreen::Borderless(None), placement(1)))
+        );
+        assert!(!state.is_fullscreen());
+        assert_eq!(state.saved(), None);
+        assert_eq!(state.exit(), None);
+
+        // The next time round saves afresh
+        state.enter(Fullscreen::Borderless(None), placement(3));
+        assert_eq!(state.saved(), Some(&placement(3)));
+    }
+
+    #[test]
+    fn display_mode_is_restored_when_left() {
+        let first = display_mode(r"\\.\DISPLAY1");
+        let second = display_mode(r"\\.\DISPLAY2");
+        let borderless = Fullscreen::Borderless(Some(monitor(r"\\.\DISPLAY1")));
+
+        assert!(needs_video_mode_restore(Some(&first), None));
+        assert!(needs_video_mode_restore(Some(&first), Some(&borderless)));
+        assert!(needs_video_mode_restore(Some(&first), Some(&second)));
+        // The same monitor is just switched to the new mode
+        assert!(!needs_video_mode_restore(
+            Some(&first),
+            Some(&first.clone())
+        ));
+
+        assert!(!needs_video_mode_restore(None, Some(&first)));
+        assert!(!needs_video_mode_restore(Some(&borderless), Some(&first)));
+        assert!(!needs_video_mode_restore(Some(&borderless), None));
+        assert!(!needs_video_mode_restore(None, None));
+    }
+
+    #[test]
+    fn monitor_of_each_mode() {
+        assert_eq!(Fullscreen::Borderless(None).monitor(), None);
+        let mode = display_mode(r"\\.\DISPLAY2");
+        assert_eq!(mode.monitor().unwrap().name, r"\\.\DISPLAY2");
+    }
+}
@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+//! Message loop modes and a fixed timestep scheduler.
+//! The simulation advances in fixed steps however long a frame takes, rendering then blends
+//! between the last two steps using the interpolation alpha.
+//! https://gafferongames.com/post/fix_your_timestep/
+
+use std::time::{Duration, Instant};
+
+/// Frame times are clamped to this so a long stall (a breakpoint, dragging the window) does not
+/// queue up more updates than can be caught up on
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// How the message loop waits for work
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LoopMode {
+    /// Block until a message arrives, the window only redraws when something changes
+    #[default]
+    Wait,
+    /// Never block, drain messages then update and render continuously
+    Poll,
+    /// Block until a message arrives or the deadline passes, then update and render once and
+    /// go back to `Wait`
+    WaitUntil(Instant),
+}
+
+impl LoopMode {
+    /// How long to wait for messages, None to wait without a timeout
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        match *self {
+            Self::Wait => None,
+            Self::Poll => Some(Duration::ZERO),
+            Self::WaitUntil(deadline) => Some(deadline.saturating_duration_since(now)),
+        }
+    }
+
+    /// True if the simulation should be advanced and rendered this iteration
+    pub fn should_tick(&self, now: Instant) -> bool {
+        match *self {
+            Self::Wait => false,
+            Self::Poll => true,
+            Self::WaitUntil(deadline) => now >= deadline,
+        }
+    }
+}
+
+/// Source of time for `FixedTimestep`, replaceable so the scheduler can be driven manually
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Accumulates frame time and hands it out in fixed steps
+#[derive(Clone, Debug)]
+pub struct FixedTimestep<C: Clock = SystemClock> {
+    clock: C,
+    step: Duration,
+    accumulator: Duration,
+    last: Option<Instant>,
+}
+
+impl FixedTimestep<SystemClock> {
+    pub fn new(step: Duration) -> Self {
+        Self::with_clock(step, SystemClock)
+    }
+
+    /// A timestep running at a number of updates per second
+    pub fn from_rate(updates_per_second: u32) -> Self {
+        Self::new(Duration::from_secs(1) / updates_per_second.max(1))
+    }
+}
+
+impl<C: Clock> FixedTimestep<C> {
+    pub fn with_clock(step: Duration, clock: C) -> Self {
+        Self {
+            clock,
+            // A zero step would never drain the accumulator
+            step: step.max(Duration::from_micros(1)),
+            accumulator: Duration::ZERO,
+            last: None,
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Forgets any accumulated time, e.g. after the loop was paused
+    pub fn reset(&mut self) {
+        self.accumulator = Duration::ZERO;
+        self.last = None;
+    }
+
+    /// Adds the time since the last call and returns how many fixed updates to run.
+    /// The first call only starts the clock.
+    pub fn advance(&mut self) -> u32 {
+        let now = self.clock.now();
+        let elapsed = match self.last {
+            Some(last) => now.saturating_duration_since(last).min(MAX_FRAME_TIME),
+            None => Duration::ZERO,
+        };
+        self.last = Some(now);
+        self.accumulator += elapsed;
+
+        let mut updates = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            updates += 1;
+        }
+        updates
+    }
+
+    /// How far between the previous and the next update the current time is, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock that only moves when told to
+    struct FakeClock(Instant);
+
+    impl FakeClock {
+        fn tick(&mut self, duration: Duration) {
+            self.0 += duration;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.0
+        }
+    }
+
+    fn timestep(step_ms: u64) -> FixedTimestep<FakeClock> {
+        FixedTimestep::with_clock(Duration::from_millis(step_ms), FakeClock(Instant::now()))
+    }
+
+    #[test]
+    fn first_advance_only_starts_clock() {
+        let mut timestep = timestep(10);
+        timestep.clock_mut().tick(Duration::from_secs(5));
+        assert_eq!(timestep.advance(), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn hands_out_whole_steps_and_keeps_remainder() {
+        let mut timestep = timestep(10);
+        timestep.advance();
+        timestep.clock_mut().tick(Duration::from_millis(25));
+        assert_eq!(timestep.advance(), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+        timestep.clock_mut().tick(Duration::from_millis(5));
+        assert_eq!(timestep.advance(), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut timestep = timestep(10);
+        timestep.advance();
+        timestep.clock_mut().tick(Duration::from_secs(3));
+        assert_eq!(timestep.advance(), 25);
+    }
+
+    #[test]
+    fn reset_forgets_accumulated_time() {
+        let mut timestep = timestep(10);
+        timestep.advance();
+        timestep.clock_mut().tick(Duration::from_millis(7));
+        timestep.advance();
+        timestep.reset();
+        timestep.clock_mut().tick(Duration::from_millis(7));
+        assert_eq!(timestep.advance(), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn zero_step_is_clamped() {
+        let timestep = FixedTimestep::with_clock(Duration::ZERO, FakeClock(Instant::now()));
+        assert_eq!(timestep.step(), Duration::from_micros(1));
+        assert_eq!(FixedTimestep::from_rate(0).step(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn loop_mode_timeouts() {
+        let now = Instant::now();
+        let later = now + Duration::from_millis(20);
+        assert_eq!(LoopMode::Wait.timeout(now), None);
+        assert!(!LoopMode::Wait.should_tick(now));
+        assert_eq!(LoopMode::Poll.timeout(now), Some(Duration::ZERO));
+        assert!(LoopMode::Poll.should_tick(now));
+        assert_eq!(
+            LoopMode::WaitUntil(later).timeout(now),
+            Some(Duration::from_millis(20))
+        );
+        assert!(!LoopMode::WaitUntil(later).should_tick(now));
+        assert_eq!(
+            LoopMode::WaitUntil(now).timeout(later),
+            Some(Duration::ZERO)
+        );
+        assert!(LoopMode::WaitUntil(now).should_tick(later));
+    }
+}
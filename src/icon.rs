@@ -0,0 +1,417 @@
+#![allow(dead_code)]
+//! Window and taskbar icons from RGBA images, and reading and writing `.ico` files.
+//! Windows asks for a small icon for the title bar and a large one for the taskbar and Alt+Tab,
+//! at sizes that depend on the DPI, so an `IconSet` keeps every resolution it was given and picks
+//! or scales the closest one when a size is needed.
+//!
+//! ICO files are written with PNG compressed images, which Windows has read since Vista, and
+//! read with either PNG or 32 bit uncompressed bitmap images. An ICO to embed as a resource is
+//! made from one or more images with `windows_gui make-icon <output.ico> <image>...`.
+
+use crate::utils::{read_i32, read_u16, read_u32};
+use image::{ImageEncoder, RgbaImage};
+use std::fmt;
+use std::path::Path;
+
+/// Every ICO starts with a reserved 0 and a type of 1 for icons (2 is cursors)
+const ICO_HEADER_SIZE: usize = 6;
+const ICO_ENTRY_SIZE: usize = 16;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+/// Largest icon an ICO entry can describe, stored as 0
+const MAX_ICO_SIZE: u32 = 256;
+
+#[derive(Debug)]
+pub enum IconError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    /// The set has no images
+    Empty,
+    /// The image is larger than 256 pixels or not square
+    InvalidSize {
+        width: u32,
+        height: u32,
+    },
+    /// The ICO file is malformed or uses a format other than PNG or 32 bit bitmaps
+    InvalidIco(String),
+    /// Bad command line arguments for `make-icon`
+    InvalidArguments(String),
+}
+
+impl fmt::Display for IconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Image(error) => write!(f, "{}", error),
+            Self::Empty => write!(f, "an icon needs at least one image"),
+            Self::InvalidSize { width, height } => write!(
+                f,
+                "a {}x{} image cannot be stored in an icon, they are square and at most {} pixels",
+                width, height, MAX_ICO_SIZE
+            ),
+            Self::InvalidIco(error) => write!(f, "invalid icon file: {}", error),
+            Self::InvalidArguments(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for IconError {}
+
+impl From<std::io::Error> for IconError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<image::ImageError> for IconError {
+    fn from(error: image::ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+/// The same icon at one or more resolutions
+#[derive(Clone, Debug)]
+pub struct IconSet {
+    images: Vec<RgbaImage>,
+}
+
+impl IconSet {
+    pub fn from_image(image: RgbaImage) -> Self {
+        Self {
+            images: vec![image],
+        }
+    }
+
+    pub fn from_images(images: Vec<RgbaImage>) -> Result<Self, IconError> {
+        if images.is_empty() {
+            return Err(IconError::Empty);
+        }
+        Ok(Self { images })
+    }
+
+    /// An `.ico` gives every resolution in it, any other image file gives one
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, IconError> {
+        let path = path.as_ref();
+        let is_ico = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ico"));
+        if is_ico {
+            Self::from_images(decode_ico(&std::fs::read(path)?)?)
+        } else {
+            Ok(Self::from_image(image::open(path)?.into_rgba8()))
+        }
+    }
+
+    pub fn images(&self) -> &[RgbaImage] {
+        &self.images
+    }
+
+    /// The icon at `size` x `size`, from the closest image, see `select_icon_size`
+    pub fn image_for_size(&self, size: u32) -> RgbaImage {
+        let sizes: Vec<(u32, u32)> = self.images.iter().map(|image| image.dimensions()).collect();
+        let index = select_icon_size(&sizes, size).unwrap_or(0);
+        let image = &self.images[index];
+        if image.dimensions() == (size, size) {
+            image.clone()
+        } else {
+            image::imageops::resize(image, size, size, image::imageops::FilterType::Lanczos3)
+        }
+    }
+
+    /// The set as an `.ico`, with every image scaled to fit in 256 pixels
+    pub fn to_ico(&self) -> Result<Vec<u8>, IconError> {
+        let images: Vec<RgbaImage> = self
+            .images
+            .iter()
+            .map(|image| {
+                let size = image.width().max(image.height()).min(MAX_ICO_SIZE);
+                if image.dimensions() == (size, size) {
+                    image.clone()
+                } else {
+                    self.image_for_size(size)
+                }
+            })
+            .collect();
+        encode_ico(&images)
+    }
+}
+
+/// The `make-icon` command, `args` are those after the command name
+pub fn run(args: &[String]) -> Result<(), IconError> {
+    let (output, inputs) = match args {
+        [output, inputs @ ..] if !inputs.is_empty() => (output, inputs),
+        _ => {
+            return Err(IconError::InvalidArguments(
+                "usage: make-icon <output.ico> <image>...".to_string(),
+            ))
+        }
+    };
+    let mut images = Vec::new();
+    for input in inputs {
+        images.extend(IconSet::load(input)?.images);
+    }
+    let icon = IconSet::from_images(images)?;
+    std::fs::write(output, icon.to_ico()?)?;
+    println!("Wrote {} images to {}", icon.images.len(), output);
+    Ok(())
+}
+
+/// Which of the available sizes to scale to a square of `target` pixels. Scaling down looks
+/// better than scaling up, so the smallest image at least as large is chosen, or the largest
+/// if none is. Non-square images are measured by their larger side.
+pub fn select_icon_size(available: &[(u32, u32)], target: u32) -> Option<usize> {
+    let side = |(width, height): (u32, u32)| width.max(height);
+    let larger = available
+        .iter()
+        .enumerate()
+        .filter(|(_, &size)| side(size) >= target)
+        .min_by_key(|(_, &size)| side(size))
+        .map(|(index, _)| index);
+    larger.or_else(|| {
+        available
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, &size)| side(size))
+            .map(|(index, _)| index)
+    })
+}
+
+/// Writes square images of at most 256 pixels as an ICO with PNG compressed entries
+pub fn encode_ico(images: &[RgbaImage]) -> Result<Vec<u8>, IconError> {
+    if images.is_empty() {
+        return Err(IconError::Empty);
+    }
+    let mut pngs = Vec::with_capacity(images.len());
+    for image in images {
+        let (width, height) = image.dimensions();
+        if width != height || width == 0 || width > MAX_ICO_SIZE {
+            return Err(IconError::InvalidSize { width, height });
+        }
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png).write_image(
+            image.as_raw(),
+            width,
+            height,
+            image::ColorType::Rgba8,
+        )?;
+        pngs.push(png);
+    }
+
+    let mut ico = Vec::new();
+    ico.extend_from_slice(&0u16.to_le_bytes());
+    ico.extend_from_slice(&1u16.to_le_bytes());
+    ico.extend_from_slice(&(images.len() as u16).to_le_bytes());
+    let mut offset = ICO_HEADER_SIZE + ICO_ENTRY_SIZE * images.len();
+    for (image, png) in images.iter().zip(&pngs) {
+        // 256 is stored as 0
+        let size = (image.width() % MAX_ICO_SIZE) as u8;
+        ico.extend_from_slice(&[size, size, 0, 0]);
+        // Colour planes and bits per pixel
+        ico.extend_from_slice(&1u16.to_le_bytes());
+        ico.extend_from_slice(&32u16.to_le_bytes());
+        ico.extend_from_slice(&(png.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += png.len();
+    }
+    for png in &pngs {
+        ico.extend_from_slice(png);
+    }
+    Ok(ico)
+}
+
+/// Every image in an ICO, in the order of its directory
+pub fn decode_ico(bytes: &[u8]) -> Result<Vec<RgbaImage>, IconError> {
+    let invalid = |message: &str| IconError::InvalidIco(message.to_string());
+    if read_u16(bytes, 0) != Some(0) || read_u16(bytes, 2) != Some(1) {
+        return Err(invalid("not an icon"));
+    }
+    let count = read_u16(bytes, 4).ok_or_else(|| invalid("truncated header"))? as usize;
+    if count == 0 {
+        return Err(IconError::Empty);
+    }
+
+    let mut images = Vec::with_capacity(count);
+    for index in 0..count {
+        let entry = ICO_HEADER_SIZE + index * ICO_ENTRY_SIZE;
+        let (size, offset) = read_u32(bytes, entry + 8)
+            .zip(read_u32(bytes, entry + 12))
+            .ok_or_else(|| invalid("truncated directory"))?;
+        let data = (offset as usize)
+            .checked_add(size as usize)
+            .and_then(|end| bytes.get(offset as usize..end))
+            .ok_or_else(|| invalid("image data past the end of the file"))?;
+        let image = if data.starts_with(&PNG_SIGNATURE) {
+            image::load_from_memory_with_format(data, image::ImageFormat::Png)?.into_rgba8()
+        } else {
+            decode_bitmap(data)?
+        };
+        images.push(image);
+    }
+    Ok(images)
+}
+
+/// A 32 bit `BITMAPINFOHEADER` image as stored in icons: BGRA rows with the height doubled to
+/// count the AND mask that follows, which the alpha channel makes redundant. The rows are bottom
+/// up unless the height is negative.
+fn decode_bitmap(data: &[u8]) -> Result<RgbaImage, IconError> {
+    let invalid = |message: &str| IconError::InvalidIco(message.to_string());
+    let header_size = read_u32(data, 0).ok_or_else(|| invalid("truncated bitmap"))? as usize;
+    let width = read_i32(data, 4).ok_or_else(|| invalid("truncated bitmap"))?;
+    let height = read_i32(data, 8).ok_or_else(|| invalid("truncated bitmap"))?;
+    let bit_count = read_u16(data, 14).ok_or_else(|| invalid("truncated bitmap"))?;
+    let compression = read_u32(data, 16).ok_or_else(|| invalid("truncated bitmap"))?;
+    if bit_count != 32 || compression != 0 {
+        return Err(invalid("only 32 bit uncompressed bitmaps are supported"));
+    }
+
+    let top_down = height < 0;
+    let width = u32::try_from(width).unwrap_or(0);
+    let height = height.unsigned_abs() / 2;
+    if width == 0 || height == 0 || width > MAX_ICO_SIZE || height > MAX_ICO_SIZE {
+        return Err(invalid("bitmap size out of range"));
+    }
+
+    let row = width as usize * 4;
+    let pixels = header_size
+        .checked_add(row * height as usize)
+        .and_then(|end| data.get(header_size..end))
+        .ok_or_else(|| invalid("truncated bitmap pixels"))?;
+    let mut image = RgbaImage::new(width, height);
+    for (y, source) in pixels.chunks_exact(row).enumerate() {
+        let y = if top_down {
+            y as u32
+        } else {
+            height - 1 - y as u32
+        };
+        for (x, bgra) in source.chunks_exact(4).enumerate() {
+            image.put_pixel(
+                x as u32,
+                y,
+                image::Rgba([bgra[2], bgra[1], bgra[0], bgra[3]]),
+            );
+        }
+    }
+    Ok(image)
+}
+
+/// Pixels as Windows bitmaps store them
+pub fn to_bgra(image: &RgbaImage) -> Vec<u8> {
+    image
+        .pixels()
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            image::Rgba([x as u8, y as u8, (x + y) as u8, 255 - x as u8])
+        })
+    }
+
+    /// An ICO with one 32 bit bitmap entry, `height` is that of the image, before doubling
+    fn bitmap_ico(width: i32, height: i32, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut bitmap = Vec::new();
+        bitmap.extend_from_slice(&40u32.to_le_bytes());
+        bitmap.extend_from_slice(&width.to_le_bytes());
+        bitmap.extend_from_slice(&(height * 2).to_le_bytes());
+        bitmap.extend_from_slice(&1u16.to_le_bytes());
+        bitmap.extend_from_slice(&32u16.to_le_bytes());
+        bitmap.resize(40, 0);
+        for bgra in pixels {
+            bitmap.extend_from_slice(bgra);
+        }
+
+        let mut ico = vec![0, 0, 1, 0, 1, 0];
+        ico.extend_from_slice(&[width as u8, height.unsigned_abs() as u8, 0, 0]);
+        ico.extend_from_slice(&1u16.to_le_bytes());
+        ico.extend_from_slice(&32u16.to_le_bytes());
+        ico.extend_from_slice(&(bitmap.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&((ICO_HEADER_SIZE + ICO_ENTRY_SIZE) as u32).to_le_bytes());
+        ico.extend_from_slice(&bitmap);
+        ico
+    }
+
+    #[test]
+    fn selects_the_smallest_larger_size() {
+        let available = [(16, 16), (48, 48), (32, 32), (256, 256)];
+        assert_eq!(select_icon_size(&available, 32), Some(2));
+        assert_eq!(select_icon_size(&available, 20), Some(2));
+        assert_eq!(select_icon_size(&available, 64), Some(3));
+        assert_eq!(select_icon_size(&available, 512), Some(3));
+        // Measured by the larger side
+        assert_eq!(select_icon_size(&[(16, 40), (32, 32)], 40), Some(0));
+        assert_eq!(select_icon_size(&[], 32), None);
+    }
+
+    #[test]
+    fn round_trips_through_ico() {
+        let images = vec![image(16), image(32), image(256)];
+        let ico = encode_ico(&images).unwrap();
+        assert_eq!(decode_ico(&ico).unwrap(), images);
+
+        // 256 does not fit in the size byte
+        let entry = ICO_HEADER_SIZE + 2 * ICO_ENTRY_SIZE;
+        assert_eq!(&ico[entry..entry + 2], &[0, 0]);
+        assert_eq!(ico[ICO_HEADER_SIZE], 16);
+    }
+
+    #[test]
+    fn rejects_images_an_ico_cannot_hold() {
+        assert!(matches!(encode_ico(&[]), Err(IconError::Empty)));
+        for (width, height) in [(16, 32), (0, 0), (257, 257)] {
+            assert!(matches!(
+                encode_ico(&[RgbaImage::new(width, height)]),
+                Err(IconError::InvalidSize { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn decodes_bitmaps_both_ways_up() {
+        let (red, blue) = ([0, 0, 255, 255], [255, 0, 0, 128]);
+        // Bottom up, the first row is the bottom one
+        let decoded = decode_ico(&bitmap_ico(1, 2, &[red, blue])).unwrap();
+        assert_eq!(decoded[0].get_pixel(0, 0), &image::Rgba([0, 0, 255, 128]));
+        assert_eq!(decoded[0].get_pixel(0, 1), &image::Rgba([255, 0, 0, 255]));
+
+        let decoded = decode_ico(&bitmap_ico(1, -2, &[red, blue])).unwrap();
+        assert_eq!(decoded[0].get_pixel(0, 0), &image::Rgba([255, 0, 0, 255]));
+        assert_eq!(decoded[0].get_pixel(0, 1), &image::Rgba([0, 0, 255, 128]));
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let invalid = |bytes: &[u8]| matches!(decode_ico(bytes), Err(IconError::InvalidIco(_)));
+        assert!(invalid(&[]));
+        // A cursor
+        assert!(invalid(&[0, 0, 2, 0, 1, 0]));
+        assert!(matches!(
+            decode_ico(&[0, 0, 1, 0, 0, 0]),
+            Err(IconError::Empty)
+        ));
+        assert!(invalid(&[0, 0, 1, 0, 1, 0, 16, 16]));
+
+        let pixel = [[0; 4]];
+        let ico = bitmap_ico(1, 1, &pixel);
+        assert!(invalid(&ico[..ico.len() - 1]));
+        // Image data past the end of the file
+        let mut ico = bitmap_ico(1, 1, &pixel);
+        ico[18..22].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(invalid(&ico));
+
+        assert!(invalid(&bitmap_ico(0, 1, &pixel)));
+        assert!(invalid(&bitmap_ico(-1, 1, &pixel)));
+        assert!(invalid(&bitmap_ico(1, 0, &pixel)));
+        assert!(invalid(&bitmap_ico(i32::MAX, 1, &pixel)));
+        assert!(invalid(&bitmap_ico(1, i32::MIN / 2, &pixel)));
+
+        let mut ico = bitmap_ico(1, 1, &pixel);
+        // 24 bits per pixel
+        ico[ICO_HEADER_SIZE + ICO_ENTRY_SIZE + 14] = 24;
+        assert!(invalid(&ico));
+    }
+}
@@ -5,12 +5,32 @@
 //! Some sort of Union-like bitwise-OR functionality might be intended
 //! https://docs.microsoft.com/en-us/dotnet/api/system.windows.forms.keys?view=net-5.0
 
+use crate::coords::ClientPoint;
+use crate::dpi::PhysicalSize;
+use crate::raw_input::RawEvent;
+use crate::window_state::WindowState;
+
 #[derive(Debug)]
 pub enum Input {
-    MouseMove((u32, u32)),
-    LeftClick((u32, u32)),
-    RightClick((u32, u32)),
+    MouseMove(ClientPoint),
+    /// Mouse movement while the cursor is locked, see `CursorGrab::Locked`. In pixels, or in
+    /// device units without acceleration when raw input is enabled.
+    MouseDelta((i32, i32)),
+    /// Input straight from a device, only sent while raw input is enabled
+    Raw(RawEvent),
+    LeftClick(ClientPoint),
+    RightClick(ClientPoint),
     KeyDown(KeyCode),
+    /// The window moved to a monitor with a different DPI and is being resized to `new_size`,
+    /// the size the system suggested for the new DPI
+    ScaleFactorChanged {
+        scale_factor: f64,
+        new_size: PhysicalSize,
+    },
+    /// The window was maximized, minimized or restored
+    WindowStateChanged(WindowState),
+    /// Something was copied, by this or any other application
+    ClipboardChanged,
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -0,0 +1,273 @@
+#![allow(dead_code)]
+//! Scene lighting for the lit material shader.
+//! Lights are described on the CPU and packed into a single uniform buffer. The layout of the
+//! packed structs has to follow the WGSL (std140-like) uniform rules exactly:
+//! https://www.w3.org/TR/WGSL/#address-space-layout-constraints
+
+use cgmath::InnerSpace;
+
+/// Maximum number of directional lights the shader will read from the uniform buffer
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+/// Maximum number of point lights the shader will read from the uniform buffer
+pub const MAX_POINT_LIGHTS: usize = 16;
+
+/// A light infinitely far away, such as the sun.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// The direction the light travels in, does not need to be normalised
+    pub direction: cgmath::Vector3<f32>,
+    pub colour: [f32; 3],
+    pub intensity: f32,
+}
+
+/// A light emitting in all directions from a single position.
+/// Attenuation follows `1 / (constant + linear * d + quadratic * d^2)` and is cut off at `range`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: cgmath::Point3<f32>,
+    pub colour: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl PointLight {
+    /// A point light with attenuation coefficients suited to the given range.
+    /// Values approximate the commonly used Ogre3D table
+    pub fn with_range(position: cgmath::Point3<f32>, colour: [f32; 3], range: f32) -> Self {
+        Self {
+            position,
+            colour,
+            intensity: 1.0,
+            range,
+            constant: 1.0,
+            linear: 4.5 / range,
+            quadratic: 75.0 / (range * range),
+        }
+    }
+
+    /// The attenuation factor at a distance from the light, matching the shader
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if distance > self.range {
+            return 0.0;
+        }
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+/// The complete set of lights in a scene
+#[derive(Clone, Debug)]
+pub struct Lights {
+    pub ambient_colour: [f32; 3],
+    pub ambient_strength: f32,
+    pub directional: Vec<DirectionalLight>,
+    pub point: Vec<PointLight>,
+}
+
+impl Default for Lights {
+    fn default() -> Self {
+        Self {
+            ambient_colour: [1.0, 1.0, 1.0],
+            ambient_strength: 0.1,
+            directional: vec![DirectionalLight {
+                direction: (-0.3, -1.0, -0.5).into(),
+                colour: [1.0, 1.0, 1.0],
+                intensity: 0.8,
+            }],
+            point: Vec::new(),
+        }
+    }
+}
+
+impl Lights {
+    /// Packs the lights into the uniform layout expected by the shader.
+    /// Lights beyond `MAX_DIRECTIONAL_LIGHTS` and `MAX_POINT_LIGHTS` are dropped.
+    pub fn to_uniform(&self) -> LightUniform {
+        let mut uniform = LightUniform::zeroed();
+        uniform.ambient = [
+            self.ambient_colour[0],
+            self.ambient_colour[1],
+            self.ambient_colour[2],
+            self.ambient_strength,
+        ];
+
+        for (raw, light) in uniform.directional.iter_mut().zip(&self.directional) {
+            *raw = DirectionalLightRaw::from(light);
+        }
+        for (raw, light) in uniform.point.iter_mut().zip(&self.point) {
+            *raw = PointLightRaw::from(light);
+        }
+
+        uniform.counts = [
+            self.directional.len().min(MAX_DIRECTIONAL_LIGHTS) as u32,
+            self.point.len().min(MAX_POINT_LIGHTS) as u32,
+            0,
+            0,
+        ];
+
+        uniform
+    }
+}
+
+/// Shader side representation of a directional light. 32 bytes.
+/// A vec3 has an alignment of 16 in a uniform buffer, the trailing scalar fills the gap.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DirectionalLightRaw {
+    direction: [f32; 3],
+    intensity: f32,
+    colour: [f32; 3],
+    _padding: f32,
+}
+
+impl From<&DirectionalLight> for DirectionalLightRaw {
+    fn from(light: &DirectionalLight) -> Self {
+        let direction = if light.direction.magnitude2() > 0.0 {
+            light.direction.normalize()
+        } else {
+            light.direction
+        };
+        Self {
+            direction: direction.into(),
+            intensity: light.intensity,
+            colour: light.colour,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Shader side representation of a point light. 48 bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLightRaw {
+    position: [f32; 3],
+    intensity: f32,
+    colour: [f32; 3],
+    range: f32,
+    // constant, linear, quadratic
+    attenuation: [f32; 3],
+    _padding: f32,
+}
+
+impl From<&PointLight> for PointLightRaw {
+    fn from(light: &PointLight) -> Self {
+        Self {
+            position: light.position.into(),
+            intensity: light.intensity,
+            colour: light.colour,
+            range: light.range,
+            attenuation: [light.constant, light.linear, light.quadratic],
+            _padding: 0.0,
+        }
+    }
+}
+
+/// The light uniform buffer contents.
+/// `ambient` is rgb + strength, `counts` is (directional, point, unused, unused)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    ambient: [f32; 4],
+    counts: [u32; 4],
+    directional: [DirectionalLightRaw; MAX_DIRECTIONAL_LIGHTS],
+    point: [PointLightRaw; MAX_POINT_LIGHTS],
+}
+
+impl LightUniform {
+    fn zeroed() -> Self {
+        bytemuck::Zeroable::zeroed()
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<Self>() as wgpu::BufferAddress
+                    ),
+                },
+                count: None,
+            }],
+            label: Some("light_bind_group_layout"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_sizes_match_shader_layout() {
+        assert_eq!(std::mem::size_of::<DirectionalLightRaw>(), 32);
+        assert_eq!(std::mem::size_of::<PointLightRaw>(), 48);
+        assert_eq!(
+            std::mem::size_of::<LightUniform>(),
+            32 + 32 * MAX_DIRECTIONAL_LIGHTS + 48 * MAX_POINT_LIGHTS
+        );
+    }
+
+    #[test]
+    fn packs_ambient_and_normalises_direction() {
+        let lights = Lights {
+            ambient_colour: [0.2, 0.3, 0.4],
+            ambient_strength: 0.5,
+            directional: vec![DirectionalLight {
+                direction: (0.0, -2.0, 0.0).into(),
+                colour: [1.0, 0.5, 0.25],
+                intensity: 3.0,
+            }],
+            point: Vec::new(),
+        };
+        let uniform = lights.to_uniform();
+        assert_eq!(uniform.ambient, [0.2, 0.3, 0.4, 0.5]);
+        assert_eq!(uniform.counts, [1, 0, 0, 0]);
+        assert_eq!(uniform.directional[0].direction, [0.0, -1.0, 0.0]);
+        assert_eq!(uniform.directional[0].intensity, 3.0);
+        assert_eq!(uniform.directional[0].colour, [1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn zero_direction_is_left_alone() {
+        let raw = DirectionalLightRaw::from(&DirectionalLight {
+            direction: (0.0, 0.0, 0.0).into(),
+            colour: [1.0; 3],
+            intensity: 1.0,
+        });
+        assert!(raw.direction.iter().all(|d| d.is_finite()));
+    }
+
+    #[test]
+    fn extra_lights_are_dropped() {
+        let point = PointLight::with_range((1.0, 2.0, 3.0).into(), [1.0; 3], 10.0);
+        let lights = Lights {
+            directional: vec![Lights::default().directional[0]; MAX_DIRECTIONAL_LIGHTS + 2],
+            point: vec![point; MAX_POINT_LIGHTS + 5],
+            ..Lights::default()
+        };
+        let uniform = lights.to_uniform();
+        assert_eq!(
+            uniform.counts,
+            [MAX_DIRECTIONAL_LIGHTS as u32, MAX_POINT_LIGHTS as u32, 0, 0]
+        );
+        let last = uniform.point[MAX_POINT_LIGHTS - 1];
+        assert_eq!(last.position, [1.0, 2.0, 3.0]);
+        assert_eq!(last.range, 10.0);
+        assert_eq!(last.attenuation, [1.0, 0.45, 0.75]);
+    }
+
+    #[test]
+    fn attenuation_cuts_off_at_range() {
+        let light = PointLight::with_range((0.0, 0.0, 0.0).into(), [1.0; 3], 10.0);
+        assert_eq!(light.attenuation(0.0), 1.0);
+        assert!(light.attenuation(5.0) < light.attenuation(1.0));
+        assert!(light.attenuation(10.0) > 0.0);
+        assert_eq!(light.attenuation(10.5), 0.0);
+    }
+}
@@ -1,25 +1,119 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 extern crate raw_window_handle;
+#[cfg(windows)]
 extern crate windows;
 
+mod adapter;
+mod alpha_mask;
 mod application;
+mod atlas;
+mod atlas_pack;
+mod clipboard;
+mod coords;
+#[cfg(windows)]
+mod cursor;
+#[cfg(windows)]
+mod debug_draw;
+mod dpi;
+#[cfg(windows)]
+mod drag_drop;
+mod environment;
+mod frame_limiter;
+mod frame_stats;
+mod fullscreen;
+mod game_loop;
+mod icon;
 mod input;
+mod light;
+mod material;
+mod model;
+mod monitor;
+mod path;
+mod perf_overlay;
+mod picking;
+mod placement;
+#[cfg(windows)]
+mod post;
+mod raw_input;
+mod ray;
+#[cfg(windows)]
 mod render;
+mod render_graph;
+mod surface;
+mod tessellation;
 mod utils;
+mod vector;
+#[cfg(windows)]
 mod winapi_utils;
+#[cfg(windows)]
 mod window;
+mod window_state;
 
-use crate::render::Engine;
+#[cfg(windows)]
+use crate::render::{Engine, RenderSettings};
+#[cfg(windows)]
 use crate::window::Window;
-use windows::core::Result;
 
-fn main() -> Result<()> {
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("pack-atlas") {
+        if let Err(error) = atlas_pack::run(&args[1..]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("make-icon") {
+        if let Err(error) = icon::run(&args[1..]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(error) = run_window() {
+        log::error!("{}", error);
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(windows)]
+fn run_window() -> windows::core::Result<()> {
     let mut window = Window::new("win title", "window class 01012")?;
-    let mut state = pollster::block_on(Engine::new(&window));
+    if let Some(path) = placement::default_path() {
+        if let Err(error) = window.remember_placement(path) {
+            log::warn!("Could not restore the window placement: {}", error);
+        }
+    }
+    let mut state = match pollster::block_on(Engine::new(&window, RenderSettings::default())) {
+        Ok(state) => state,
+        Err(error) => {
+            log::error!("{}", error);
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
+    state.set_pick_handler(|result| match result.hit {
+        Some(hit) => log::info!("Clicked {:?} at {:?}", hit.object, result.point),
+        None => log::info!("Clicked nothing at {:?}", result.point),
+    });
     window.set_engine(&mut state);
+    if let Err(error) = window.set_file_drop(true) {
+        log::warn!("Could not enable dropping files: {}", error);
+    }
 
     window.start();
     Ok(())
 }
+
+/// Only the commands run elsewhere, the window is built on the Windows API
+#[cfg(not(windows))]
+fn run_window() -> Result<(), String> {
+    Err(
+        "windows_gui only opens a window on Windows, pack-atlas and make-icon run anywhere"
+            .to_string(),
+    )
+}
@@ -0,0 +1,394 @@
+#![allow(dead_code)]
+//! Physically based materials following the glTF 2.0 metallic-roughness model
+//! https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#materials
+//! Each texture slot is optional, a missing texture is replaced by a 1x1 fallback texture whose
+//! value leaves the matching factor untouched so that factors alone still describe the surface.
+
+use crate::utils::create_rgba_texture;
+use image::RgbaImage;
+
+/// The texture inputs of a material, in the order they are bound in the shader
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextureSlot {
+    BaseColour,
+    MetallicRoughness,
+    Normal,
+    Occlusion,
+    Emissive,
+}
+
+impl TextureSlot {
+    pub const ALL: [TextureSlot; 5] = [
+        Self::BaseColour,
+        Self::MetallicRoughness,
+        Self::Normal,
+        Self::Occlusion,
+        Self::Emissive,
+    ];
+
+    /// Colour textures are authored in sRGB, data textures are linear
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            Self::BaseColour | Self::Emissive => wgpu::TextureFormat::Rgba8UnormSrgb,
+            _ => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// The value used when a material has no texture in this slot
+    pub fn fallback(&self) -> [u8; 4] {
+        match self {
+            // A flat tangent space normal (0, 0, 1)
+            Self::Normal => [128, 128, 255, 255],
+            // Everything else is multiplied by its factor so white is the identity
+            _ => [255, 255, 255, 255],
+        }
+    }
+
+    fn binding(&self) -> u32 {
+        2 + *self as u32
+    }
+}
+
+/// CPU side description of a metallic-roughness material
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    pub name: Option<String>,
+    pub base_colour_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    /// Fragments with a lower alpha are discarded, only used with alpha mode MASK
+    pub alpha_cutoff: Option<f32>,
+    pub base_colour_texture: Option<RgbaImage>,
+    /// Roughness is read from the green channel and metalness from the blue channel
+    pub metallic_roughness_texture: Option<RgbaImage>,
+    pub normal_texture: Option<RgbaImage>,
+    /// Occlusion is read from the red channel
+    pub occlusion_texture: Option<RgbaImage>,
+    pub emissive_texture: Option<RgbaImage>,
+}
+
+impl Default for PbrMaterial {
+    /// The glTF default material
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_colour_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0; 3],
+            alpha_cutoff: None,
+            base_colour_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl PbrMaterial {
+    /// Reads a material and its textures from an imported glTF document.
+    /// Only the first texture coordinate set is supported.
+    pub fn from_gltf(material: &gltf::Material, images: &[gltf::image::Data]) -> Self {
+        let pbr = material.pbr_metallic_roughness();
+        let image = |index: usize| images.get(index).and_then(gltf_image_to_rgba);
+
+        Self {
+            name: material.name().map(str::to_owned),
+            base_colour_factor: pbr.base_color_factor(),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            normal_scale: material.normal_texture().map_or(1.0, |t| t.scale()),
+            occlusion_strength: material.occlusion_texture().map_or(1.0, |t| t.strength()),
+            emissive_factor: material.emissive_factor(),
+            alpha_cutoff: match material.alpha_mode() {
+                gltf::material::AlphaMode::Mask => Some(material.alpha_cutoff().unwrap_or(0.5)),
+                _ => None,
+            },
+            base_colour_texture: pbr
+                .base_color_texture()
+                .and_then(|t| image(t.texture().source().index())),
+            metallic_roughness_texture: pbr
+                .metallic_roughness_texture()
+                .and_then(|t| image(t.texture().source().index())),
+            normal_texture: material
+                .normal_texture()
+                .and_then(|t| image(t.texture().source().index())),
+            occlusion_texture: material
+                .occlusion_texture()
+                .and_then(|t| image(t.texture().source().index())),
+            emissive_texture: material
+                .emissive_texture()
+                .and_then(|t| image(t.texture().source().index())),
+        }
+    }
+
+    pub fn texture(&self, slot: TextureSlot) -> Option<&RgbaImage> {
+        match slot {
+            TextureSlot::BaseColour => self.base_colour_texture.as_ref(),
+            TextureSlot::MetallicRoughness => self.metallic_roughness_texture.as_ref(),
+            TextureSlot::Normal => self.normal_texture.as_ref(),
+            TextureSlot::Occlusion => self.occlusion_texture.as_ref(),
+            TextureSlot::Emissive => self.emissive_texture.as_ref(),
+        }
+    }
+
+    pub fn to_uniform(&self) -> PbrMaterialUniform {
+        PbrMaterialUniform {
+            base_colour_factor: self.base_colour_factor,
+            emissive_factor: self.emissive_factor,
+            metallic_factor: self.metallic_factor,
+            roughness_factor: self.roughness_factor,
+            normal_scale: self.normal_scale,
+            occlusion_strength: self.occlusion_strength,
+            // A negative cutoff disables alpha testing in the shader
+            alpha_cutoff: self.alpha_cutoff.unwrap_or(-1.0),
+        }
+    }
+
+    /// Uploads the material to the gpu, using fallback textures for empty slots
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> GpuMaterial {
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("PBR Material Buffer"),
+                contents: bytemuck::cast_slice(&[self.to_uniform()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let textures: Vec<wgpu::Texture> = TextureSlot::ALL
+            .iter()
+            .map(|slot| match self.texture(*slot) {
+                Some(img) => create_rgba_texture(
+                    device,
+                    queue,
+                    img.dimensions(),
+                    img.as_raw(),
+                    slot.format(),
+                    Some("pbr_material_texture"),
+                ),
+                None => create_rgba_texture(
+                    device,
+                    queue,
+                    (1, 1),
+                    &slot.fallback(),
+                    slot.format(),
+                    Some("pbr_fallback_texture"),
+                ),
+            })
+            .collect();
+
+        let views: Vec<wgpu::TextureView> = textures
+            .iter()
+            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect();
+
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ];
+        for (slot, view) in TextureSlot::ALL.iter().zip(&views) {
+            entries.push(wgpu::BindGroupEntry {
+                binding: slot.binding(),
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &entries,
+            label: Some("pbr_material_bind_group"),
+        });
+
+        GpuMaterial {
+            bind_group,
+            uniform_buffer,
+            _textures: textures,
+        }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+        for slot in TextureSlot::ALL {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: slot.binding(),
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+        }
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("pbr_material_bind_group_layout"),
+        })
+    }
+}
+
+/// A material living on the gpu.
+/// The textures are held so that they live as long as the bind group
+pub struct GpuMaterial {
+    pub bind_group: wgpu::BindGroup,
+    pub uniform_buffer: wgpu::Buffer,
+    _textures: Vec<wgpu::Texture>,
+}
+
+/// Shader side representation of `PbrMaterial`. 48 bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrMaterialUniform {
+    base_colour_factor: [f32; 4],
+    emissive_factor: [f32; 3],
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    alpha_cutoff: f32,
+}
+
+/// Converts any of the decoded glTF pixel formats into 8 bit rgba
+fn gltf_image_to_rgba(data: &gltf::image::Data) -> Option<RgbaImage> {
+    use gltf::image::Format;
+
+    let to_u8_16 = |bytes: &[u8]| (u16::from_le_bytes([bytes[0], bytes[1]]) >> 8) as u8;
+    let to_u8_f32 = |bytes: &[u8]| {
+        let v = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        (v.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+
+    let (channels, channel_size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let mut rgba = Vec::with_capacity((data.width * data.height * 4) as usize);
+    for pixel in data.pixels.chunks_exact(channels * channel_size) {
+        let mut out = [0, 0, 0, 255];
+        for (c, bytes) in pixel.chunks_exact(channel_size).enumerate() {
+            out[c] = match channel_size {
+                1 => bytes[0],
+                2 => to_u8_16(bytes),
+                _ => to_u8_f32(bytes),
+            };
+        }
+        // Single channel images are greyscale
+        if channels == 1 {
+            out[1] = out[0];
+            out[2] = out[0];
+        }
+        rgba.extend_from_slice(&out);
+    }
+
+    RgbaImage::from_raw(data.width, data.height, rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gltf::image::{Data, Format};
+
+    fn data(format: Format, pixels: Vec<u8>) -> Data {
+        Data {
+            pixels,
+            format,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn converts_gltf_pixel_formats() {
+        let grey = gltf_image_to_rgba(&data(Format::R8, vec![7])).unwrap();
+        assert_eq!(grey.get_pixel(0, 0).0, [7, 7, 7, 255]);
+
+        let rg16 = [0xff00u16.to_le_bytes(), 0x1234u16.to_le_bytes()].concat();
+        let rg = gltf_image_to_rgba(&data(Format::R16G16, rg16)).unwrap();
+        assert_eq!(rg.get_pixel(0, 0).0, [0xff, 0x12, 0, 255]);
+
+        let floats: Vec<u8> = [0.5f32, -1.0, 2.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let rgb = gltf_image_to_rgba(&data(Format::R32G32B32FLOAT, floats)).unwrap();
+        assert_eq!(rgb.get_pixel(0, 0).0, [128, 0, 255, 255]);
+
+        // Fewer pixels than the size says
+        assert!(gltf_image_to_rgba(&data(Format::R8G8B8A8, vec![1, 2])).is_none());
+    }
+
+    #[test]
+    fn alpha_cutoff_is_disabled_unless_masked() {
+        assert_eq!(PbrMaterial::default().to_uniform().alpha_cutoff, -1.0);
+        let masked = PbrMaterial {
+            alpha_cutoff: Some(0.25),
+            ..Default::default()
+        };
+        assert_eq!(masked.to_uniform().alpha_cutoff, 0.25);
+    }
+
+    #[test]
+    fn fallbacks_leave_factors_unchanged() {
+        assert_eq!(TextureSlot::Normal.fallback(), [128, 128, 255, 255]);
+        for slot in TextureSlot::ALL {
+            assert!(slot.binding() >= 2);
+            if slot != TextureSlot::Normal {
+                assert_eq!(slot.fallback(), [255; 4]);
+            }
+        }
+        assert_eq!(
+            TextureSlot::BaseColour.format(),
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            TextureSlot::MetallicRoughness.format(),
+            wgpu::TextureFormat::Rgba8Unorm
+        );
+    }
+}
@@ -0,0 +1,408 @@
+#![allow(dead_code)]
+//! Meshes and models loaded from glTF files for the PBR pipeline.
+
+use crate::material::{GpuMaterial, PbrMaterial};
+use cgmath::{InnerSpace, Matrix, SquareMatrix};
+use std::fmt;
+use std::path::Path;
+use wgpu::util::DeviceExt;
+
+#[derive(Debug)]
+pub enum ModelError {
+    Gltf(gltf::Error),
+    /// A primitive's index refers past the end of its vertices
+    IndexOutOfRange {
+        index: u32,
+        vertex_count: usize,
+    },
+    /// A vertex attribute has a different number of elements than the positions
+    AttributeLength {
+        attribute: &'static str,
+        len: usize,
+        vertex_count: usize,
+    },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gltf(error) => write!(f, "{}", error),
+            Self::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "index {} is out of range for {} vertices",
+                index, vertex_count
+            ),
+            Self::AttributeLength {
+                attribute,
+                len,
+                vertex_count,
+            } => write!(
+                f,
+                "{} has {} elements but there are {} vertices",
+                attribute, len, vertex_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Gltf(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for ModelError {
+    fn from(error: gltf::Error) -> Self {
+        Self::Gltf(error)
+    }
+}
+
+/// Vertex layout of the PBR pipeline.
+/// The tangent w component holds the handedness of the bitangent (+1 or -1)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4],
+}
+
+impl ModelVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x3,
+        3 => Float32x4
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Mesh data on the CPU, `material` indexes into the materials loaded alongside it
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+}
+
+/// A mesh uploaded to the gpu
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_count: u32,
+    pub material: Option<usize>,
+}
+
+impl Mesh {
+    pub fn upload(device: &wgpu::Device, data: &MeshData) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(&data.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(&data.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: data.indices.len() as u32,
+            material: data.material,
+        }
+    }
+}
+
+/// A set of meshes sharing a list of materials.
+/// Meshes without a material are drawn with `default_material`
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<GpuMaterial>,
+    pub default_material: GpuMaterial,
+}
+
+/// Loads every mesh primitive of the default scene (or the first scene) of a glTF file.
+/// Node transforms are baked into the vertices.
+/// Fails if a primitive's indices or attributes do not fit its positions.
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<(Vec<MeshData>, Vec<PbrMaterial>), ModelError> {
+    let (document, buffers, images) = gltf::import(path)?;
+
+    let materials = document
+        .materials()
+        .map(|m| PbrMaterial::from_gltf(&m, &images))
+        .collect();
+
+    let mut meshes = Vec::new();
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            collect_node(&node, cgmath::Matrix4::identity(), &buffers, &mut meshes)?;
+        }
+    }
+
+    Ok((meshes, materials))
+}
+
+fn collect_node(
+    node: &gltf::Node,
+    parent: cgmath::Matrix4<f32>,
+    buffers: &[gltf::buffer::Data],
+    meshes: &mut Vec<MeshData>,
+) -> Result<(), ModelError> {
+    let transform = parent * cgmath::Matrix4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        // Normals are transformed by the inverse transpose to survive non-uniform scaling
+        let normal_matrix = transform
+            .invert()
+            .map(|m| m.transpose())
+            .unwrap_or(transform);
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(positions) => positions.collect(),
+                None => continue,
+            };
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            validate_indices(&indices, positions.len())?;
+            let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => generate_flat_normals(&positions, &indices)?,
+            };
+            let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                Some(tex_coords) => tex_coords.into_f32().collect(),
+                None => vec![[0.0, 0.0]; positions.len()],
+            };
+            validate_attribute("normals", normals.len(), positions.len())?;
+            validate_attribute("texture coordinates", tex_coords.len(), positions.len())?;
+            let tangents: Vec<[f32; 4]> = match reader.read_tangents() {
+                Some(tangents) => tangents.collect(),
+                None => generate_tangents(&positions, &normals, &tex_coords, &indices)?,
+            };
+            validate_attribute("tangents", tangents.len(), positions.len())?;
+
+            let vertices = (0..positions.len())
+                .map(|i| {
+                    let p = transform * cgmath::Vector3::from(positions[i]).extend(1.0);
+                    let n = (normal_matrix * cgmath::Vector3::from(normals[i]).extend(0.0))
+                        .truncate()
+                        .normalize();
+                    let t = tangents[i];
+                    let t3 = (transform * cgmath::Vector4::new(t[0], t[1], t[2], 0.0))
+                        .truncate()
+                        .normalize();
+                    ModelVertex {
+                        position: p.truncate().into(),
+                        tex_coords: tex_coords[i],
+                        normal: n.into(),
+                        tangent: [t3.x, t3.y, t3.z, t[3]],
+                    }
+                })
+                .collect();
+
+            meshes.push(MeshData {
+                vertices,
+                indices,
+                material: primitive.material().index(),
+            });
+        }
+    }
+
+    for child in node.children() {
+        collect_node(&child, transform, buffers, meshes)?;
+    }
+    Ok(())
+}
+
+/// Fails if any index refers past the end of `vertex_count` vertices
+pub fn validate_indices(indices: &[u32], vertex_count: usize) -> Result<(), ModelError> {
+    match indices.iter().find(|&&i| i as usize >= vertex_count) {
+        Some(&index) => Err(ModelError::IndexOutOfRange {
+            index,
+            vertex_count,
+        }),
+        None => Ok(()),
+    }
+}
+
+fn validate_attribute(
+    attribute: &'static str,
+    len: usize,
+    vertex_count: usize,
+) -> Result<(), ModelError> {
+    if len == vertex_count {
+        Ok(())
+    } else {
+        Err(ModelError::AttributeLength {
+            attribute,
+            len,
+            vertex_count,
+        })
+    }
+}
+
+/// Normals for meshes that do not supply them, each vertex takes the normal of the last
+/// triangle that references it
+pub fn generate_flat_normals(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+) -> Result<Vec<[f32; 3]>, ModelError> {
+    validate_indices(indices, positions.len())?;
+    let mut normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] =
+            [tri[0], tri[1], tri[2]].map(|i| cgmath::Vector3::from(positions[i as usize]));
+        let n = (b - a).cross(c - a);
+        if n.magnitude2() > 0.0 {
+            for i in tri {
+                normals[*i as usize] = n.normalize().into();
+            }
+        }
+    }
+    Ok(normals)
+}
+
+/// Generates per vertex tangents for normal mapping from triangle uv gradients.
+/// Tangents are accumulated over every triangle sharing a vertex, orthogonalised against the
+/// vertex normal (Gram-Schmidt) and given the handedness of the uv mapping in w.
+/// http://www.terathon.com/code/tangent.html
+pub fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+    indices: &[u32],
+) -> Result<Vec<[f32; 4]>, ModelError> {
+    validate_indices(indices, positions.len())?;
+    validate_attribute("normals", normals.len(), positions.len())?;
+    validate_attribute("texture coordinates", tex_coords.len(), positions.len())?;
+    let zero = cgmath::Vector3::new(0.0, 0.0, 0.0);
+    let mut tan = vec![zero; positions.len()];
+    let mut bitan = vec![zero; positions.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+        let p0 = cgmath::Vector3::from(positions[i0]);
+        let e1 = cgmath::Vector3::from(positions[i1]) - p0;
+        let e2 = cgmath::Vector3::from(positions[i2]) - p0;
+
+        let uv0 = tex_coords[i0];
+        let (du1, dv1) = (tex_coords[i1][0] - uv0[0], tex_coords[i1][1] - uv0[1]);
+        let (du2, dv2) = (tex_coords[i2][0] - uv0[0], tex_coords[i2][1] - uv0[1]);
+
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < f32::EPSILON {
+            // Degenerate uv mapping, this triangle has no defined tangent
+            continue;
+        }
+        let r = 1.0 / det;
+        let t = (e1 * dv2 - e2 * dv1) * r;
+        let b = (e2 * du1 - e1 * du2) * r;
+
+        for i in [i0, i1, i2] {
+            tan[i] += t;
+            bitan[i] += b;
+        }
+    }
+
+    Ok((0..positions.len())
+        .map(|i| {
+            let n = cgmath::Vector3::from(normals[i]);
+            let t = tan[i] - n * n.dot(tan[i]);
+            let t = if t.magnitude2() > f32::EPSILON {
+                t.normalize()
+            } else {
+                // Pick any vector perpendicular to the normal
+                let axis = if n.x.abs() < 0.9 {
+                    cgmath::Vector3::unit_x()
+                } else {
+                    cgmath::Vector3::unit_y()
+                };
+                n.cross(axis).normalize()
+            };
+            let w = if n.cross(t).dot(bitan[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [t.x, t.y, t.z, w]
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    #[test]
+    fn flat_normals_face_out_of_triangle() {
+        let normals = generate_flat_normals(&TRIANGLE, &[0, 1, 2]).unwrap();
+        assert_eq!(normals, vec![[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        assert!(matches!(
+            generate_flat_normals(&TRIANGLE, &[0, 1, 3]),
+            Err(ModelError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3
+            })
+        ));
+        let normals = [[0.0, 0.0, 1.0]; 3];
+        let tex_coords = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        assert!(matches!(
+            generate_tangents(&TRIANGLE, &normals, &tex_coords, &[0, 7, 2]),
+            Err(ModelError::IndexOutOfRange { index: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn short_attributes_are_rejected() {
+        let normals = [[0.0, 0.0, 1.0]; 2];
+        let tex_coords = [[0.0, 0.0]; 3];
+        assert!(matches!(
+            generate_tangents(&TRIANGLE, &normals, &tex_coords, &[0, 1, 2]),
+            Err(ModelError::AttributeLength {
+                attribute: "normals",
+                len: 2,
+                vertex_count: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn tangents_follow_uv_u_axis() {
+        let normals = [[0.0, 0.0, 1.0]; 3];
+        let tex_coords = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        let tangents = generate_tangents(&TRIANGLE, &normals, &tex_coords, &[0, 1, 2]).unwrap();
+        assert_eq!(tangents, vec![[1.0, 0.0, 0.0, 1.0]; 3]);
+    }
+}
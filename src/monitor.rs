@@ -0,0 +1,204 @@
+#![allow(dead_code)]
+//! Monitors attached to the desktop and the display modes they support.
+//! A `Monitor` is a snapshot taken when it was queried, query again after the display settings
+//! change to see new bounds.
+
+use crate::coords::ScreenRect;
+use crate::dpi::{scale_factor_from_dpi, PhysicalSize};
+#[cfg(windows)]
+use crate::{coords::ScreenPoint, winapi_utils::*};
+#[cfg(windows)]
+use windows::core::Result;
+#[cfg(windows)]
+use windows::Win32::Foundation::{HWND, RECT};
+#[cfg(windows)]
+use windows::Win32::Graphics::Gdi::{
+    DEVMODEW, DM_BITSPERPEL, DM_DISPLAYFREQUENCY, DM_PELSHEIGHT, DM_PELSWIDTH,
+    ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_MODE, HMONITOR,
+};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::MONITORINFOF_PRIMARY;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Monitor {
+    // HMONITOR
+    handle: isize,
+    // Null terminated, as display settings calls expect
+    device: [u16; 32],
+    /// Device name such as `\\.\DISPLAY1`
+    pub name: String,
+    pub bounds: ScreenRect,
+    /// The bounds without the taskbar and docked toolbars
+    pub work_area: ScreenRect,
+    pub dpi: u32,
+    /// In hertz, 0 if the driver reports the hardware default
+    pub refresh_rate: u32,
+    pub primary: bool,
+}
+
+/// A resolution, colour depth and refresh rate a monitor can be switched to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VideoMode {
+    pub size: PhysicalSize,
+    pub bit_depth: u32,
+    /// In hertz
+    pub refresh_rate: u32,
+}
+
+#[cfg(windows)]
+impl VideoMode {
+    fn from_devmode(devmode: &DEVMODEW) -> Self {
+        Self {
+            size: PhysicalSize::new(devmode.dmPelsWidth, devmode.dmPelsHeight),
+            bit_depth: devmode.dmBitsPerPel,
+            refresh_rate: devmode.dmDisplayFrequency,
+        }
+    }
+}
+
+impl Monitor {
+    /// DPI over 96
+    pub fn scale_factor(&self) -> f64 {
+        scale_factor_from_dpi(self.dpi)
+    }
+
+    /// True if both are the same display device
+    pub fn is_same(&self, other: &Monitor) -> bool {
+        self.device == other.device
+    }
+
+    /// A monitor that was never queried, named like a display device
+    #[cfg(test)]
+    pub(crate) fn for_tests(name: &str, bounds: ScreenRect) -> Self {
+        let mut device = [0; 32];
+        for (slot, unit) in device.iter_mut().zip(name.encode_utf16().take(31)) {
+            *slot = unit;
+        }
+        Self {
+            handle: 0,
+            device,
+            name: name.to_owned(),
+            bounds,
+            work_area: bounds,
+            dpi: 96,
+            refresh_rate: 60,
+            primary: false,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Monitor {
+    fn from_handle(handle: HMONITOR) -> Result<Self> {
+        let info = get_monitor_info(handle)?;
+        let device = info.szDevice;
+        let length = device.iter().position(|&c| c == 0).unwrap_or(device.len());
+        let refresh_rate = enum_display_settings(&device, ENUM_CURRENT_SETTINGS)
+            .map(|devmode| devmode.dmDisplayFrequency)
+            .unwrap_or(0);
+        Ok(Self {
+            handle: handle.0,
+            device,
+            name: String::from_utf16_lossy(&device[..length]),
+            bounds: screen_rect(&info.monitorInfo.rcMonitor),
+            work_area: screen_rect(&info.monitorInfo.rcWork),
+            dpi: get_dpi_for_monitor(handle),
+            refresh_rate,
+            primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+        })
+    }
+
+    /// The monitor with the largest part of a window on it
+    pub fn from_window(handle: HWND) -> Result<Self> {
+        Self::from_handle(monitor_from_window(handle))
+    }
+
+    /// The monitor with the largest part of a rectangle on it
+    pub fn from_rect(rect: &RECT) -> Result<Self> {
+        Self::from_handle(monitor_from_rect(rect))
+    }
+
+    /// The same monitor queried again, such as after its display mode changed
+    pub fn refresh(&self) -> Result<Self> {
+        Self::from_handle(HMONITOR(self.handle))
+    }
+
+    /// Every mode the monitor supports, without duplicates that only differ in flags the
+    /// mode does not describe
+    pub fn video_modes(&self) -> Vec<VideoMode> {
+        let mut modes: Vec<VideoMode> = Vec::new();
+        let mut index = 0;
+        while let Some(devmode) =
+            enum_display_settings(&self.device, ENUM_DISPLAY_SETTINGS_MODE(index))
+        {
+            let mode = VideoMode::from_devmode(&devmode);
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+            index += 1;
+        }
+        modes
+    }
+
+    pub fn current_video_mode(&self) -> Option<VideoMode> {
+        enum_display_settings(&self.device, ENUM_CURRENT_SETTINGS)
+            .map(|devmode| VideoMode::from_devmode(&devmode))
+    }
+
+    /// Switches the monitor to a mode until `restore_video_mode` or the application exits
+    pub fn set_video_mode(&self, mode: &VideoMode) -> Result<()> {
+        let devmode = DEVMODEW {
+            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+            dmFields: (DM_PELSWIDTH | DM_PELSHEIGHT | DM_BITSPERPEL | DM_DISPLAYFREQUENCY) as u32,
+            dmPelsWidth: mode.size.width,
+            dmPelsHeight: mode.size.height,
+            dmBitsPerPel: mode.bit_depth,
+            dmDisplayFrequency: mode.refresh_rate,
+            ..Default::default()
+        };
+        change_display_settings(&self.device, Some(&devmode))
+    }
+
+    /// Goes back to the mode set in the display settings
+    pub fn restore_video_mode(&self) -> Result<()> {
+        change_display_settings(&self.device, None)
+    }
+}
+
+/// Every monitor on the desktop, in the order the system lists them
+#[cfg(windows)]
+pub fn available_monitors() -> Result<Vec<Monitor>> {
+    enum_display_monitors()?
+        .into_iter()
+        .map(Monitor::from_handle)
+        .collect()
+}
+
+#[cfg(windows)]
+pub fn primary_monitor() -> Result<Option<Monitor>> {
+    Ok(available_monitors()?
+        .into_iter()
+        .find(|monitor| monitor.primary))
+}
+
+#[cfg(windows)]
+pub(crate) fn screen_rect(rect: &RECT) -> ScreenRect {
+    ScreenRect::new(
+        ScreenPoint::new(rect.left, rect.top),
+        PhysicalSize::new(
+            (rect.right - rect.left).max(0) as u32,
+            (rect.bottom - rect.top).max(0) as u32,
+        ),
+    )
+}
+
+#[cfg(windows)]
+pub(crate) fn win32_rect(rect: &ScreenRect) -> RECT {
+    let end = rect.bottom_right();
+    RECT {
+        left: rect.position.x,
+        top: rect.position.y,
+        right: end.x,
+        bottom: end.y,
+    }
+}
@@ -0,0 +1,413 @@
+#![allow(dead_code)]
+//! 2D vector paths in pixels, with the origin in the top left and y pointing down.
+//! Curves and arcs are kept as given and only flattened into straight segments when the path
+//! is tessellated, see `tessellation`.
+
+use cgmath::{EuclideanSpace, InnerSpace, Point2, Vector2};
+
+/// Most segments an arc is flattened into, whatever its size or tolerance
+pub const MAX_ARC_SEGMENTS: usize = 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathEvent {
+    MoveTo(Point2<f32>),
+    LineTo(Point2<f32>),
+    /// Control point and end point
+    QuadTo(Point2<f32>, Point2<f32>),
+    /// Two control points and end point
+    CubicTo(Point2<f32>, Point2<f32>, Point2<f32>),
+    /// Angles in radians, positive sweeps are clockwise on screen
+    Arc {
+        centre: Point2<f32>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+    },
+    Close,
+}
+
+/// A sequence of sub paths, each starting with a move
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    events: Vec<PathEvent>,
+}
+
+/// A flattened sub path
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Point2<f32>>,
+    /// The last point joins back to the first
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An axis aligned rectangle from its top left corner
+    pub fn rect(origin: Point2<f32>, size: Vector2<f32>) -> Self {
+        let mut path = Self::new();
+        path.move_to(origin)
+            .line_to(origin + Vector2::new(size.x, 0.0))
+            .line_to(origin + size)
+            .line_to(origin + Vector2::new(0.0, size.y))
+            .close();
+        path
+    }
+
+    pub fn circle(centre: Point2<f32>, radius: f32) -> Self {
+        let mut path = Self::new();
+        path.arc(centre, radius, 0.0, std::f32::consts::TAU).close();
+        path
+    }
+
+    pub fn events(&self) -> &[PathEvent] {
+        &self.events
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Starts a new sub path
+    pub fn move_to(&mut self, point: Point2<f32>) -> &mut Self {
+        self.events.push(PathEvent::MoveTo(point));
+        self
+    }
+
+    pub fn line_to(&mut self, point: Point2<f32>) -> &mut Self {
+        self.events.push(PathEvent::LineTo(point));
+        self
+    }
+
+    pub fn quad_to(&mut self, control: Point2<f32>, point: Point2<f32>) -> &mut Self {
+        self.events.push(PathEvent::QuadTo(control, point));
+        self
+    }
+
+    pub fn cubic_to(
+        &mut self,
+        control1: Point2<f32>,
+        control2: Point2<f32>,
+        point: Point2<f32>,
+    ) -> &mut Self {
+        self.events
+            .push(PathEvent::CubicTo(control1, control2, point));
+        self
+    }
+
+    /// A circular arc, joined to the current point with a line.
+    /// Starts a sub path if there is none
+    pub fn arc(
+        &mut self,
+        centre: Point2<f32>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+    ) -> &mut Self {
+        self.events.push(PathEvent::Arc {
+            centre,
+            radius,
+            start_angle,
+            sweep_angle,
+        });
+        self
+    }
+
+    /// Joins the current sub path back to its start
+    pub fn close(&mut self) -> &mut Self {
+        self.events.push(PathEvent::Close);
+        self
+    }
+
+    /// Straight segments within `tolerance` pixels of the curves
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let tolerance = tolerance.max(0.001);
+        let mut polylines = Vec::new();
+        let mut current: Vec<Point2<f32>> = Vec::new();
+        // Where the next sub path starts if a segment comes without a move
+        let mut start = Point2::origin();
+
+        fn finish(polylines: &mut Vec<Polyline>, points: &mut Vec<Point2<f32>>, closed: bool) {
+            if points.len() > 1 {
+                polylines.push(Polyline {
+                    points: std::mem::take(points),
+                    closed,
+                });
+            }
+            points.clear();
+        }
+
+        for event in &self.events {
+            let last = current.last().copied().unwrap_or(start);
+            // A segment without a move starts a sub path, arcs start at their own first point
+            let implicit_start =
+                current.is_empty() && !matches!(event, PathEvent::MoveTo(_) | PathEvent::Close);
+            if implicit_start && !matches!(event, PathEvent::Arc { .. }) {
+                current.push(start);
+            }
+            match *event {
+                PathEvent::MoveTo(point) => {
+                    finish(&mut polylines, &mut current, false);
+                    start = point;
+                    current.push(point);
+                }
+                PathEvent::LineTo(point) => push_point(&mut current, point),
+                PathEvent::QuadTo(control, point) => {
+                    let dd = (last.to_vec() - control.to_vec() * 2.0 + point.to_vec()).magnitude();
+                    // The chord of each step is within dd / (4 n^2) of the curve
+                    let steps = ((dd / (4.0 * tolerance)).sqrt().ceil() as usize).max(1);
+                    for i in 1..=steps {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        let p = last.to_vec() * (mt * mt)
+                            + control.to_vec() * (2.0 * mt * t)
+                            + point.to_vec() * (t * t);
+                        push_point(&mut current, Point2::from_vec(p));
+                    }
+                }
+                PathEvent::CubicTo(control1, control2, point) => {
+                    let dd1 = last.to_vec() - control1.to_vec() * 2.0 + control2.to_vec();
+                    let dd2 = control1.to_vec() - control2.to_vec() * 2.0 + point.to_vec();
+                    let dd = dd1.magnitude().max(dd2.magnitude());
+                    // The second derivative is at most 6 dd, so each chord is within 3 dd / (4 n^2)
+                    let steps = ((3.0 * dd / (4.0 * tolerance)).sqrt().ceil() as usize).max(1);
+                    for i in 1..=steps {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        let p = last.to_vec() * (mt * mt * mt)
+                            + control1.to_vec() * (3.0 * mt * mt * t)
+                            + control2.to_vec() * (3.0 * mt * t * t)
+                            + point.to_vec() * (t * t * t);
+                        push_point(&mut current, Point2::from_vec(p));
+                    }
+                }
+                PathEvent::Arc {
+                    centre,
+                    radius,
+                    start_angle,
+                    sweep_angle,
+                } => {
+                    let points = arc_points(centre, radius, start_angle, sweep_angle, tolerance);
+                    if let (true, Some(&first)) = (implicit_start, points.first()) {
+                        start = first;
+                    }
+                    for point in points {
+                        push_point(&mut current, point);
+                    }
+                }
+                PathEvent::Close => {
+                    // A sub path that already ends at its start, such as a full circle
+                    if let (Some(&first), Some(&last)) = (current.first(), current.last()) {
+                        if current.len() > 1 && (last - first).magnitude2() < 1e-8 {
+                            current.pop();
+                        }
+                    }
+                    let first = current.first().copied();
+                    finish(&mut polylines, &mut current, true);
+                    // Segments after a close continue from the start of the closed sub path
+                    if let Some(first) = first {
+                        start = first;
+                    }
+                }
+            }
+        }
+        finish(&mut polylines, &mut current, false);
+        polylines
+    }
+}
+
+/// Points along an arc, including both ends. At most `MAX_ARC_SEGMENTS` segments, none at all
+/// when any of the numbers is not finite
+pub fn arc_points(
+    centre: Point2<f32>,
+    radius: f32,
+    start_angle: f32,
+    sweep_angle: f32,
+    tolerance: f32,
+) -> Vec<Point2<f32>> {
+    if ![
+        centre.x,
+        centre.y,
+        radius,
+        start_angle,
+        sweep_angle,
+        tolerance,
+    ]
+    .iter()
+    .all(|n| n.is_finite())
+    {
+        return Vec::new();
+    }
+    let radius = radius.abs();
+    // Largest angle whose chord stays within the tolerance of the circle
+    let step = if radius > tolerance {
+        2.0 * (1.0 - tolerance / radius).acos()
+    } else {
+        std::f32::consts::FRAC_PI_2
+    };
+    let steps = ((sweep_angle.abs() / step).ceil() as usize).clamp(1, MAX_ARC_SEGMENTS);
+    (0..=steps)
+        .map(|i| {
+            let angle = start_angle + sweep_angle * i as f32 / steps as f32;
+            centre + Vector2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+/// Adds a point unless it repeats the previous one
+fn push_point(points: &mut Vec<Point2<f32>>, point: Point2<f32>) {
+    if points.last() != Some(&point) {
+        points.push(point);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Distance from a point to the segment between `a` and `b`
+    fn distance_to_segment(point: Point2<f32>, a: Point2<f32>, b: Point2<f32>) -> f32 {
+        let ab = b - a;
+        let t = ((point - a).dot(ab) / ab.magnitude2()).clamp(0.0, 1.0);
+        (point - (a + ab * t)).magnitude()
+    }
+
+    /// Furthest any of the samples is from the polyline
+    fn max_error(polyline: &Polyline, samples: impl Iterator<Item = Point2<f32>>) -> f32 {
+        samples
+            .map(|sample| {
+                polyline
+                    .points
+                    .windows(2)
+                    .map(|segment| distance_to_segment(sample, segment[0], segment[1]))
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn rect_is_one_closed_polyline() {
+        let polylines = Path::rect(Point2::new(1.0, 2.0), Vector2::new(3.0, 4.0)).flatten(0.25);
+        assert_eq!(
+            polylines,
+            [Polyline {
+                points: vec![
+                    Point2::new(1.0, 2.0),
+                    Point2::new(4.0, 2.0),
+                    Point2::new(4.0, 6.0),
+                    Point2::new(1.0, 6.0)
+                ],
+                closed: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn moves_start_sub_paths() {
+        let mut path = Path::new();
+        path.line_to(Point2::new(1.0, 0.0))
+            .move_to(Point2::new(5.0, 5.0))
+            .line_to(Point2::new(6.0, 5.0))
+            .move_to(Point2::new(9.0, 9.0));
+        let polylines = path.flatten(0.25);
+        assert_eq!(polylines.len(), 2);
+        // A segment without a move starts at the origin
+        assert_eq!(polylines[0].points[0], Point2::new(0.0, 0.0));
+        assert_eq!(polylines[1].points[0], Point2::new(5.0, 5.0));
+        assert!(!polylines[1].closed);
+    }
+
+    #[test]
+    fn full_circle_does_not_repeat_its_start() {
+        let polylines = Path::circle(Point2::new(0.0, 0.0), 10.0).flatten(0.1);
+        let points = &polylines[0].points;
+        assert!(polylines[0].closed);
+        assert!((points[0] - points[points.len() - 1]).magnitude() > 0.1);
+        assert!(points
+            .iter()
+            .all(|p| (p.to_vec().magnitude() - 10.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn arcs_stay_within_tolerance() {
+        for tolerance in [1.0, 0.25, 0.01] {
+            let points = arc_points(Point2::new(0.0, 0.0), 100.0, 0.0, 3.0, tolerance);
+            for segment in points.windows(2) {
+                let middle = segment[0].midpoint(segment[1]);
+                assert!(100.0 - middle.to_vec().magnitude() <= tolerance + 1e-3);
+            }
+        }
+        let coarse = arc_points(Point2::new(0.0, 0.0), 100.0, 0.0, 3.0, 1.0).len();
+        let fine = arc_points(Point2::new(0.0, 0.0), 100.0, 0.0, 3.0, 0.01).len();
+        assert!(fine > coarse);
+    }
+
+    #[test]
+    fn arc_segments_are_bounded() {
+        let centre = Point2::new(0.0, 0.0);
+        for (radius, sweep, tolerance) in [
+            (1e30, 3.0, 0.01),
+            (100.0, 1e30, 0.25),
+            (100.0, 3.0, 0.0),
+            (100.0, 3.0, -1.0),
+        ] {
+            let points = arc_points(centre, radius, 0.0, sweep, tolerance);
+            assert!(points.len() >= 2 && points.len() <= MAX_ARC_SEGMENTS + 1);
+        }
+        for bad in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert!(arc_points(centre, bad, 0.0, 3.0, 0.25).is_empty());
+            assert!(arc_points(centre, 10.0, bad, 3.0, 0.25).is_empty());
+            assert!(arc_points(centre, 10.0, 0.0, bad, 0.25).is_empty());
+            assert!(arc_points(centre, 10.0, 0.0, 3.0, bad).is_empty());
+            assert!(arc_points(Point2::new(bad, 0.0), 10.0, 0.0, 3.0, 0.25).is_empty());
+        }
+        // Flattening skips such arcs instead of panicking
+        let mut path = Path::new();
+        path.arc(centre, f32::NAN, 0.0, 3.0);
+        path.line_to(Point2::new(5.0, 5.0));
+        path.flatten(0.25);
+    }
+
+    #[test]
+    fn curves_stay_within_tolerance() {
+        let start = Point2::new(0.0, 0.0);
+        let (c1, c2, end) = (
+            Point2::new(30.0, 80.0),
+            Point2::new(70.0, -40.0),
+            Point2::new(100.0, 20.0),
+        );
+        for tolerance in [2.0, 0.25, 0.05] {
+            let mut quad = Path::new();
+            quad.move_to(start).quad_to(c1, end);
+            let polyline = &quad.flatten(tolerance)[0];
+            let samples = (0..=200).map(|i| {
+                let t = i as f32 / 200.0;
+                let mt = 1.0 - t;
+                Point2::from_vec(
+                    start.to_vec() * (mt * mt)
+                        + c1.to_vec() * (2.0 * mt * t)
+                        + end.to_vec() * (t * t),
+                )
+            });
+            assert!(max_error(polyline, samples) <= tolerance + 1e-3);
+
+            let mut cubic = Path::new();
+            cubic.move_to(start).cubic_to(c1, c2, end);
+            let polyline = &cubic.flatten(tolerance)[0];
+            let samples = (0..=200).map(|i| {
+                let t = i as f32 / 200.0;
+                let mt = 1.0 - t;
+                Point2::from_vec(
+                    start.to_vec() * (mt * mt * mt)
+                        + c1.to_vec() * (3.0 * mt * mt * t)
+                        + c2.to_vec() * (3.0 * mt * t * t)
+                        + end.to_vec() * (t * t * t),
+                )
+            });
+            assert!(max_error(polyline, samples) <= tolerance + 1e-3);
+            assert_eq!(*polyline.points.last().unwrap(), end);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+//! An on-screen graph of recent frame times.
+//! Each frame is a bar whose height is its CPU time, with the GPU time drawn as a narrower bar
+//! inside it. Horizontal lines mark the 60 and 30 fps budgets.
+
+use crate::frame_stats::FrameStats;
+use wgpu::include_wgsl;
+
+/// Size of the graph in pixels and its distance from the bottom left corner
+const GRAPH_SIZE: (f32, f32) = (240.0, 80.0);
+const GRAPH_MARGIN: f32 = 8.0;
+/// Frame time in milliseconds at the top of the graph
+const GRAPH_MAX_MS: f32 = 40.0;
+const BUDGETS_MS: [f32; 2] = [1000.0 / 60.0, 1000.0 / 30.0];
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const CPU_COLOUR: [f32; 4] = [0.3, 0.8, 0.3, 0.9];
+const CPU_OVER_BUDGET_COLOUR: [f32; 4] = [0.9, 0.3, 0.2, 0.9];
+const GPU_COLOUR: [f32; 4] = [0.3, 0.6, 1.0, 0.9];
+const BUDGET_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 0.4];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OverlayVertex {
+    position: [f32; 2],
+    colour: [f32; 4],
+}
+
+impl OverlayVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+pub struct PerfOverlay {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    capacity: usize,
+    vertex_count: u32,
+}
+
+impl PerfOverlay {
+    /// `max_frames` is the number of bars the graph has room for
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, max_frames: usize) -> Self {
+        let shader = device.create_shader_module(&include_wgsl!("shaders/overlay.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[OverlayVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        // A quad for the background, one per budget line and two per frame
+        let capacity = (1 + BUDGETS_MS.len() + max_frames * 2) * 6;
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overlay Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<OverlayVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            capacity,
+            vertex_count: 0,
+        }
+    }
+
+    /// Rebuilds the graph from the recorded frames
+    pub fn update(&mut self, queue: &wgpu::Queue, stats: &FrameStats, surface: (u32, u32)) {
+        let vertices = graph_vertices(stats, surface, self.capacity);
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Draws the graph over whatever is in `view`
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Triangles of the graph in clip space, at most `capacity` vertices
+fn graph_vertices(stats: &FrameStats, surface: (u32, u32), capacity: usize) -> Vec<OverlayVertex> {
+    let (width, height) = (surface.0.max(1) as f32, surface.1.max(1) as f32);
+    // Pixel rectangle with the origin in the bottom left corner to clip space
+    let mut vertices = Vec::with_capacity(capacity);
+    let mut quad = |x0: f32, y0: f32, x1: f32, y1: f32, colour: [f32; 4]| {
+        if vertices.len() + 6 > capacity {
+            return;
+        }
+        let [l, r] = [x0, x1].map(|x| x / width * 2.0 - 1.0);
+        let [b, t] = [y0, y1].map(|y| y / height * 2.0 - 1.0);
+        for position in [[l, b], [r, b], [r, t], [l, b], [r, t], [l, t]] {
+            vertices.push(OverlayVertex { position, colour });
+        }
+    };
+
+    let (left, bottom) = (GRAPH_MARGIN, GRAPH_MARGIN);
+    let (graph_width, graph_height) = GRAPH_SIZE;
+    let scale = graph_height / GRAPH_MAX_MS;
+    quad(
+        left,
+        bottom,
+        left + graph_width,
+        bottom + graph_height,
+        BACKGROUND,
+    );
+
+    let cpu = stats.cpu_total();
+    let bar_width = graph_width / stats.capacity() as f32;
+    let gpu: Vec<f32> = stats.gpu_total().samples().collect();
+    for (i, ms) in cpu.samples().enumerate() {
+        let x = left + i as f32 * bar_width;
+        let colour = if ms > BUDGETS_MS[0] {
+            CPU_OVER_BUDGET_COLOUR
+        } else {
+            CPU_COLOUR
+        };
+        quad(
+            x,
+            bottom,
+            x + bar_width,
+            bottom + ms.min(GRAPH_MAX_MS) * scale,
+            colour,
+        );
+
+        // GPU samples lag behind, line the newest up with the newest CPU sample
+        let gpu_index = i as isize - (cpu.len() as isize - gpu.len() as isize);
+        if let Some(gpu_ms) = usize::try_from(gpu_index).ok().and_then(|i| gpu.get(i)) {
+            let inset = bar_width * 0.25;
+            quad(
+                x + inset,
+                bottom,
+                x + bar_width - inset,
+                bottom + gpu_ms.min(GRAPH_MAX_MS) * scale,
+                GPU_COLOUR,
+            );
+        }
+    }
+
+    for budget in BUDGETS_MS {
+        let y = bottom + budget * scale;
+        quad(left, y, left + graph_width, y + 1.0, BUDGET_COLOUR);
+    }
+
+    vertices
+}
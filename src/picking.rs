@@ -0,0 +1,486 @@
+#![allow(dead_code)]
+//! GPU object picking.
+//! The scene is drawn again with the id of each object in place of its colour. Only the pixel
+//! under the cursor matters, so the projection is narrowed until that pixel fills a 1x1 target
+//! and nothing else is rasterised. The id and depth are then mapped back to the CPU without
+//! stalling the frame.
+
+use crate::utils::noop_waker;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wgpu::include_wgsl;
+use wgpu::util::DeviceExt;
+
+pub const PICK_ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+pub const PICK_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+/// Depth tested by the pick pass. It draws into targets of its own, so this need not match the
+/// depth buffer of the scene
+const DEPTH_TEST_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Offset of the depth in the readback buffer, texture copies have to start 256 byte aligned
+const DEPTH_OFFSET: wgpu::BufferAddress = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as _;
+
+/// Something in the scene that can be picked
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SceneObject {
+    /// The built in textured pentagon
+    Demo,
+    /// A mesh of a loaded model, both are indices in load order
+    Mesh { model: usize, mesh: usize },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pick {
+    pub object: SceneObject,
+    /// Depth buffer value at the point, 0 at the near plane and 1 at the far plane
+    pub depth: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PickResult {
+    /// The point that was picked in pixels from the top left
+    pub point: (u32, u32),
+    /// None when nothing covers the point
+    pub hit: Option<Pick>,
+}
+
+/// A draw call of the pick pass.
+/// Vertex positions have to be the first attribute, as they are for every vertex type here.
+pub struct PickDraw<'a> {
+    pub object: SceneObject,
+    pub vertex_buffer: &'a wgpu::Buffer,
+    pub vertex_stride: wgpu::BufferAddress,
+    pub index_buffer: &'a wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
+    pub index_count: u32,
+    /// Should match the pipeline the object is normally drawn with
+    pub cull_mode: Option<wgpu::Face>,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// A pick that was submitted and is being read back
+struct InFlight {
+    point: (u32, u32),
+    objects: Vec<SceneObject>,
+    mapping: MapFuture,
+}
+
+pub struct Picker {
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    shader: wgpu::ShaderModule,
+    // Built as vertex layouts are first seen, keyed by vertex stride and cull mode
+    pipelines: Vec<(
+        (wgpu::BufferAddress, Option<wgpu::Face>),
+        wgpu::RenderPipeline,
+    )>,
+    id_view: wgpu::TextureView,
+    id_texture: wgpu::Texture,
+    depth_value_texture: wgpu::Texture,
+    depth_value_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+    in_flight: Option<InFlight>,
+}
+
+impl Picker {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("pick_bind_group_layout"),
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pick Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[PickUniform {
+                view_proj: cgmath::Matrix4::from_scale(1.0).into(),
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("pick_bind_group"),
+        });
+
+        let target = |format, label| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            })
+        };
+        let id_texture = target(PICK_ID_FORMAT, "pick_id_texture");
+        let depth_value_texture = target(PICK_DEPTH_FORMAT, "pick_depth_value_texture");
+        let depth_texture = target(DEPTH_TEST_FORMAT, "pick_depth_texture");
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: DEPTH_OFFSET + 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            bind_group_layout,
+            uniform_buffer,
+            bind_group,
+            shader: device.create_shader_module(&include_wgsl!("shaders/pick.wgsl")),
+            pipelines: Vec::new(),
+            id_view: id_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            id_texture,
+            depth_value_view: depth_value_texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            depth_value_texture,
+            depth_view: depth_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            readback,
+            in_flight: None,
+        }
+    }
+
+    /// True while a pick is being read back, no new pick can be requested until it is collected
+    pub fn is_busy(&self) -> bool {
+        self.in_flight.is_some()
+    }
+
+    /// Draws the pick pass for `point` of a surface of `size` and starts reading it back.
+    /// Returns false, doing nothing, while the previous pick has not been collected.
+    pub fn request(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        view_proj: cgmath::Matrix4<f32>,
+        size: (u32, u32),
+        point: (u32, u32),
+        draws: &[PickDraw],
+    ) -> bool {
+        if self.in_flight.is_some() {
+            return false;
+        }
+
+        let view_proj = pick_matrix(point, size) * view_proj;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[PickUniform {
+                view_proj: view_proj.into(),
+            }]),
+        );
+
+        for draw in draws {
+            self.ensure_pipeline(device, draw.vertex_stride, draw.cull_mode);
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pick Pass"),
+                color_attachments: &[
+                    wgpu::RenderPassColorAttachment {
+                        view: &self.id_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            // 0 means nothing was hit
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    },
+                    wgpu::RenderPassColorAttachment {
+                        view: &self.depth_value_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                            store: true,
+                        },
+                    },
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            for (i, draw) in draws.iter().enumerate() {
+                render_pass.set_pipeline(self.pipeline(draw.vertex_stride, draw.cull_mode));
+                render_pass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(draw.index_buffer.slice(..), draw.index_format);
+                // Ids start at 1 so that 0 can mean nothing
+                let id = i as u32 + 1;
+                render_pass.draw_indexed(0..draw.index_count, 0, id..id + 1);
+            }
+        }
+
+        for (texture, offset) in [
+            (&self.id_texture, 0),
+            (&self.depth_value_texture, DEPTH_OFFSET),
+        ] {
+            encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.in_flight = Some(InFlight {
+            point,
+            objects: draws.iter().map(|draw| draw.object).collect(),
+            mapping: Box::pin(self.readback.slice(..).map_async(wgpu::MapMode::Read)),
+        });
+        true
+    }
+
+    /// The result of the pick in flight if the gpu has finished it, never blocks
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<PickResult> {
+        device.poll(wgpu::Maintain::Poll);
+        self.collect()
+    }
+
+    /// Waits for the pick in flight, None if there is none
+    pub fn wait(&mut self, device: &wgpu::Device) -> Option<PickResult> {
+        self.in_flight.as_ref()?;
+        device.poll(wgpu::Maintain::Wait);
+        self.collect()
+    }
+
+    fn collect(&mut self) -> Option<PickResult> {
+        let in_flight = self.in_flight.as_mut()?;
+        let waker = noop_waker();
+        let mut context = Context::from_waker(&waker);
+        let mapped = match in_flight.mapping.as_mut().poll(&mut context) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return None,
+        };
+        let in_flight = self.in_flight.take()?;
+
+        let hit = match mapped {
+            Ok(()) => {
+                let (id, depth) = {
+                    let data = self.readback.slice(..).get_mapped_range();
+                    let offset = DEPTH_OFFSET as usize;
+                    let id: u32 = bytemuck::pod_read_unaligned(&data[0..4]);
+                    let depth: f32 = bytemuck::pod_read_unaligned(&data[offset..offset + 4]);
+                    (id, depth)
+                };
+                self.readback.unmap();
+                resolve_id(id, &in_flight.objects).map(|object| Pick { object, depth })
+            }
+            Err(_) => None,
+        };
+
+        Some(PickResult {
+            point: in_flight.point,
+            hit,
+        })
+    }
+
+    fn ensure_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        stride: wgpu::BufferAddress,
+        cull_mode: Option<wgpu::Face>,
+    ) {
+        if self
+            .pipelines
+            .iter()
+            .any(|(key, _)| *key == (stride, cull_mode))
+        {
+            return;
+        }
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[&self.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let attributes = [wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x3,
+            offset: 0,
+            shader_location: 0,
+        }];
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Pick Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: stride,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &attributes,
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[PICK_ID_FORMAT.into(), PICK_DEPTH_FORMAT.into()],
+            }),
+            primitive: wgpu::PrimitiveState {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_TEST_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        self.pipelines.push(((stride, cull_mode), pipeline));
+    }
+
+    fn pipeline(
+        &self,
+        stride: wgpu::BufferAddress,
+        cull_mode: Option<wgpu::Face>,
+    ) -> &wgpu::RenderPipeline {
+        self.pipelines
+            .iter()
+            .find(|(key, _)| *key == (stride, cull_mode))
+            .map(|(_, pipeline)| pipeline)
+            .expect("pipelines are built before drawing")
+    }
+}
+
+/// Maps clip space so that one pixel of a surface of `size` covers the whole of clip space, with
+/// `point` (in pixels from the top left) at the centre. Depth is left as it is.
+pub fn pick_matrix(point: (u32, u32), size: (u32, u32)) -> cgmath::Matrix4<f32> {
+    let (width, height) = (size.0.max(1) as f32, size.1.max(1) as f32);
+    // Centre of the pixel in normalised device coordinates, y points up
+    let x = (point.0 as f32 + 0.5) / width * 2.0 - 1.0;
+    let y = 1.0 - (point.1 as f32 + 0.5) / height * 2.0;
+    #[rustfmt::skip]
+    let matrix = cgmath::Matrix4::new(
+        width, 0.0, 0.0, 0.0,
+        0.0, height, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        -x * width, -y * height, 0.0, 1.0,
+    );
+    matrix
+}
+
+/// The object an id written by the pick pass belongs to, ids start at 1
+pub fn resolve_id(id: u32, objects: &[SceneObject]) -> Option<SceneObject> {
+    let index = id.checked_sub(1)?;
+    objects.get(index as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Vector4, Zero};
+
+    // Where a point in normalised device coordinates ends up after the pick matrix
+    fn transform(matrix: cgmath::Matrix4<f32>, x: f32, y: f32) -> (f32, f32) {
+        let p = matrix * Vector4::new(x, y, 0.5, 1.0);
+        (p.x / p.w, p.y / p.w)
+    }
+
+    fn ndc(pixel: (f32, f32), size: (u32, u32)) -> (f32, f32) {
+        (
+            pixel.0 / size.0 as f32 * 2.0 - 1.0,
+            1.0 - pixel.1 / size.1 as f32 * 2.0,
+        )
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn picked_pixel_fills_clip_space() {
+        let size = (800, 600);
+        let point = (200, 450);
+        let matrix = pick_matrix(point, size);
+        let corner = |dx: f32, dy: f32| {
+            let (x, y) = ndc((point.0 as f32 + dx, point.1 as f32 + dy), size);
+            transform(matrix, x, y)
+        };
+        assert_near(corner(0.5, 0.5), (0.0, 0.0));
+        // The pixel's corners, y flips since pixels count down from the top
+        assert_near(corner(0.0, 0.0), (-1.0, 1.0));
+        assert_near(corner(1.0, 1.0), (1.0, -1.0));
+        // The neighbouring pixel is outside
+        assert!(corner(1.5, 0.5).0 > 1.0);
+    }
+
+    #[test]
+    fn pick_matrix_keeps_depth() {
+        let matrix = pick_matrix((3, 4), (10, 10));
+        let p = matrix * Vector4::new(0.25, -0.5, 0.75, 1.0);
+        assert_eq!(p.z, 0.75);
+        assert_eq!(p.w, 1.0);
+        // An empty surface is treated as a single pixel rather than dividing by zero
+        let matrix = pick_matrix((0, 0), (0, 0));
+        assert_near(transform(matrix, 0.0, 0.0), (0.0, 0.0));
+        assert!(!matrix.x.x.is_nan() && !matrix.w.is_zero());
+    }
+
+    #[test]
+    fn ids_start_at_one() {
+        let objects = [SceneObject::Demo, SceneObject::Mesh { model: 0, mesh: 2 }];
+        assert_eq!(resolve_id(0, &objects), None);
+        assert_eq!(resolve_id(1, &objects), Some(SceneObject::Demo));
+        assert_eq!(
+            resolve_id(2, &objects),
+            Some(SceneObject::Mesh { model: 0, mesh: 2 })
+        );
+        assert_eq!(resolve_id(3, &objects), None);
+        assert_eq!(resolve_id(u32::MAX, &objects), None);
+    }
+}
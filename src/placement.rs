@@ -0,0 +1,275 @@
+#![allow(dead_code)]
+//! Where a window was when the application closed, so the next launch can open it there.
+//! The monitors may have changed in between, so a restored placement is first fitted onto one
+//! that is still connected, see `fit_to_monitors`.
+
+use crate::coords::{ScreenPoint, ScreenRect};
+use crate::dpi::PhysicalSize;
+use crate::monitor::Monitor;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum PlacementError {
+    Io(std::io::Error),
+    /// The file is not a valid placement
+    Format(String),
+    /// The placement could not be read from or applied to the window
+    #[cfg(windows)]
+    Window(windows::core::Error),
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Format(error) => write!(f, "invalid window placement: {}", error),
+            #[cfg(windows)]
+            Self::Window(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for PlacementError {
+    fn from(error: windows::core::Error) -> Self {
+        Self::Window(error)
+    }
+}
+
+impl From<std::io::Error> for PlacementError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// A window's outer rect when neither maximized nor fullscreen, and the state on top of it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowPlacement {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    /// Device name of the monitor the window was on
+    pub monitor: String,
+    /// DPI of that monitor, the size is scaled if the DPI is different when restored
+    pub dpi: u32,
+}
+
+/// What placements are fitted against, taken from a connected monitor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonitorArea {
+    pub name: String,
+    pub work_area: ScreenRect,
+    pub dpi: u32,
+}
+
+impl From<&Monitor> for MonitorArea {
+    fn from(monitor: &Monitor) -> Self {
+        Self {
+            name: monitor.name.clone(),
+            work_area: monitor.work_area,
+            dpi: monitor.dpi,
+        }
+    }
+}
+
+impl WindowPlacement {
+    pub fn rect(&self) -> ScreenRect {
+        ScreenRect::new(
+            ScreenPoint::new(self.x, self.y),
+            PhysicalSize::new(self.width, self.height),
+        )
+    }
+
+    pub fn set_rect(&mut self, rect: ScreenRect) {
+        self.x = rect.position.x;
+        self.y = rect.position.y;
+        self.width = rect.size.width;
+        self.height = rect.size.height;
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PlacementError> {
+        let text = std::fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|error| PlacementError::Format(error.to_string()))
+    }
+
+    /// Writes the placement, creating the folder it goes in
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PlacementError> {
+        let path = path.as_ref();
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| PlacementError::Format(error.to_string()))?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Moves a placement onto a connected monitor, None if there are no monitors.
+///
+/// The window goes back to the monitor it was on if that is still connected and the window
+/// still overlaps it, otherwise to the monitor it overlaps most, otherwise to the first one.
+/// The size is scaled for a change of DPI, shrunk to fit the work area and the window is moved
+/// until it is entirely inside.
+pub fn fit_to_monitors(
+    placement: &WindowPlacement,
+    monitors: &[MonitorArea],
+) -> Option<WindowPlacement> {
+    let rect = placement.rect();
+    let same = monitors
+        .iter()
+        .find(|monitor| monitor.name == placement.monitor)
+        .filter(|monitor| overlap(&rect, &monitor.work_area) > 0);
+    let overlapping = monitors
+        .iter()
+        .map(|monitor| (overlap(&rect, &monitor.work_area), monitor))
+        .filter(|(area, _)| *area > 0)
+        .max_by_key(|(area, _)| *area)
+        .map(|(_, monitor)| monitor);
+    let monitor = same.or(overlapping).or_else(|| monitors.first())?;
+
+    let scale = if placement.dpi == 0 {
+        1.0
+    } else {
+        monitor.dpi as f64 / placement.dpi as f64
+    };
+    let size = PhysicalSize::new(
+        ((placement.width as f64 * scale).round() as u32).min(monitor.work_area.size.width),
+        ((placement.height as f64 * scale).round() as u32).min(monitor.work_area.size.height),
+    );
+
+    let area = &monitor.work_area;
+    let end = area.bottom_right();
+    let position = ScreenPoint::new(
+        rect.position
+            .x
+            .clamp(area.position.x, end.x - size.width as i32),
+        rect.position
+            .y
+            .clamp(area.position.y, end.y - size.height as i32),
+    );
+
+    let mut fitted = placement.clone();
+    fitted.set_rect(ScreenRect::new(position, size));
+    fitted.monitor = monitor.name.clone();
+    fitted.dpi = monitor.dpi;
+    Some(fitted)
+}
+
+/// Area in pixels covered by both rects
+fn overlap(a: &ScreenRect, b: &ScreenRect) -> u64 {
+    let (a_end, b_end) = (a.bottom_right(), b.bottom_right());
+    let width = a_end.x.min(b_end.x) - a.position.x.max(b.position.x);
+    let height = a_end.y.min(b_end.y) - a.position.y.max(b.position.y);
+    if width > 0 && height > 0 {
+        width as u64 * height as u64
+    } else {
+        0
+    }
+}
+
+/// `%LOCALAPPDATA%\windows_gui\window.ron`, None if there is no local app data folder
+pub fn default_path() -> Option<std::path::PathBuf> {
+    let folder = std::env::var_os("LOCALAPPDATA")?;
+    Some(Path::new(&folder).join("windows_gui").join("window.ron"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(name: &str, x: i32, width: u32, dpi: u32) -> MonitorArea {
+        MonitorArea {
+            name: name.to_owned(),
+            work_area: ScreenRect::new(ScreenPoint::new(x, 0), PhysicalSize::new(width, 1000)),
+            dpi,
+        }
+    }
+
+    fn placement(monitor: &str, x: i32, y: i32, width: u32, height: u32) -> WindowPlacement {
+        WindowPlacement {
+            x,
+            y,
+            width,
+            height,
+            maximized: false,
+            fullscreen: false,
+            monitor: monitor.to_owned(),
+            dpi: 96,
+        }
+    }
+
+    #[test]
+    fn stays_on_the_same_monitor() {
+        let monitors = [area("A", 0, 1000, 96), area("B", 1000, 1000, 96)];
+        // Mostly over B, but it was on A and still overlaps it
+        let saved = placement("A", 900, 100, 400, 300);
+        let fitted = fit_to_monitors(&saved, &monitors).unwrap();
+        assert_eq!(fitted.monitor, "A");
+        assert_eq!(
+            fitted.rect(),
+            ScreenRect::new(ScreenPoint::new(600, 100), PhysicalSize::new(400, 300))
+        );
+    }
+
+    #[test]
+    fn unchanged_when_it_fits() {
+        let monitors = [area("A", 0, 1000, 96)];
+        let saved = placement("A", 100, 100, 400, 300);
+        assert_eq!(fit_to_monitors(&saved, &monitors), Some(saved));
+    }
+
+    #[test]
+    fn moves_to_the_most_overlapped_monitor() {
+        // A is no longer connected, B has more of the window than C
+        let monitors = [area("B", 1000, 1000, 96), area("C", 2000, 1000, 96)];
+        let saved = placement("A", 1500, 100, 600, 300);
+        let fitted = fit_to_monitors(&saved, &monitors).unwrap();
+        assert_eq!(fitted.monitor, "B");
+        assert_eq!(fitted.x, 1400);
+    }
+
+    #[test]
+    fn disconnected_monitor_falls_back_to_the_first() {
+        let monitors = [area("B", 0, 1000, 96), area("C", 1000, 1000, 96)];
+        let saved = placement("A", -2000, 100, 400, 300);
+        let fitted = fit_to_monitors(&saved, &monitors).unwrap();
+        assert_eq!(fitted.monitor, "B");
+        assert_eq!((fitted.x, fitted.y), (0, 100));
+    }
+
+    #[test]
+    fn scales_for_dpi() {
+        let monitors = [area("A", 0, 2000, 192)];
+        let saved = placement("A", 100, 100, 400, 300);
+        let fitted = fit_to_monitors(&saved, &monitors).unwrap();
+        assert_eq!((fitted.width, fitted.height), (800, 600));
+        assert_eq!(fitted.dpi, 192);
+
+        // A placement without a DPI keeps its size
+        let saved = WindowPlacement { dpi: 0, ..saved };
+        let fitted = fit_to_monitors(&saved, &monitors).unwrap();
+        assert_eq!((fitted.width, fitted.height), (400, 300));
+    }
+
+    #[test]
+    fn shrinks_to_the_work_area() {
+        let monitors = [area("A", 0, 1000, 96)];
+        let saved = placement("A", 500, 500, 3000, 2000);
+        let fitted = fit_to_monitors(&saved, &monitors).unwrap();
+        assert_eq!(fitted.rect(), monitors[0].work_area);
+    }
+
+    #[test]
+    fn no_monitors() {
+        let saved = placement("A", 0, 0, 400, 300);
+        assert_eq!(fit_to_monitors(&saved, &[]), None);
+    }
+}
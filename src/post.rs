@@ -0,0 +1,585 @@
+#![allow(dead_code)]
+//! Built-in post processing effects executed through the render graph.
+//! The scene is drawn into an HDR texture by the engine, the effects below turn that into the
+//! image presented on the surface:
+//! `scene -> [bloom extract -> blur h -> blur v] -> tonemap -> [colour grade] -> [fxaa] -> surface`
+
+use crate::render_graph::{CompiledGraph, GraphError, RenderGraph, SizePolicy, TextureDesc};
+use crate::utils::create_rgba_texture;
+use wgpu::util::DeviceExt;
+use wgpu::{include_wgsl, Device, Queue};
+
+/// The format the scene is rendered in before tonemapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+pub const SCENE_PASS: &str = "scene";
+pub const SCENE_COLOUR: &str = "scene_colour";
+pub const BACKBUFFER: &str = "backbuffer";
+
+/// Edge length of the identity colour grading lookup table
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Tonemapper {
+    Reinhard = 0,
+    Aces = 1,
+    /// Clamps the colour, useful to compare against
+    None = 2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomSettings {
+    /// Brightness above which a pixel contributes to bloom
+    pub threshold: f32,
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.3,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PostSettings {
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    pub bloom: Option<BloomSettings>,
+    pub colour_grading: bool,
+    pub fxaa: bool,
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapper: Tonemapper::Aces,
+            bloom: Some(BloomSettings::default()),
+            colour_grading: false,
+            fxaa: true,
+        }
+    }
+}
+
+impl PostSettings {
+    /// Declares the passes needed for these settings.
+    /// `output_format` is the format of the surface, intermediate LDR textures share it.
+    pub fn build_graph(&self, output_format: wgpu::TextureFormat) -> RenderGraph {
+        let hdr = |size| TextureDesc {
+            format: HDR_FORMAT,
+            size,
+        };
+        let ldr = TextureDesc {
+            format: output_format,
+            size: SizePolicy::Surface,
+        };
+
+        let mut graph = RenderGraph::new();
+        graph
+            .import(BACKBUFFER)
+            .add_texture(SCENE_COLOUR, hdr(SizePolicy::Surface))
+            .add_pass(SCENE_PASS, &[], &[SCENE_COLOUR]);
+
+        let mut tonemap_inputs = vec![SCENE_COLOUR];
+        if self.bloom.is_some() {
+            graph
+                .add_texture("bloom_bright", hdr(SizePolicy::SurfaceScaled(0.5)))
+                .add_texture("bloom_blur_h", hdr(SizePolicy::SurfaceScaled(0.5)))
+                .add_texture("bloom_blur_v", hdr(SizePolicy::SurfaceScaled(0.5)))
+                .add_pass(
+                    Effect::BloomExtract.name(),
+                    &[SCENE_COLOUR],
+                    &["bloom_bright"],
+                )
+                .add_pass(
+                    Effect::BloomBlurHorizontal.name(),
+                    &["bloom_bright"],
+                    &["bloom_blur_h"],
+                )
+                .add_pass(
+                    Effect::BloomBlurVertical.name(),
+                    &["bloom_blur_h"],
+                    &["bloom_blur_v"],
+                );
+            tonemap_inputs.push("bloom_blur_v");
+        }
+
+        // The LDR chain, the last effect writes straight to the surface
+        let mut chain = vec![Effect::Tonemap];
+        if self.colour_grading {
+            chain.push(Effect::ColourGrade);
+        }
+        if self.fxaa {
+            chain.push(Effect::Fxaa);
+        }
+
+        let mut input = String::new();
+        for (i, effect) in chain.iter().enumerate() {
+            let output = if i + 1 == chain.len() {
+                BACKBUFFER.to_owned()
+            } else {
+                let name = format!("{}_output", effect.name());
+                graph.add_texture(&name, ldr);
+                name
+            };
+            if *effect == Effect::Tonemap {
+                graph.add_pass(effect.name(), &tonemap_inputs, &[&output]);
+            } else {
+                graph.add_pass(effect.name(), &[&input], &[&output]);
+            }
+            input = output;
+        }
+
+        graph
+    }
+}
+
+/// The effects that can appear in the graph, the pass name identifies the effect
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Effect {
+    BloomExtract,
+    BloomBlurHorizontal,
+    BloomBlurVertical,
+    Tonemap,
+    ColourGrade,
+    Fxaa,
+}
+
+impl Effect {
+    const ALL: [Effect; 6] = [
+        Self::BloomExtract,
+        Self::BloomBlurHorizontal,
+        Self::BloomBlurVertical,
+        Self::Tonemap,
+        Self::ColourGrade,
+        Self::Fxaa,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::BloomExtract => "bloom_extract",
+            Self::BloomBlurHorizontal => "bloom_blur_horizontal",
+            Self::BloomBlurVertical => "bloom_blur_vertical",
+            Self::Tonemap => "tonemap",
+            Self::ColourGrade => "colour_grade",
+            Self::Fxaa => "fxaa",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|e| e.name() == name)
+    }
+
+    fn fragment_entry_point(&self) -> &'static str {
+        match self {
+            Self::BloomExtract => "fs_extract",
+            Self::BloomBlurHorizontal => "fs_blur_horizontal",
+            Self::BloomBlurVertical => "fs_blur_vertical",
+            _ => "fs_main",
+        }
+    }
+
+    /// Number of textures bound after the uniform and sampler
+    fn texture_count(&self) -> u32 {
+        match self {
+            Self::Tonemap | Self::ColourGrade => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Shader side post processing settings
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    exposure: f32,
+    tonemapper: u32,
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+    lut_size: f32,
+    _padding: [f32; 3],
+}
+
+/// A graph pass ready to record
+struct PreparedPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    /// None when writing to the surface
+    target: Option<usize>,
+}
+
+pub struct PostProcess {
+    settings: PostSettings,
+    output_format: wgpu::TextureFormat,
+    size: (u32, u32),
+    compiled: CompiledGraph,
+    slots: Vec<(wgpu::Texture, wgpu::TextureView)>,
+    passes: Vec<PreparedPass>,
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    // Bound in place of bloom when it is disabled
+    black_view: wgpu::TextureView,
+    lut_view: wgpu::TextureView,
+    lut_size: u32,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        output_format: wgpu::TextureFormat,
+        size: (u32, u32),
+        settings: PostSettings,
+    ) -> Result<Self, GraphError> {
+        let compiled = settings.build_graph(output_format).compile()?;
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Process Buffer"),
+            contents: bytemuck::cast_slice(&[Self::uniform(&settings, IDENTITY_LUT_SIZE)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let black_view = create_rgba_texture(
+            device,
+            queue,
+            (1, 1),
+            &[0, 0, 0, 255],
+            wgpu::TextureFormat::Rgba8Unorm,
+            Some("post_black_texture"),
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let lut = identity_lut(IDENTITY_LUT_SIZE);
+        let lut_view = create_rgba_texture(
+            device,
+            queue,
+            lut.dimensions(),
+            lut.as_raw(),
+            wgpu::TextureFormat::Rgba8Unorm,
+            Some("colour_grading_lut"),
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut post = Self {
+            settings,
+            output_format,
+            size,
+            compiled,
+            slots: Vec::new(),
+            passes: Vec::new(),
+            uniform_buffer,
+            sampler,
+            black_view,
+            lut_view,
+            lut_size: IDENTITY_LUT_SIZE,
+        };
+        post.prepare(device);
+        Ok(post)
+    }
+
+    pub fn settings(&self) -> &PostSettings {
+        &self.settings
+    }
+
+    /// Changes the enabled effects, recompiling the graph if the passes change
+    pub fn set_settings(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        settings: PostSettings,
+    ) -> Result<(), GraphError> {
+        let compiled = settings.build_graph(self.output_format).compile()?;
+        let rebuild = compiled != self.compiled;
+        self.settings = settings;
+        self.compiled = compiled;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[Self::uniform(&self.settings, self.lut_size)]),
+        );
+        if rebuild {
+            self.prepare(device);
+        }
+        Ok(())
+    }
+
+    /// Replaces the colour grading lookup table.
+    /// The image is a strip of `size` slices of `size x size` texels each
+    pub fn set_colour_grading_lut(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        lut: &image::RgbaImage,
+    ) {
+        self.lut_size = lut.height();
+        self.lut_view = create_rgba_texture(
+            device,
+            queue,
+            lut.dimensions(),
+            lut.as_raw(),
+            wgpu::TextureFormat::Rgba8Unorm,
+            Some("colour_grading_lut"),
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[Self::uniform(&self.settings, self.lut_size)]),
+        );
+        self.prepare(device);
+    }
+
+    /// Reallocates the intermediate textures to follow the surface size
+    pub fn resize(&mut self, device: &Device, size: (u32, u32)) {
+        if size != self.size {
+            self.size = size;
+            self.prepare(device);
+        }
+    }
+
+    /// The texture the scene pass has to render into
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        let slot = self
+            .compiled
+            .slot(SCENE_COLOUR)
+            .expect("the scene texture is always part of the graph");
+        &self.slots[slot].1
+    }
+
+    /// Records every effect pass, the last one writes to `output`
+    pub fn execute(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        for pass in &self.passes {
+            let target = match pass.target {
+                Some(slot) => &self.slots[slot].1,
+                None => output,
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Process Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&pass.pipeline);
+            render_pass.set_bind_group(0, &pass.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    fn uniform(settings: &PostSettings, lut_size: u32) -> PostUniform {
+        let bloom = settings.bloom.unwrap_or(BloomSettings {
+            threshold: 0.0,
+            intensity: 0.0,
+        });
+        PostUniform {
+            exposure: settings.exposure,
+            tonemapper: settings.tonemapper as u32,
+            bloom_threshold: bloom.threshold,
+            bloom_intensity: bloom.intensity,
+            lut_size: lut_size as f32,
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// Allocates the graph textures and builds a pipeline and bind group for each effect pass
+    fn prepare(&mut self, device: &Device) {
+        self.slots = self
+            .compiled
+            .slots
+            .iter()
+            .map(|desc| {
+                let (width, height) = desc.size.resolve(self.size);
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("render_graph_texture"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: desc.format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (texture, view)
+            })
+            .collect();
+
+        let mut passes = Vec::new();
+        for pass in &self.compiled.passes {
+            // The scene pass is recorded by the engine
+            let effect = match Effect::from_name(&pass.name) {
+                Some(effect) => effect,
+                None => continue,
+            };
+
+            let target = self.compiled.slot(&pass.outputs[0]);
+            let target_format = match target {
+                Some(slot) => self.compiled.slots[slot].format,
+                None => self.output_format,
+            };
+
+            let mut views: Vec<&wgpu::TextureView> = pass
+                .inputs
+                .iter()
+                .map(|input| &self.slots[self.compiled.slot(input).unwrap()].1)
+                .collect();
+            match effect {
+                Effect::Tonemap if views.len() < 2 => views.push(&self.black_view),
+                Effect::ColourGrade => views.push(&self.lut_view),
+                _ => {}
+            }
+
+            let layout = effect_bind_group_layout(device, effect);
+            let mut entries = vec![
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ];
+            for (i, view) in views.iter().enumerate() {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: 2 + i as u32,
+                    resource: wgpu::BindingResource::TextureView(view),
+                });
+            }
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &layout,
+                entries: &entries,
+                label: Some("post_process_bind_group"),
+            });
+
+            passes.push(PreparedPass {
+                pipeline: effect_pipeline(device, effect, &layout, target_format),
+                bind_group,
+                target,
+            });
+        }
+        self.passes = passes;
+    }
+}
+
+fn effect_bind_group_layout(device: &Device, effect: Effect) -> wgpu::BindGroupLayout {
+    let mut entries = vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ];
+    for i in 0..effect.texture_count() {
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 2 + i,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        });
+    }
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &entries,
+        label: Some("post_process_bind_group_layout"),
+    })
+}
+
+fn effect_pipeline(
+    device: &Device,
+    effect: Effect,
+    layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = match effect {
+        Effect::BloomExtract | Effect::BloomBlurHorizontal | Effect::BloomBlurVertical => {
+            device.create_shader_module(&include_wgsl!("shaders/post/bloom.wgsl"))
+        }
+        Effect::Tonemap => device.create_shader_module(&include_wgsl!("shaders/post/tonemap.wgsl")),
+        Effect::ColourGrade => {
+            device.create_shader_module(&include_wgsl!("shaders/post/colour_grade.wgsl"))
+        }
+        Effect::Fxaa => device.create_shader_module(&include_wgsl!("shaders/post/fxaa.wgsl")),
+    };
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Post Process Pipeline Layout"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(effect.name()),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_fullscreen",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: effect.fragment_entry_point(),
+            targets: &[wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// A lookup table that maps every colour onto itself, laid out as expected by the shader
+pub fn identity_lut(size: u32) -> image::RgbaImage {
+    let scale = 255.0 / (size - 1) as f32;
+    image::RgbaImage::from_fn(size * size, size, |x, y| {
+        let r = x % size;
+        let b = x / size;
+        image::Rgba([
+            (r as f32 * scale).round() as u8,
+            (y as f32 * scale).round() as u8,
+            (b as f32 * scale).round() as u8,
+            255,
+        ])
+    })
+}
@@ -0,0 +1,355 @@
+#![allow(dead_code)]
+//! Raw input from `WM_INPUT`: mouse movement as the device reports it, without pointer
+//! acceleration or clamping to the screen, and which device each event came from.
+//! The `RAWINPUT` data is read from the bytes `GetRawInputData` fills rather than through the
+//! struct so the parsing works on plain buffers.
+
+use crate::utils::{read_isize, read_u16, read_u32};
+
+/// `RAWINPUTHEADER`: type and size, then a device handle and a wparam that are pointer sized
+pub const HEADER_SIZE: usize = 8 + 2 * std::mem::size_of::<usize>();
+/// `RAWMOUSE`
+const MOUSE_SIZE: usize = 24;
+/// `RAWKEYBOARD`
+const KEYBOARD_SIZE: usize = 16;
+
+// RAWINPUTHEADER::dwType
+const RIM_TYPEMOUSE: u32 = 0;
+const RIM_TYPEKEYBOARD: u32 = 1;
+
+// RAWMOUSE::usFlags
+const MOUSE_MOVE_ABSOLUTE: u16 = 0x01;
+
+// RAWMOUSE::usButtonFlags, pairs of down and up for each button
+const RI_MOUSE_BUTTONS: [(u16, u16, MouseButton); 5] = [
+    (0x0001, 0x0002, MouseButton::Left),
+    (0x0004, 0x0008, MouseButton::Right),
+    (0x0010, 0x0020, MouseButton::Middle),
+    (0x0040, 0x0080, MouseButton::Back),
+    (0x0100, 0x0200, MouseButton::Forward),
+];
+const RI_MOUSE_WHEEL: u16 = 0x0400;
+const RI_MOUSE_HWHEEL: u16 = 0x0800;
+
+// RAWKEYBOARD::Flags
+const RI_KEY_BREAK: u16 = 0x01;
+const RI_KEY_E0: u16 = 0x02;
+/// RAWKEYBOARD::VKey of the extra events some keys send, such as the fake shift around the
+/// navigation keys
+const VK_FAKE: u16 = 0xff;
+
+// WM_INPUT_DEVICE_CHANGE wparam
+const GIDC_ARRIVAL: usize = 1;
+const GIDC_REMOVAL: usize = 2;
+
+/// The device handle raw input reports, the same for every event from one mouse or keyboard
+/// until it is disconnected
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceId(pub isize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+    Mouse,
+    Keyboard,
+    /// Any other human interface device, such as a game controller
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RawEvent {
+    /// Movement in device units (mickeys), without acceleration
+    MouseMotion {
+        device: DeviceId,
+        delta: (i32, i32),
+    },
+    /// Position from a device that reports absolute coordinates, such as a pen or a remote
+    /// desktop session, normalised to 0..=65535 over the screen
+    MouseAbsolute {
+        device: DeviceId,
+        position: (i32, i32),
+    },
+    MouseButton {
+        device: DeviceId,
+        button: MouseButton,
+        pressed: bool,
+    },
+    /// In multiples of 120 per notch, positive away from the user (or to the right)
+    MouseWheel {
+        device: DeviceId,
+        delta: i16,
+        horizontal: bool,
+    },
+    Key {
+        device: DeviceId,
+        /// Virtual key code
+        key: u16,
+        /// Scan code, with 0xe000 set for the extended keys
+        scan_code: u16,
+        pressed: bool,
+    },
+    DeviceAdded(DeviceId),
+    DeviceRemoved(DeviceId),
+}
+
+/// The events in one `RAWINPUT`, in the order they happened. Empty if the buffer is too short
+/// or from a device that is not a mouse or keyboard.
+pub fn parse_raw_input(bytes: &[u8]) -> Vec<RawEvent> {
+    let mut events = Vec::new();
+    let kind = match read_u32(bytes, 0) {
+        Some(kind) => kind,
+        None => return events,
+    };
+    let device = match read_isize(bytes, 8) {
+        Some(handle) => DeviceId(handle),
+        None => return events,
+    };
+    match kind {
+        RIM_TYPEMOUSE => parse_mouse(bytes, device, &mut events),
+        RIM_TYPEKEYBOARD => parse_keyboard(bytes, device, &mut events),
+        _ => {}
+    }
+    events
+}
+
+/// The event for a `WM_INPUT_DEVICE_CHANGE`
+pub fn parse_device_change(wparam: usize, lparam: isize) -> Option<RawEvent> {
+    match wparam {
+        GIDC_ARRIVAL => Some(RawEvent::DeviceAdded(DeviceId(lparam))),
+        GIDC_REMOVAL => Some(RawEvent::DeviceRemoved(DeviceId(lparam))),
+        _ => None,
+    }
+}
+
+fn parse_mouse(bytes: &[u8], device: DeviceId, events: &mut Vec<RawEvent>) {
+    let data = match bytes.get(HEADER_SIZE..HEADER_SIZE + MOUSE_SIZE) {
+        Some(data) => data,
+        None => return,
+    };
+    let flags = read_u16(data, 0).unwrap_or(0);
+    let button_flags = read_u16(data, 4).unwrap_or(0);
+    let button_data = read_u16(data, 6).unwrap_or(0) as i16;
+    let x = read_u32(data, 12).unwrap_or(0) as i32;
+    let y = read_u32(data, 16).unwrap_or(0) as i32;
+
+    if flags & MOUSE_MOVE_ABSOLUTE != 0 {
+        events.push(RawEvent::MouseAbsolute {
+            device,
+            position: (x, y),
+        });
+    } else if x != 0 || y != 0 {
+        events.push(RawEvent::MouseMotion {
+            device,
+            delta: (x, y),
+        });
+    }
+
+    for (down, up, button) in RI_MOUSE_BUTTONS {
+        if button_flags & down != 0 {
+            events.push(RawEvent::MouseButton {
+                device,
+                button,
+                pressed: true,
+            });
+        }
+        if button_flags & up != 0 {
+            events.push(RawEvent::MouseButton {
+                device,
+                button,
+                pressed: false,
+            });
+        }
+    }
+
+    for (flag, horizontal) in [(RI_MOUSE_WHEEL, false), (RI_MOUSE_HWHEEL, true)] {
+        if button_flags & flag != 0 {
+            events.push(RawEvent::MouseWheel {
+                device,
+                delta: button_data,
+                horizontal,
+            });
+        }
+    }
+}
+
+fn parse_keyboard(bytes: &[u8], device: DeviceId, events: &mut Vec<RawEvent>) {
+    let data = match bytes.get(HEADER_SIZE..HEADER_SIZE + KEYBOARD_SIZE) {
+        Some(data) => data,
+        None => return,
+    };
+    let make_code = read_u16(data, 0).unwrap_or(0);
+    let flags = read_u16(data, 2).unwrap_or(0);
+    let key = read_u16(data, 6).unwrap_or(0);
+    if key == VK_FAKE {
+        return;
+    }
+    let scan_code = if flags & RI_KEY_E0 != 0 {
+        make_code | 0xe000
+    } else {
+        make_code
+    };
+    events.push(RawEvent::Key {
+        device,
+        key,
+        scan_code,
+        pressed: flags & RI_KEY_BREAK == 0,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE: DeviceId = DeviceId(0x1234);
+
+    fn header(kind: u32, size: usize) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE + size];
+        bytes[0..4].copy_from_slice(&kind.to_le_bytes());
+        bytes[4..8].copy_from_slice(&((HEADER_SIZE + size) as u32).to_le_bytes());
+        bytes[8..8 + std::mem::size_of::<isize>()].copy_from_slice(&DEVICE.0.to_le_bytes());
+        bytes
+    }
+
+    fn mouse(flags: u16, button_flags: u16, button_data: i16, x: i32, y: i32) -> Vec<u8> {
+        let mut bytes = header(RIM_TYPEMOUSE, MOUSE_SIZE);
+        let data = &mut bytes[HEADER_SIZE..];
+        data[0..2].copy_from_slice(&flags.to_le_bytes());
+        data[4..6].copy_from_slice(&button_flags.to_le_bytes());
+        data[6..8].copy_from_slice(&button_data.to_le_bytes());
+        data[12..16].copy_from_slice(&x.to_le_bytes());
+        data[16..20].copy_from_slice(&y.to_le_bytes());
+        bytes
+    }
+
+    fn keyboard(make_code: u16, flags: u16, key: u16) -> Vec<u8> {
+        let mut bytes = header(RIM_TYPEKEYBOARD, KEYBOARD_SIZE);
+        let data = &mut bytes[HEADER_SIZE..];
+        data[0..2].copy_from_slice(&make_code.to_le_bytes());
+        data[2..4].copy_from_slice(&flags.to_le_bytes());
+        data[6..8].copy_from_slice(&key.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn relative_motion() {
+        assert_eq!(
+            parse_raw_input(&mouse(0, 0, 0, -3, 7)),
+            [RawEvent::MouseMotion {
+                device: DEVICE,
+                delta: (-3, 7)
+            }]
+        );
+        // A report of only button changes has no motion
+        assert!(parse_raw_input(&mouse(0, 0, 0, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn absolute_position() {
+        assert_eq!(
+            parse_raw_input(&mouse(MOUSE_MOVE_ABSOLUTE, 0, 0, 0, 65535)),
+            [RawEvent::MouseAbsolute {
+                device: DEVICE,
+                position: (0, 65535)
+            }]
+        );
+    }
+
+    #[test]
+    fn buttons_in_flag_order() {
+        let button = |button, pressed| RawEvent::MouseButton {
+            device: DEVICE,
+            button,
+            pressed,
+        };
+        assert_eq!(
+            parse_raw_input(&mouse(0, 0x0001 | 0x0008 | 0x0200, 0, 0, 0)),
+            [
+                button(MouseButton::Left, true),
+                button(MouseButton::Right, false),
+                button(MouseButton::Forward, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn wheels() {
+        assert_eq!(
+            parse_raw_input(&mouse(0, RI_MOUSE_WHEEL, -120, 0, 0)),
+            [RawEvent::MouseWheel {
+                device: DEVICE,
+                delta: -120,
+                horizontal: false
+            }]
+        );
+        assert_eq!(
+            parse_raw_input(&mouse(0, RI_MOUSE_HWHEEL, 240, 0, 0)),
+            [RawEvent::MouseWheel {
+                device: DEVICE,
+                delta: 240,
+                horizontal: true
+            }]
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let key = |key, scan_code, pressed| RawEvent::Key {
+            device: DEVICE,
+            key,
+            scan_code,
+            pressed,
+        };
+        // A
+        assert_eq!(
+            parse_raw_input(&keyboard(0x1e, 0, 0x41)),
+            [key(0x41, 0x1e, true)]
+        );
+        assert_eq!(
+            parse_raw_input(&keyboard(0x1e, RI_KEY_BREAK, 0x41)),
+            [key(0x41, 0x1e, false)]
+        );
+        // Right control is the extended left control
+        assert_eq!(
+            parse_raw_input(&keyboard(0x1d, RI_KEY_E0 | RI_KEY_BREAK, 0x11)),
+            [key(0x11, 0xe01d, false)]
+        );
+        assert!(parse_raw_input(&keyboard(0x2a, RI_KEY_E0, VK_FAKE)).is_empty());
+    }
+
+    #[test]
+    fn short_buffers() {
+        assert!(parse_raw_input(&[]).is_empty());
+        assert!(parse_raw_input(&[0; 6]).is_empty());
+        let bytes = mouse(0, RI_MOUSE_WHEEL, 120, 1, 1);
+        assert!(parse_raw_input(&bytes[..HEADER_SIZE]).is_empty());
+        assert!(parse_raw_input(&bytes[..bytes.len() - 1]).is_empty());
+        let bytes = keyboard(0x1e, 0, 0x41);
+        assert!(parse_raw_input(&bytes[..bytes.len() - 1]).is_empty());
+    }
+
+    #[test]
+    fn other_devices() {
+        assert!(parse_raw_input(&header(2, 16)).is_empty());
+    }
+
+    #[test]
+    fn device_changes() {
+        assert_eq!(
+            parse_device_change(GIDC_ARRIVAL, 7),
+            Some(RawEvent::DeviceAdded(DeviceId(7)))
+        );
+        assert_eq!(
+            parse_device_change(GIDC_REMOVAL, 7),
+            Some(RawEvent::DeviceRemoved(DeviceId(7)))
+        );
+        assert_eq!(parse_device_change(0, 7), None);
+    }
+}
@@ -0,0 +1,380 @@
+#![allow(dead_code)]
+//! Ray casting on the CPU.
+//! A point on the screen is unprojected through the inverse view-projection into a world space
+//! ray, which can then be intersected with simple shapes and triangle meshes. Useful for hovering
+//! and dragging where waiting on a GPU readback would lag behind the cursor.
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+
+/// Determinants and dot products below this are treated as zero
+const EPSILON: f32 = 1e-6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    /// Always normalised, so distances along the ray are in world units
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// None if the direction has no length
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Option<Self> {
+        if direction.magnitude2() < EPSILON * EPSILON {
+            return None;
+        }
+        Some(Self {
+            origin,
+            direction: direction.normalize(),
+        })
+    }
+
+    /// The ray from the near plane through a point in pixels from the top left of a surface of
+    /// `size`. `view_proj` must map to wgpu clip space, with depth from 0 to 1, as the camera's
+    /// matrix including `OPENGL_TO_WGPU_MATRIX` does. None if the matrix cannot be inverted.
+    pub fn from_screen(
+        point: (f32, f32),
+        size: (u32, u32),
+        view_proj: Matrix4<f32>,
+    ) -> Option<Self> {
+        let inverse = view_proj.invert()?;
+        let (x, y) = screen_to_ndc(point, size);
+        let near = unproject(&inverse, Vector4::new(x, y, 0.0, 1.0))?;
+        let far = unproject(&inverse, Vector4::new(x, y, 1.0, 1.0))?;
+        Self::new(near, far - near)
+    }
+
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// Distance to the plane, None if the ray is parallel to it or the plane is behind
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denominator = plane.normal.dot(self.direction);
+        if denominator.abs() < EPSILON {
+            return None;
+        }
+        let t = -(plane.normal.dot(self.origin.to_vec()) + plane.distance) / denominator;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Distance to the nearest face of the box, 0 if the ray starts inside it.
+    /// Uses the slab method.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let origin = self.origin[axis];
+            let direction = self.direction[axis];
+            if direction.abs() < EPSILON {
+                // Parallel to this slab, missing unless already between its faces
+                if origin < aabb.min[axis] || origin > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / direction;
+            let mut t0 = (aabb.min[axis] - origin) * inverse;
+            let mut t1 = (aabb.max[axis] - origin) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+
+    /// Distance to the sphere's surface, 0 if the ray starts inside it
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<f32> {
+        let to_centre = sphere.centre - self.origin;
+        let radius2 = sphere.radius * sphere.radius;
+        if to_centre.magnitude2() <= radius2 {
+            return Some(0.0);
+        }
+        // Closest approach along the ray
+        let projection = to_centre.dot(self.direction);
+        if projection < 0.0 {
+            return None;
+        }
+        let distance2 = to_centre.magnitude2() - projection * projection;
+        if distance2 > radius2 {
+            return None;
+        }
+        Some(projection - (radius2 - distance2).sqrt())
+    }
+
+    /// Möller-Trumbore intersection, both faces of the triangle are hit.
+    /// Returns the distance and the barycentric coordinates of `b` and `c`.
+    /// https://www.graphics.cornell.edu/pubs/1997/MT97.pdf
+    pub fn intersect_triangle(
+        &self,
+        a: Point3<f32>,
+        b: Point3<f32>,
+        c: Point3<f32>,
+    ) -> Option<(f32, f32, f32)> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < EPSILON {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inverse;
+        (t >= 0.0).then_some((t, u, v))
+    }
+
+    /// The nearest triangle of an indexed mesh that the ray hits
+    pub fn intersect_mesh(&self, positions: &[[f32; 3]], indices: &[u32]) -> Option<MeshHit> {
+        let mut nearest: Option<MeshHit> = None;
+        for (triangle, tri) in indices.chunks_exact(3).enumerate() {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| Point3::from(positions[i as usize]));
+            if let Some((t, u, v)) = self.intersect_triangle(a, b, c) {
+                if nearest.is_none_or(|hit| t < hit.distance) {
+                    nearest = Some(MeshHit {
+                        distance: t,
+                        triangle,
+                        barycentric: [1.0 - u - v, u, v],
+                    });
+                }
+            }
+        }
+        nearest
+    }
+}
+
+/// The points where `normal . p + distance = 0`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn from_point_normal(point: Point3<f32>, normal: Vector3<f32>) -> Self {
+        let normal = normal.normalize();
+        Self {
+            normal,
+            distance: -normal.dot(point.to_vec()),
+        }
+    }
+
+    /// The y = 0 plane, for dragging things across the ground
+    pub fn ground() -> Self {
+        Self::from_point_normal(Point3::origin(), Vector3::unit_y())
+    }
+}
+
+/// An axis aligned bounding box
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// The smallest box containing the points, None if there are none
+    pub fn from_points<'a, I: IntoIterator<Item = &'a [f32; 3]>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter().map(|p| Point3::from(*p));
+        let first = points.next()?;
+        Some(points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: Point3::new(
+                    aabb.min.x.min(p.x),
+                    aabb.min.y.min(p.y),
+                    aabb.min.z.min(p.z),
+                ),
+                max: Point3::new(
+                    aabb.max.x.max(p.x),
+                    aabb.max.y.max(p.y),
+                    aabb.max.z.max(p.z),
+                ),
+            },
+        ))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+    pub centre: Point3<f32>,
+    pub radius: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshHit {
+    pub distance: f32,
+    /// Index of the triangle, i.e. the index of its first index divided by 3
+    pub triangle: usize,
+    /// Weights of the triangle's three vertices at the hit point
+    pub barycentric: [f32; 3],
+}
+
+/// A point in pixels from the top left to normalised device coordinates, y points up
+pub fn screen_to_ndc(point: (f32, f32), size: (u32, u32)) -> (f32, f32) {
+    let (width, height) = (size.0.max(1) as f32, size.1.max(1) as f32);
+    (point.0 / width * 2.0 - 1.0, 1.0 - point.1 / height * 2.0)
+}
+
+fn unproject(inverse: &Matrix4<f32>, clip: Vector4<f32>) -> Option<Point3<f32>> {
+    let world = inverse * clip;
+    if world.w.abs() < EPSILON {
+        return None;
+    }
+    Some(Point3::from_homogeneous(world))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{perspective, Deg};
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray::new(origin.into(), direction.into()).unwrap()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn zero_direction_is_not_a_ray() {
+        assert!(Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 0.0)).is_none());
+        let r = ray([0.0; 3], [0.0, 3.0, 4.0]);
+        assert!(close(r.direction.magnitude(), 1.0));
+        assert!(close(r.at(5.0).z, 4.0));
+    }
+
+    #[test]
+    fn plane_hits_in_front_only() {
+        let ground = Plane::ground();
+        let down = ray([1.0, 4.0, 2.0], [0.0, -1.0, 0.0]);
+        assert!(close(down.intersect_plane(&ground).unwrap(), 4.0));
+        let up = ray([1.0, 4.0, 2.0], [0.0, 1.0, 0.0]);
+        assert_eq!(up.intersect_plane(&ground), None);
+        let parallel = ray([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(parallel.intersect_plane(&ground), None);
+    }
+
+    #[test]
+    fn aabb_hits_nearest_face() {
+        let aabb = Aabb::from_points(&[[-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]]).unwrap();
+        let towards = ray([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert!(close(towards.intersect_aabb(&aabb).unwrap(), 4.0));
+        let inside = ray([0.0; 3], [0.0, 0.0, 1.0]);
+        assert_eq!(inside.intersect_aabb(&aabb), Some(0.0));
+        let away = ray([-5.0, 0.0, 0.0], [-1.0, 0.0, 0.0]);
+        assert_eq!(away.intersect_aabb(&aabb), None);
+        let beside = ray([-5.0, 2.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(beside.intersect_aabb(&aabb), None);
+        assert_eq!(Aabb::from_points(&[]), None);
+    }
+
+    #[test]
+    fn sphere_hits_surface() {
+        let sphere = Sphere {
+            centre: Point3::new(0.0, 0.0, -10.0),
+            radius: 2.0,
+        };
+        let r = ray([0.0; 3], [0.0, 0.0, -1.0]);
+        assert!(close(r.intersect_sphere(&sphere).unwrap(), 8.0));
+        let miss = ray([3.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        assert_eq!(miss.intersect_sphere(&sphere), None);
+    }
+
+    #[test]
+    fn triangle_hits_inside_and_both_faces() {
+        let [a, b, c] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(Point3::from);
+        let front = ray([0.25, 0.25, 1.0], [0.0, 0.0, -1.0]);
+        let (t, u, v) = front.intersect_triangle(a, b, c).unwrap();
+        assert!(close(t, 1.0) && close(u, 0.25) && close(v, 0.25));
+        let back = ray([0.25, 0.25, -1.0], [0.0, 0.0, 1.0]);
+        assert!(back.intersect_triangle(a, b, c).is_some());
+        let outside = ray([1.0, 1.0, 1.0], [0.0, 0.0, -1.0]);
+        assert_eq!(outside.intersect_triangle(a, b, c), None);
+        let edge_on = ray([0.25, 0.25, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(edge_on.intersect_triangle(a, b, c), None);
+    }
+
+    #[test]
+    fn mesh_reports_nearest_triangle() {
+        let positions = [
+            [-1.0, -1.0, -5.0],
+            [1.0, -1.0, -5.0],
+            [0.0, 1.0, -5.0],
+            [-1.0, -1.0, -2.0],
+            [1.0, -1.0, -2.0],
+            [0.0, 1.0, -2.0],
+        ];
+        let hit = ray([0.0; 3], [0.0, 0.0, -1.0])
+            .intersect_mesh(&positions, &[0, 1, 2, 3, 4, 5])
+            .unwrap();
+        assert_eq!(hit.triangle, 1);
+        assert!(close(hit.distance, 2.0));
+        assert!(close(hit.barycentric.iter().sum(), 1.0));
+    }
+
+    #[test]
+    fn screen_corners_map_to_ndc() {
+        assert_eq!(screen_to_ndc((0.0, 0.0), (200, 100)), (-1.0, 1.0));
+        assert_eq!(screen_to_ndc((200.0, 100.0), (200, 100)), (1.0, -1.0));
+        assert_eq!(screen_to_ndc((100.0, 50.0), (200, 100)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn unprojects_through_camera() {
+        #[rustfmt::skip]
+        let opengl_to_wgpu = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.0,
+            0.0, 0.0, 0.5, 1.0,
+        );
+        let eye = Point3::new(0.0, 0.0, 5.0);
+        let view = Matrix4::look_at_rh(eye, Point3::origin(), Vector3::unit_y());
+        let view_proj = opengl_to_wgpu * perspective(Deg(90.0), 1.0, 0.1, 100.0) * view;
+
+        let centre = Ray::from_screen((50.0, 50.0), (100, 100), view_proj).unwrap();
+        assert!(close(centre.direction.z, -1.0));
+        // Starts on the near plane
+        assert!(close(centre.origin.z, 4.9));
+        assert!(close(
+            centre
+                .intersect_plane(&Plane::from_point_normal(
+                    Point3::origin(),
+                    Vector3::unit_z()
+                ))
+                .unwrap(),
+            4.9
+        ));
+
+        // A 90 degree field of view puts the right edge at 45 degrees
+        let right = Ray::from_screen((100.0, 50.0), (100, 100), view_proj).unwrap();
+        assert!(close(right.direction.x, -right.direction.z));
+        assert!(right.direction.x > 0.0);
+        let top = Ray::from_screen((50.0, 0.0), (100, 100), view_proj).unwrap();
+        assert!(top.direction.y > 0.0);
+
+        assert_eq!(
+            Ray::from_screen((0.0, 0.0), (100, 100), Matrix4::from_scale(0.0)),
+            None
+        );
+    }
+}
@@ -1,13 +1,40 @@
 #![allow(dead_code)]
 
+use crate::adapter::{request_device, select_adapter, AdapterError, AdapterSettings};
+use crate::coords::ClientPoint;
+use crate::debug_draw::DebugDraw;
+#[cfg(all(feature = "debug-draw", debug_assertions))]
+use crate::debug_draw::DebugRenderer;
+use crate::dpi::{LogicalSize, PhysicalSize};
+use crate::drag_drop::{effect_for_paths, DragEvent, DropEffect, FileKind};
+use crate::environment::{Environment, EnvironmentBaker};
+use crate::frame_limiter::{backend_present_modes, choose_present_mode, FrameLimiter};
+use crate::frame_stats::{CpuTimer, FrameStage, FrameStats, GpuTimer};
 #[allow(unused_imports)]
 use crate::input::{Input, KeyCode};
+use crate::light::{LightUniform, Lights};
+use crate::material::PbrMaterial;
+use crate::model::{load_gltf, Mesh, Model, ModelError, ModelVertex};
+use crate::path::Path as VectorPath;
+use crate::perf_overlay::PerfOverlay;
+use crate::picking::{PickDraw, PickResult, Picker, SceneObject};
+use crate::post::{PostProcess, PostSettings, HDR_FORMAT};
+use crate::ray::Ray;
+use crate::render_graph::GraphError;
+use crate::surface::{FrameAction, Redraw, RenderError, SurfaceRecovery};
+use crate::tessellation::{FillOptions, StrokeOptions};
+use crate::utils::create_rgba_texture;
+use crate::vector::VectorLayer;
+use crate::winapi_utils::invalidate_rect;
 use crate::window::Window;
 use cgmath;
 use cgmath::SquareMatrix;
 use image;
 use image::GenericImageView;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use wgpu::{include_wgsl, Device, Queue, Texture};
+use windows::Win32::Foundation::HWND;
 
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
@@ -31,81 +58,198 @@ const VERTICES: &[Vertex] = &[
     Vertex {
         position: [-0.0868241, 0.49240386, 0.0],
         tex_coords: [0.4131759, 0.00759614],
+        normal: [0.0, 0.0, 1.0],
     }, // A
     Vertex {
         position: [-0.49513406, 0.06958647, 0.0],
         tex_coords: [0.0048659444, 0.43041354],
+        normal: [0.0, 0.0, 1.0],
     }, // B
     Vertex {
         position: [-0.21918549, -0.44939706, 0.0],
         tex_coords: [0.28081453, 0.949397],
+        normal: [0.0, 0.0, 1.0],
     }, // C
     Vertex {
         position: [0.35966998, -0.3473291, 0.0],
         tex_coords: [0.85967, 0.84732914],
+        normal: [0.0, 0.0, 1.0],
     }, // D
     Vertex {
         position: [0.44147372, 0.2347359, 0.0],
         tex_coords: [0.9414737, 0.2652641],
+        normal: [0.0, 0.0, 1.0],
     }, // E
 ];
 
 const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
 
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Passes timed on the gpu each frame: scene and post processing
+const GPU_TIMED_PASSES: u32 = 2;
+
+/// Settings chosen when the engine is created
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    /// Which adapter to use and what the device needs to support
+    pub adapter: AdapterSettings,
+    /// Falls back to the closest supported mode, see `frame_limiter::fallback_chain`
+    pub present_mode: wgpu::PresentMode,
+    /// Caps the frame rate on the CPU, None for uncapped
+    pub frame_limit: Option<f32>,
+    /// Clear to transparent for windows created with `Window::new_transparent`.
+    /// Output is premultiplied alpha. wgpu 0.12 always configures the swapchain with an opaque
+    /// composite alpha, DX12 and DX11 then discard the alpha channel, so of `adapter.backends`
+    /// only GL is used. When `adapter.backends` leaves out GL, or no GL adapter is found, a
+    /// warning is logged and the window is opaque.
+    pub transparent: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            adapter: AdapterSettings::default(),
+            present_mode: wgpu::PresentMode::Fifo,
+            frame_limit: None,
+            transparent: false,
+        }
+    }
+}
+
+/// Creates a surface for the window and picks an adapter that can present to it
+fn open_adapter(
+    window: &Window,
+    settings: &AdapterSettings,
+) -> Result<(wgpu::Surface, wgpu::Adapter), AdapterError> {
+    let instance = wgpu::Instance::new(settings.backends);
+    let surface = unsafe { instance.create_surface(window) };
+    let adapter = select_adapter(&instance, &surface, settings)?;
+    Ok((surface, adapter))
+}
+
 /// Holds information to manage wgpu
 // #[derive(Debug)]
 pub struct Engine {
+    // The window drawn to, invalidated to schedule a frame
+    window: HWND,
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: (u32, u32),
+    // DPI of the window's monitor over 96
+    scale_factor: f64,
     render_pipeline: wgpu::RenderPipeline,
     vertex_count: u32,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     diffuse_bind_group: wgpu::BindGroup,
+    // Kept to replace the diffuse texture
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    diffuse_sampler: wgpu::Sampler,
+    material_buffer: wgpu::Buffer,
+    // The answer to the files being dragged over the window
+    drag_effect: DropEffect,
+    // Dropped files, loaded on the next frame rather than while OLE waits for the drop
+    dropped_files: Vec<PathBuf>,
     clear_color: wgpu::Color,
     camera: Camera,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
+    lights: Lights,
+    light_buffer: wgpu::Buffer,
+    light_bind_group: wgpu::BindGroup,
+    depth_view: wgpu::TextureView,
+    pbr_pipeline: wgpu::RenderPipeline,
+    material_bind_group_layout: wgpu::BindGroupLayout,
+    material_sampler: wgpu::Sampler,
+    environment_bind_group_layout: wgpu::BindGroupLayout,
+    environment_baker: EnvironmentBaker,
+    environment: Environment,
+    models: Vec<Model>,
+    picker: Picker,
+    // A pick requested while the previous one was still being read back
+    pending_pick: Option<(u32, u32)>,
+    // Told about every pick requested with `request_pick` once it is read back
+    pick_handler: Option<Box<dyn FnMut(PickResult)>>,
+    post: PostProcess,
+    surface_recovery: SurfaceRecovery,
+    backend: wgpu::Backend,
+    adapter_info: wgpu::AdapterInfo,
+    frame_limiter: FrameLimiter,
+    // Set by any change that alters the next frame, cleared once a frame is drawn.
+    // The window is invalidated whenever this becomes set, so a WM_PAINT is on its way
+    redraw: Redraw,
+    // Time advanced by fixed updates and how far rendering is between the last two of them
+    simulation_time: Duration,
+    interpolation: f32,
+    stats: FrameStats,
+    gpu_timer: Option<GpuTimer>,
+    overlay: Option<PerfOverlay>,
+    vectors: VectorLayer,
+    debug_draw: DebugDraw,
+    #[cfg(all(feature = "debug-draw", debug_assertions))]
+    debug_renderer: DebugRenderer,
 }
 
 impl Engine {
     // Creating some of the wgpu types requires async code
-    pub async fn new(window: &Window) -> Self {
-        let size = window.get_size().unwrap();
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: Default::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None, // Trace path
-            )
-            .await
-            .unwrap();
+    pub async fn new(window: &Window, settings: RenderSettings) -> Result<Self, AdapterError> {
+        let size: (u32, u32) = window.inner_size()?.into();
+        let gl_requested = settings.adapter.backends.contains(wgpu::Backends::GL);
+        if settings.transparent && !gl_requested {
+            log::warn!(
+                "Backends {:?} leave out GL, which transparency needs, the window will be opaque",
+                settings.adapter.backends
+            );
+        }
+        let (surface, adapter) = if settings.transparent && gl_requested {
+            let gl = AdapterSettings {
+                backends: wgpu::Backends::GL,
+                ..settings.adapter.clone()
+            };
+            match open_adapter(window, &gl) {
+                Ok(opened) => opened,
+                Err(error) => {
+                    log::warn!("No GL adapter for a transparent window: {}", error);
+                    open_adapter(window, &settings.adapter)?
+                }
+            }
+        } else {
+            open_adapter(window, &settings.adapter)?
+        };
+        let (device, queue) = request_device(&adapter, &settings.adapter).await?;
+
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "Using {} ({:?}, {:?})",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.device_type
+        );
+        let backend = adapter_info.backend;
+        let present_mode =
+            choose_present_mode(settings.present_mode, backend_present_modes(backend));
 
+        // A window created minimized has no area, the surface is configured once it is restored
+        let mut surface_recovery = SurfaceRecovery::default();
+        let format = surface.get_preferred_format(&adapter).ok_or_else(|| {
+            AdapterError::NoSurfaceFormat {
+                adapter: adapter_info.clone(),
+            }
+        })?;
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
-            width: size.0,
-            height: size.1,
-            present_mode: wgpu::PresentMode::Fifo,
+            format,
+            width: size.0.max(1),
+            height: size.1.max(1),
+            present_mode,
         };
-        surface.configure(&device, &config);
+        if surface_recovery.resized(size) {
+            surface.configure(&device, &config);
+        }
 
         let shader = device.create_shader_module(&include_wgsl!("shaders/shader.wgsl"));
 
@@ -143,30 +287,50 @@ impl Engine {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("texture_bind_group_layout"),
             });
 
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::cast_slice(&[MaterialUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let clear_color = wgpu::Color {
-            r: 0.04,
-            g: 0.045,
-            b: 0.05,
-            a: 1.0,
+        let diffuse_bind_group = create_diffuse_bind_group(
+            &device,
+            &texture_bind_group_layout,
+            &diffuse_texture_view,
+            &diffuse_sampler,
+            &material_buffer,
+        );
+
+        // Premultiplied, so nothing but zero is fully transparent
+        let clear_color = if settings.transparent {
+            if matches!(backend, wgpu::Backend::Dx12 | wgpu::Backend::Dx11) {
+                log::warn!(
+                    "{:?} ignores surface alpha, the window will be opaque",
+                    backend
+                );
+            }
+            wgpu::Color::TRANSPARENT
+        } else {
+            wgpu::Color {
+                r: 0.04,
+                g: 0.045,
+                b: 0.05,
+                a: 1.0,
+            }
         };
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -206,7 +370,8 @@ impl Engine {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // The fragment stage needs the eye position for specular highlights
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -226,10 +391,33 @@ impl Engine {
             label: Some("camera_bind_group"),
         });
 
+        let lights = Lights::default();
+
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Buffer"),
+            contents: bytemuck::cast_slice(&[lights.to_uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let light_bind_group_layout = LightUniform::bind_group_layout(&device);
+
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &light_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            }],
+            label: Some("light_bind_group"),
+        });
+
         let render_pipline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &light_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
@@ -245,7 +433,8 @@ impl Engine {
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[wgpu::ColorTargetState {
-                    format: config.format,
+                    // The scene is drawn in HDR and tonemapped by the post processing graph
+                    format: HDR_FORMAT,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 }],
@@ -259,7 +448,7 @@ impl Engine {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(depth_stencil_state()),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
@@ -268,52 +457,231 @@ impl Engine {
             multiview: None,
         });
 
-        Self {
+        let depth_view = create_depth_view(&device, &config);
+
+        let material_bind_group_layout = PbrMaterial::bind_group_layout(&device);
+        let environment_bind_group_layout = Environment::bind_group_layout(&device);
+        let material_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        // A dim grey sky until an environment image is supplied
+        let environment_baker = EnvironmentBaker::new(&device, &queue);
+        let environment = Environment::uniform(
+            &device,
+            &queue,
+            &environment_bind_group_layout,
+            &environment_baker,
+            [40, 42, 46, 255],
+        );
+
+        let pbr_shader = device.create_shader_module(&include_wgsl!("shaders/pbr.wgsl"));
+        let pbr_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PBR Pipeline Layout"),
+            bind_group_layouts: &[
+                &material_bind_group_layout,
+                &camera_bind_group_layout,
+                &light_bind_group_layout,
+                &environment_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let pbr_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("PBR Pipeline"),
+            layout: Some(&pbr_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &pbr_shader,
+                entry_point: "vs_main",
+                buffers: &[ModelVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &pbr_shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    // The scene is drawn in HDR and tonemapped by the post processing graph
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // glTF materials may be double sided, the depth test resolves visibility
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(depth_stencil_state()),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let post = PostProcess::new(
+            &device,
+            &queue,
+            config.format,
+            size,
+            PostSettings::default(),
+        )
+        .expect("the default post processing graph is valid");
+
+        let gpu_timer = GpuTimer::new(&device, &queue, GPU_TIMED_PASSES);
+        let picker = Picker::new(&device);
+        let vectors = VectorLayer::new(&device, config.format, size);
+        #[cfg(all(feature = "debug-draw", debug_assertions))]
+        let debug_renderer = DebugRenderer::new(&device, config.format, &camera_bind_group_layout);
+
+        Ok(Self {
+            window: window.handle(),
             surface,
             device,
             queue,
             config,
             size,
+            scale_factor: window.scale_factor(),
             render_pipeline,
             vertex_count,
             vertex_buffer,
             index_buffer,
             diffuse_bind_group,
+            texture_bind_group_layout,
+            diffuse_sampler,
+            material_buffer,
+            drag_effect: DropEffect::None,
+            dropped_files: Vec::new(),
             clear_color,
             camera,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
+            lights,
+            light_buffer,
+            light_bind_group,
+            depth_view,
+            pbr_pipeline,
+            material_bind_group_layout,
+            material_sampler,
+            environment_bind_group_layout,
+            environment_baker,
+            environment,
+            models: Vec::new(),
+            picker,
+            pending_pick: None,
+            pick_handler: None,
+            post,
+            surface_recovery,
+            backend,
+            adapter_info,
+            frame_limiter: FrameLimiter::new(settings.frame_limit),
+            redraw: Redraw::default(),
+            simulation_time: Duration::ZERO,
+            interpolation: 0.0,
+            stats: FrameStats::default(),
+            gpu_timer,
+            overlay: None,
+            vectors,
+            debug_draw: DebugDraw::default(),
+            #[cfg(all(feature = "debug-draw", debug_assertions))]
+            debug_renderer,
+        })
+    }
+
+    /// Marks the engine as needing a new frame and asks the window to paint one
+    pub fn invalidate(&mut self) {
+        if self.redraw.invalidate() {
+            self.request_redraw();
         }
     }
 
+    /// Schedules a WM_PAINT, several before it arrives are merged into one
+    fn request_redraw(&self) {
+        if let Err(error) = invalidate_rect(self.window) {
+            log::warn!("Could not schedule a redraw: {}", error);
+        }
+    }
+
+    /// True if something changed since the last presented frame
+    pub fn needs_redraw(&self) -> bool {
+        self.redraw.is_dirty()
+    }
+
+    /// DPI of the window's monitor over 96
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// The surface size in pixels at 96 DPI
+    pub fn logical_size(&self) -> LogicalSize {
+        PhysicalSize::from(self.size).to_logical(self.scale_factor)
+    }
+
+    /// Resizes the surface, a size of zero in either direction pauses rendering until the
+    /// window is given an area again (e.g. while minimized)
     pub fn resize(&mut self, new_size: (u32, u32)) {
-        if (new_size.0 > 0) & (new_size.1 > 0) {
+        if self.surface_recovery.resized(new_size) {
             self.size = new_size;
             self.config.width = new_size.0;
             self.config.height = new_size.1;
             self.surface.configure(&self.device, &self.config);
+            self.depth_view = create_depth_view(&self.device, &self.config);
+            self.post.resize(&self.device, new_size);
+            self.vectors.resize(&self.queue, new_size);
             self.update();
+            self.invalidate();
         }
     }
 
+    /// Handles an input event, returns true if it changed anything that needs a redraw
     pub fn input(&mut self, input: Input) -> bool {
-        match input {
+        let changed = match input {
             #[allow(unused_variables)]
-            Input::MouseMove((x, y)) => {
+            Input::MouseMove(ClientPoint { x, y }) => {
                 // self.clear_color = wgpu::Color {
                 //     r: x as f64 / self.size.0 as f64,
                 //     g: y as f64 / self.size.1 as f64,
                 //     b: 1.0,
                 //     a: 1.0,
                 // };
+                false
             }
-            _ => (println!("Input received: {:?}", input)),
-        }
+            // Nothing is turned by relative movement yet
+            Input::MouseDelta(_) => false,
+            Input::Raw(_) => false,
+            Input::ClipboardChanged => false,
+            Input::LeftClick(point) => {
+                if let Some(pixel) = point.to_pixel(self.size.into()) {
+                    self.request_pick(pixel);
+                }
+                false
+            }
+            Input::ScaleFactorChanged { scale_factor, .. } => {
+                // The new size arrives as a resize once the window has been moved
+                self.scale_factor = scale_factor;
+                true
+            }
+            _ => {
+                log::trace!("Input received: {:?}", input);
+                false
+            }
+        };
 
-        //FIXME: should only re-render if needed by tracking changes
-        self.render().unwrap();
-        false
+        if changed {
+            self.invalidate();
+        }
+        changed
     }
 
     pub fn update(&mut self) {
@@ -324,10 +692,439 @@ impl Engine {
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        self.queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[self.lights.to_uniform()]),
+        );
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+    /// Mutable access to the scene lights, changes are uploaded before the next frame
+    pub fn lights_mut(&mut self) -> &mut Lights {
+        self.invalidate();
+        &mut self.lights
+    }
+
+    /// Loads a glTF file and adds its meshes to the scene, drawn with the PBR pipeline
+    pub fn load_gltf<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ModelError> {
+        let (meshes, materials) = load_gltf(path)?;
+
+        let upload = |material: &PbrMaterial| {
+            material.upload(
+                &self.device,
+                &self.queue,
+                &self.material_bind_group_layout,
+                &self.material_sampler,
+            )
+        };
+
+        let model = Model {
+            meshes: meshes
+                .iter()
+                .map(|mesh| Mesh::upload(&self.device, mesh))
+                .collect(),
+            materials: materials.iter().map(upload).collect(),
+            default_material: upload(&PbrMaterial::default()),
+        };
+
+        self.models.push(model);
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Replaces the texture drawn on the default shape. An image larger than the device allows
+    /// is scaled down to fit.
+    pub fn set_diffuse_texture(&mut self, image: &image::RgbaImage) {
+        let max = self.device.limits().max_texture_dimension_2d;
+        let (width, height) = image.dimensions();
+        let scaled;
+        let image = if width > max || height > max {
+            let scale = max as f64 / width.max(height) as f64;
+            let size = (
+                ((width as f64 * scale) as u32).clamp(1, max),
+                ((height as f64 * scale) as u32).clamp(1, max),
+            );
+            log::warn!(
+                "Scaling a {}x{} texture down to {}x{}, the largest the device allows is {}",
+                width,
+                height,
+                size.0,
+                size.1,
+                max
+            );
+            scaled = image::imageops::resize(
+                image,
+                size.0,
+                size.1,
+                image::imageops::FilterType::Triangle,
+            );
+            &scaled
+        } else {
+            image
+        };
+        let texture = create_rgba_texture(
+            &self.device,
+            &self.queue,
+            image.dimensions(),
+            image.as_raw(),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            Some("diffuse_texture"),
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.diffuse_bind_group = create_diffuse_bind_group(
+            &self.device,
+            &self.texture_bind_group_layout,
+            &view,
+            &self.diffuse_sampler,
+            &self.material_buffer,
+        );
+        self.invalidate();
+    }
+
+    /// Answers files dragged over the window, accepting them if any is an image or a glTF
+    /// model. Dropped images replace the diffuse texture and dropped models are added to the
+    /// scene, both on the next frame so the drop source is not kept waiting.
+    pub fn drag(&mut self, event: DragEvent) -> DropEffect {
+        match event {
+            DragEvent::HoverEnter { paths, .. } => {
+                self.drag_effect = effect_for_paths(&paths);
+                self.drag_effect
+            }
+            DragEvent::HoverOver { .. } => self.drag_effect,
+            DragEvent::Leave => {
+                self.drag_effect = DropEffect::None;
+                DropEffect::None
+            }
+            DragEvent::Drop { paths, .. } => {
+                self.drag_effect = DropEffect::None;
+                let effect = effect_for_paths(&paths);
+                if effect == DropEffect::Copy {
+                    self.dropped_files.extend(paths);
+                    self.invalidate();
+                }
+                effect
+            }
+        }
+    }
+
+    /// Loads the files dropped since the last frame
+    fn load_dropped_files(&mut self) {
+        for path in std::mem::take(&mut self.dropped_files) {
+            self.load_dropped_file(&path);
+        }
+    }
+
+    fn load_dropped_file(&mut self, path: &Path) {
+        match FileKind::from_path(path) {
+            Some(FileKind::Texture) => match image::open(path) {
+                Ok(image) => self.set_diffuse_texture(&image.into_rgba8()),
+                Err(error) => log::warn!("Could not load {}: {}", path.display(), error),
+            },
+            Some(FileKind::Model) => {
+                if let Err(error) = self.load_gltf(path) {
+                    log::warn!("Could not load {}: {}", path.display(), error);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Replaces the image based lighting with one baked from an equirectangular image, ideally
+    /// an HDR one such as a `.hdr` file
+    pub fn set_environment(&mut self, image: &image::DynamicImage) {
+        self.environment = Environment::from_equirectangular(
+            &self.device,
+            &self.queue,
+            &self.environment_bind_group_layout,
+            &self.environment_baker,
+            image,
+        );
+        self.invalidate();
+    }
+
+    /// Enables, disables or tunes the post processing effects
+    pub fn set_post_settings(&mut self, settings: PostSettings) -> Result<(), GraphError> {
+        self.post
+            .set_settings(&self.device, &self.queue, settings)?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Sets the colour grading lookup table, see `PostProcess::set_colour_grading_lut`
+    pub fn set_colour_grading_lut(&mut self, lut: &image::RgbaImage) {
+        self.post
+            .set_colour_grading_lut(&self.device, &self.queue, lut);
+        self.invalidate();
+    }
+
+    /// Switches the present mode, returning the mode requested from wgpu after falling back
+    /// against `backend_present_modes`. The driver can still choose `Fifo` instead
+    pub fn set_present_mode(&mut self, requested: wgpu::PresentMode) -> wgpu::PresentMode {
+        let present_mode = choose_present_mode(requested, backend_present_modes(self.backend));
+        if present_mode != self.config.present_mode {
+            self.config.present_mode = present_mode;
+            if !self.surface_recovery.is_minimized() {
+                self.surface.configure(&self.device, &self.config);
+            }
+            self.invalidate();
+        }
+        present_mode
+    }
+
+    /// The mode the surface was configured with. This is what was requested, wgpu 0.12 cannot
+    /// report whether the driver fell back to another mode
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    /// Caps the frame rate, None for uncapped
+    pub fn set_frame_limit(&mut self, target_fps: Option<f32>) {
+        self.frame_limiter.set_target_fps(target_fps);
+    }
+
+    /// Advances the simulation by one fixed step
+    pub fn fixed_update(&mut self, dt: Duration) {
+        self.simulation_time += dt;
+        self.invalidate();
+    }
+
+    /// Total time the simulation has been advanced by
+    pub fn simulation_time(&self) -> Duration {
+        self.simulation_time
+    }
+
+    /// Sets how far between the last two fixed updates the next frame is, in [0, 1).
+    /// A new value needs a new frame even when no update ran, so it is drawn in between them
+    pub fn set_interpolation(&mut self, alpha: f32) {
+        let alpha = alpha.clamp(0.0, 1.0);
+        if alpha != self.interpolation {
+            self.interpolation = alpha;
+            self.invalidate();
+        }
+    }
+
+    pub fn interpolation(&self) -> f32 {
+        self.interpolation
+    }
+
+    /// Starts finding the object at a point in pixels from the top left of the window. The
+    /// result goes to the pick handler once it has been read back, which is checked every frame.
+    /// Only the latest request is kept while a pick is in flight.
+    pub fn request_pick(&mut self, point: (u32, u32)) {
+        if self.picker.is_busy() {
+            self.pending_pick = Some(point);
+        } else {
+            self.submit_pick(point);
+        }
+        // Frames keep coming until the result is in
+        self.invalidate();
+    }
+
+    /// Called with the result of every `request_pick`, replacing the previous handler.
+    /// Without a handler results are only logged.
+    pub fn set_pick_handler<F: FnMut(PickResult) + 'static>(&mut self, handler: F) {
+        self.pick_handler = Some(Box::new(handler));
+    }
+
+    /// The result of a requested pick once the gpu has finished it. `render` does this and
+    /// passes results to the pick handler, so it only needs calling without rendering.
+    pub fn poll_pick(&mut self) -> Option<PickResult> {
+        let result = self.picker.poll(&self.device)?;
+        if let Some(point) = self.pending_pick.take() {
+            self.submit_pick(point);
+        }
+        Some(result)
+    }
+
+    /// Finds the object at a point, waiting for the gpu. Discards any requested pick.
+    pub fn pick(&mut self, point: (u32, u32)) -> PickResult {
+        self.pending_pick = None;
+        self.picker.wait(&self.device);
+        self.submit_pick(point);
+        self.picker
+            .wait(&self.device)
+            .expect("a pick was just submitted")
+    }
+
+    fn submit_pick(&mut self, point: (u32, u32)) {
+        let mut draws = vec![PickDraw {
+            object: SceneObject::Demo,
+            vertex_buffer: &self.vertex_buffer,
+            vertex_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            index_buffer: &self.index_buffer,
+            index_format: wgpu::IndexFormat::Uint16,
+            index_count: self.vertex_count,
+            cull_mode: Some(wgpu::Face::Back),
+        }];
+        for (model_index, model) in self.models.iter().enumerate() {
+            for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+                draws.push(PickDraw {
+                    object: SceneObject::Mesh {
+                        model: model_index,
+                        mesh: mesh_index,
+                    },
+                    vertex_buffer: &mesh.vertex_buffer,
+                    vertex_stride: std::mem::size_of::<ModelVertex>() as wgpu::BufferAddress,
+                    index_buffer: &mesh.index_buffer,
+                    index_format: wgpu::IndexFormat::Uint32,
+                    index_count: mesh.index_count,
+                    cull_mode: None,
+                });
+            }
+        }
+
+        self.picker.request(
+            &self.device,
+            &self.queue,
+            self.camera.build_view_projection_matrix(),
+            (self.config.width, self.config.height),
+            point,
+            &draws,
+        );
+    }
+
+    /// The world space ray through a point in pixels from the top left of the window.
+    /// Pixel centres are at +0.5, the same as for `request_pick`
+    pub fn screen_ray(&self, point: (f32, f32)) -> Option<Ray> {
+        self.camera
+            .screen_ray(point, (self.config.width, self.config.height))
+    }
+
+    /// Timings of the recently rendered frames
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// The adapter the engine is running on
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// True if gpu pass timings are being measured
+    pub fn gpu_timing_supported(&self) -> bool {
+        self.gpu_timer.is_some()
+    }
+
+    /// Writes the recorded frame timings to a CSV file
+    pub fn export_frame_stats<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.stats.write_csv(std::io::BufWriter::new(file))
+    }
+
+    /// Shows or hides the frame time graph
+    pub fn set_overlay_enabled(&mut self, enabled: bool) {
+        if enabled == self.overlay.is_some() {
+            return;
+        }
+        self.overlay = enabled
+            .then(|| PerfOverlay::new(&self.device, self.config.format, self.stats.capacity()));
+        self.invalidate();
+    }
+
+    pub fn overlay_enabled(&self) -> bool {
+        self.overlay.is_some()
+    }
+
+    /// Fills a 2D path in pixels from the top left of the window, kept until `clear_paths`
+    pub fn fill_path(&mut self, path: &VectorPath, options: &FillOptions, colour: [f32; 4]) {
+        self.vectors.fill(path, options, colour);
+        self.invalidate();
+    }
+
+    /// Strokes a 2D path in pixels from the top left of the window, kept until `clear_paths`
+    pub fn stroke_path(&mut self, path: &VectorPath, options: &StrokeOptions, colour: [f32; 4]) {
+        self.vectors.stroke(path, options, colour);
+        self.invalidate();
+    }
+
+    /// Removes every filled and stroked path
+    pub fn clear_paths(&mut self) {
+        self.vectors.clear();
+        self.invalidate();
+    }
+
+    /// Queue of debug lines for the next frame. Does nothing unless the `debug-draw` feature is
+    /// enabled in a debug build
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        self.invalidate();
+        &mut self.debug_draw
+    }
+
+    /// True while the window has no area and frames are skipped
+    pub fn is_paused(&self) -> bool {
+        self.surface_recovery.is_minimized()
+    }
+
+    /// Acquires the next surface texture, recovering from lost and outdated surfaces.
+    /// Returns None when this frame should be skipped
+    fn acquire_frame(&mut self) -> Result<Option<wgpu::SurfaceTexture>, RenderError> {
+        let mut action = self.surface_recovery.begin_frame();
+        loop {
+            match action {
+                FrameAction::Skip => return Ok(None),
+                FrameAction::Reconfigure => {
+                    self.surface.configure(&self.device, &self.config);
+                    self.surface_recovery.reconfigured();
+                }
+                FrameAction::Render => {}
+            }
+
+            match self.surface.get_current_texture() {
+                Ok(frame) => {
+                    self.surface_recovery.acquired();
+                    return Ok(Some(frame));
+                }
+                Err(error) => {
+                    log::warn!("Failed to acquire frame: {}", error);
+                    action = self.surface_recovery.acquire_failed(&error)?;
+                }
+            }
+        }
+    }
+
+    /// Passes finished picks to the pick handler
+    fn dispatch_picks(&mut self) {
+        while let Some(result) = self.poll_pick() {
+            match self.pick_handler.as_mut() {
+                Some(handler) => handler(result),
+                None => log::debug!("Picked {:?}", result),
+            }
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), RenderError> {
+        let mut cpu_timer = CpuTimer::start();
+        self.dispatch_picks();
+        self.load_dropped_files();
+        if !self.surface_recovery.is_minimized() {
+            cpu_timer.lap(FrameStage::Acquire);
+            self.frame_limiter.wait();
+            cpu_timer.skip();
+        }
+
+        let output = match self.acquire_frame()? {
+            Some(output) => output,
+            None => {
+                if self.redraw.skipped(self.surface_recovery.is_minimized()) {
+                    self.request_redraw();
+                }
+                return Ok(());
+            }
+        };
+        cpu_timer.lap(FrameStage::Acquire);
+
+        // Timings of earlier frames that have finished reading back
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            for (frame, timings) in gpu_timer.collect(&self.device) {
+                self.stats.record_gpu(frame, timings);
+            }
+        }
+
+        if self.redraw.begin_draw() {
+            self.update();
+        }
+        cpu_timer.lap(FrameStage::Update);
+
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -337,33 +1134,117 @@ impl Engine {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.begin_frame(self.stats.next_frame());
+            gpu_timer.begin_pass(&mut encoder, "scene");
+        }
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: self.post.scene_view(),
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.clear_color),
                         store: true,
                     },
                 }],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
             });
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             // render_pass.draw(0..self.vertex_count, 0..1);
-            render_pass.draw_indexed(0..self.vertex_count, 0, 0..1)
+            render_pass.draw_indexed(0..self.vertex_count, 0, 0..1);
+
+            render_pass.set_pipeline(&self.pbr_pipeline);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+            render_pass.set_bind_group(3, &self.environment.bind_group, &[]);
+            for model in &self.models {
+                for mesh in &model.meshes {
+                    let material = mesh
+                        .material
+                        .and_then(|i| model.materials.get(i))
+                        .unwrap_or(&model.default_material);
+                    render_pass.set_bind_group(0, &material.bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+                }
+            }
         }
 
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.end_pass(&mut encoder);
+            gpu_timer.begin_pass(&mut encoder, "post");
+        }
+
+        self.post.execute(&mut encoder, &view);
+
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.end_pass(&mut encoder);
+        }
+
+        self.vectors
+            .draw(&self.device, &self.queue, &mut encoder, &view);
+
+        #[cfg(all(feature = "debug-draw", debug_assertions))]
+        self.debug_renderer.draw(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &view,
+            &self.depth_view,
+            &self.camera_bind_group,
+            &self.debug_draw,
+            self.camera.eye,
+        );
+
+        if let Some(overlay) = self.overlay.as_mut() {
+            overlay.update(
+                &self.queue,
+                &self.stats,
+                (self.config.width, self.config.height),
+            );
+            overlay.draw(&mut encoder, &view);
+        }
+
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.resolve(&mut encoder);
+        }
+        cpu_timer.lap(FrameStage::Encode);
+
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.end_frame();
+        }
+        cpu_timer.lap(FrameStage::Submit);
+
         output.present();
+        cpu_timer.lap(FrameStage::Present);
+        // Shapes drawn for a number of seconds need frames until they expire, picks until they
+        // have been read back
+        self.debug_draw.end_frame();
+        if self.debug_draw.has_timed_shapes() || self.picker.is_busy() {
+            self.invalidate();
+        }
+
+        self.stats.record(cpu_timer.finish());
 
         Ok(())
     }
@@ -375,39 +1256,70 @@ fn queue_texture(bytes: &[u8], queue: &Queue, device: &Device) -> Texture {
     let dimensions = img.dimensions();
     let rgba_data = img.as_rgba8().unwrap();
 
-    let texture_size = wgpu::Extent3d {
-        width: dimensions.0,
-        height: dimensions.1,
-        depth_or_array_layers: 1,
-    };
+    create_rgba_texture(
+        device,
+        queue,
+        dimensions,
+        rgba_data,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        Some("diffuse_texture"),
+    )
+}
 
+/// Binds a texture to the default shape's pipeline
+fn create_diffuse_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    material_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: material_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("diffuse_bind_group"),
+    })
+}
+
+/// The depth buffer has to match the size of the surface
+fn create_depth_view(device: &Device, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureView {
     let texture = device.create_texture(&wgpu::TextureDescriptor {
-        label: Some("diffuse_texture"),
-        size: texture_size,
+        label: Some("depth_texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
     });
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
 
-    queue.write_texture(
-        wgpu::ImageCopyTexture {
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        rgba_data,
-        wgpu::ImageDataLayout {
-            offset: 0,
-            bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-            rows_per_image: std::num::NonZeroU32::new(dimensions.1),
-        },
-        texture_size,
-    );
-
-    texture
+fn depth_stencil_state() -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    }
 }
 
 #[repr(C)]
@@ -415,12 +1327,13 @@ fn queue_texture(bytes: &[u8], queue: &Queue, device: &Device) -> Texture {
 struct Vertex {
     position: [f32; 3],
     tex_coords: [f32; 2],
+    normal: [f32; 3],
     // colour: [f32; 3],
 }
 
 impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3];
     // wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
 
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -461,11 +1374,18 @@ impl Camera {
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
         return OPENGL_TO_WGPU_MATRIX * proj * view;
     }
+
+    /// The world space ray through a point in pixels from the top left of a surface of `size`
+    fn screen_ray(&self, point: (f32, f32), size: (u32, u32)) -> Option<Ray> {
+        Ray::from_screen(point, size, self.build_view_projection_matrix())
+    }
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
+    // The eye position, w is unused but keeps the matrix 16 byte aligned
+    view_position: [f32; 4],
     // convert the Matrix4 into a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
 }
@@ -473,11 +1393,30 @@ struct CameraUniform {
 impl CameraUniform {
     fn new() -> Self {
         Self {
+            view_position: [0.0; 4],
             view_proj: cgmath::Matrix4::identity().into(),
         }
     }
 
     fn update_view_proj(&mut self, camera: &Camera) {
+        self.view_position = camera.eye.to_homogeneous().into();
         self.view_proj = camera.build_view_projection_matrix().into();
     }
 }
+
+/// Blinn-Phong surface properties for the lit material shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    specular_colour: [f32; 3],
+    shininess: f32,
+}
+
+impl Default for MaterialUniform {
+    fn default() -> Self {
+        Self {
+            specular_colour: [0.5, 0.5, 0.5],
+            shininess: 32.0,
+        }
+    }
+}
@@ -0,0 +1,546 @@
+#![allow(dead_code)]
+//! A small render graph.
+//! Passes declare the named textures they read and write, the graph then works out the order
+//! passes have to run in, drops passes that do not contribute to an imported output (such as the
+//! swapchain) and assigns every intermediate texture to a physical texture slot. Slots are reused
+//! once the last reader of a texture has run, so a chain of effects only needs a couple of
+//! textures. Nothing in here touches the gpu, allocating the slots is up to the caller.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// How big an intermediate texture is relative to the surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SizePolicy {
+    /// Same size as the surface
+    Surface,
+    /// The surface size multiplied by a factor, e.g. 0.5 for half resolution
+    SurfaceScaled(f32),
+    Fixed(u32, u32),
+}
+
+impl SizePolicy {
+    /// The size in pixels, never smaller than 1x1
+    pub fn resolve(&self, surface: (u32, u32)) -> (u32, u32) {
+        let (w, h) = match *self {
+            Self::Surface => surface,
+            Self::SurfaceScaled(scale) => (
+                (surface.0 as f32 * scale) as u32,
+                (surface.1 as f32 * scale) as u32,
+            ),
+            Self::Fixed(w, h) => (w, h),
+        };
+        (w.max(1), h.max(1))
+    }
+}
+
+/// Description of a texture managed by the graph
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureDesc {
+    pub format: wgpu::TextureFormat,
+    pub size: SizePolicy,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassDesc {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphError {
+    DuplicatePass(String),
+    DuplicateResource(String),
+    /// A pass refers to a texture that was neither added nor imported
+    UnknownResource {
+        pass: String,
+        resource: String,
+    },
+    /// More than one pass writes the same texture
+    MultipleWriters {
+        resource: String,
+        passes: Vec<String>,
+    },
+    /// A pass reads and writes the same texture, which wgpu does not allow in one pass
+    ReadWriteHazard {
+        pass: String,
+        resource: String,
+    },
+    /// A pass reads a graph texture that no pass writes
+    MissingProducer {
+        pass: String,
+        resource: String,
+    },
+    /// The passes listed depend on each other
+    Cycle(Vec<String>),
+    /// No pass writes an imported resource so nothing would be visible
+    NoOutput,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicatePass(name) => write!(f, "pass '{}' is declared twice", name),
+            Self::DuplicateResource(name) => write!(f, "resource '{}' is declared twice", name),
+            Self::UnknownResource { pass, resource } => {
+                write!(f, "pass '{}' uses unknown resource '{}'", pass, resource)
+            }
+            Self::MultipleWriters { resource, passes } => {
+                write!(f, "resource '{}' is written by {:?}", resource, passes)
+            }
+            Self::ReadWriteHazard { pass, resource } => {
+                write!(f, "pass '{}' reads and writes '{}'", pass, resource)
+            }
+            Self::MissingProducer { pass, resource } => write!(
+                f,
+                "pass '{}' reads '{}' which is never written",
+                pass, resource
+            ),
+            Self::Cycle(passes) => write!(f, "passes {:?} form a cycle", passes),
+            Self::NoOutput => write!(f, "no pass writes an imported resource"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Declared passes and resources, call `compile` to get something that can be executed
+#[derive(Clone, Debug, Default)]
+pub struct RenderGraph {
+    textures: Vec<(String, TextureDesc)>,
+    imports: Vec<String>,
+    passes: Vec<PassDesc>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Declares a texture owned by the graph
+    pub fn add_texture(&mut self, name: &str, desc: TextureDesc) -> &mut Self {
+        self.textures.push((name.to_owned(), desc));
+        self
+    }
+
+    /// Declares a texture owned outside of the graph, such as the swapchain.
+    /// Passes writing imported textures are the roots that keep the rest of the graph alive.
+    pub fn import(&mut self, name: &str) -> &mut Self {
+        self.imports.push(name.to_owned());
+        self
+    }
+
+    pub fn add_pass(&mut self, name: &str, inputs: &[&str], outputs: &[&str]) -> &mut Self {
+        self.passes.push(PassDesc {
+            name: name.to_owned(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+        });
+        self
+    }
+
+    fn texture(&self, name: &str) -> Option<&TextureDesc> {
+        self.textures
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, d)| d)
+    }
+
+    fn is_imported(&self, name: &str) -> bool {
+        self.imports.iter().any(|n| n == name)
+    }
+
+    pub fn compile(&self) -> Result<CompiledGraph, GraphError> {
+        self.validate()?;
+
+        // Which pass writes each resource
+        let mut producers: HashMap<&str, usize> = HashMap::new();
+        for (i, pass) in self.passes.iter().enumerate() {
+            for output in &pass.outputs {
+                if let Some(previous) = producers.insert(output, i) {
+                    return Err(GraphError::MultipleWriters {
+                        resource: output.clone(),
+                        passes: vec![self.passes[previous].name.clone(), pass.name.clone()],
+                    });
+                }
+            }
+        }
+
+        // Walk backwards from the passes writing imported resources
+        let mut live = vec![false; self.passes.len()];
+        let mut stack: Vec<usize> = self
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.outputs.iter().any(|o| self.is_imported(o)))
+            .map(|(i, _)| i)
+            .collect();
+        if stack.is_empty() {
+            return Err(GraphError::NoOutput);
+        }
+        while let Some(i) = stack.pop() {
+            if live[i] {
+                continue;
+            }
+            live[i] = true;
+            for input in &self.passes[i].inputs {
+                match producers.get(input.as_str()) {
+                    Some(&producer) => stack.push(producer),
+                    None if self.is_imported(input) => {}
+                    None => {
+                        return Err(GraphError::MissingProducer {
+                            pass: self.passes[i].name.clone(),
+                            resource: input.clone(),
+                        })
+                    }
+                }
+            }
+        }
+
+        // Kahn's algorithm, ties are broken by declaration order so the result is stable
+        let mut dependencies = vec![0usize; self.passes.len()];
+        let mut dependants: Vec<Vec<usize>> = vec![Vec::new(); self.passes.len()];
+        for (i, pass) in self.passes.iter().enumerate().filter(|(i, _)| live[*i]) {
+            for input in &pass.inputs {
+                // Never the pass itself, validate rejects passes reading their own output
+                if let Some(&producer) = producers.get(input.as_str()) {
+                    dependencies[i] += 1;
+                    dependants[producer].push(i);
+                }
+            }
+        }
+
+        let mut ready: Vec<usize> = (0..self.passes.len())
+            .filter(|&i| live[i] && dependencies[i] == 0)
+            .collect();
+        let mut order = Vec::new();
+        while !ready.is_empty() {
+            ready.sort_unstable_by(|a, b| b.cmp(a));
+            let i = ready.pop().unwrap();
+            order.push(i);
+            for &dependant in &dependants[i] {
+                dependencies[dependant] -= 1;
+                if dependencies[dependant] == 0 {
+                    ready.push(dependant);
+                }
+            }
+        }
+
+        let live_count = live.iter().filter(|l| **l).count();
+        if order.len() != live_count {
+            let mut stuck: Vec<usize> = (0..self.passes.len())
+                .filter(|&i| live[i] && !order.contains(&i))
+                .collect();
+            // Passes that only wait on the cycle without feeding back into it are not part of it
+            while let Some(position) = stuck
+                .iter()
+                .position(|&i| !dependants[i].iter().any(|d| stuck.contains(d)))
+            {
+                stuck.remove(position);
+            }
+            let stuck = stuck.iter().map(|&i| self.passes[i].name.clone()).collect();
+            return Err(GraphError::Cycle(stuck));
+        }
+
+        let passes: Vec<PassDesc> = order.iter().map(|&i| self.passes[i].clone()).collect();
+
+        // First write and last read of every graph owned texture, as positions in `passes`
+        let mut lifetimes: HashMap<String, (usize, usize)> = HashMap::new();
+        for (position, pass) in passes.iter().enumerate() {
+            for resource in pass.outputs.iter().chain(&pass.inputs) {
+                if self.is_imported(resource) {
+                    continue;
+                }
+                lifetimes
+                    .entry(resource.clone())
+                    .and_modify(|(_, last)| *last = position)
+                    .or_insert((position, position));
+            }
+        }
+
+        // Greedy slot assignment in order of first use
+        let mut by_first_use: Vec<(&String, &(usize, usize))> = lifetimes.iter().collect();
+        by_first_use.sort_by_key(|(name, (first, _))| (*first, name.to_string()));
+
+        let mut slots: Vec<TextureDesc> = Vec::new();
+        let mut slot_free_after: Vec<usize> = Vec::new();
+        let mut assignments = HashMap::new();
+        for (name, &(first, last)) in by_first_use {
+            let desc = *self.texture(name).expect("validated above");
+            let reusable =
+                (0..slots.len()).find(|&slot| slots[slot] == desc && slot_free_after[slot] < first);
+            let slot = match reusable {
+                Some(slot) => slot,
+                None => {
+                    slots.push(desc);
+                    slot_free_after.push(0);
+                    slots.len() - 1
+                }
+            };
+            slot_free_after[slot] = last;
+            assignments.insert(name.clone(), slot);
+        }
+
+        Ok(CompiledGraph {
+            passes,
+            slots,
+            assignments,
+            lifetimes,
+        })
+    }
+
+    fn validate(&self) -> Result<(), GraphError> {
+        for (i, pass) in self.passes.iter().enumerate() {
+            if self.passes[..i].iter().any(|p| p.name == pass.name) {
+                return Err(GraphError::DuplicatePass(pass.name.clone()));
+            }
+            for resource in pass.inputs.iter().chain(&pass.outputs) {
+                if self.texture(resource).is_none() && !self.is_imported(resource) {
+                    return Err(GraphError::UnknownResource {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+            }
+            if let Some(resource) = pass.inputs.iter().find(|i| pass.outputs.contains(i)) {
+                return Err(GraphError::ReadWriteHazard {
+                    pass: pass.name.clone(),
+                    resource: resource.clone(),
+                });
+            }
+        }
+
+        let mut names: Vec<&String> = self
+            .textures
+            .iter()
+            .map(|(n, _)| n)
+            .chain(&self.imports)
+            .collect();
+        names.sort();
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(GraphError::DuplicateResource(pair[0].clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of compiling a `RenderGraph`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledGraph {
+    /// Live passes in execution order
+    pub passes: Vec<PassDesc>,
+    /// Physical textures that have to be allocated
+    pub slots: Vec<TextureDesc>,
+    /// Which slot each graph owned texture lives in
+    pub assignments: HashMap<String, usize>,
+    /// First and last position in `passes` each graph owned texture is used
+    pub lifetimes: HashMap<String, (usize, usize)>,
+}
+
+impl CompiledGraph {
+    pub fn slot(&self, resource: &str) -> Option<usize> {
+        self.assignments.get(resource).copied()
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&PassDesc> {
+        self.passes.iter().find(|p| p.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOUR: TextureDesc = TextureDesc {
+        format: wgpu::TextureFormat::Rgba16Float,
+        size: SizePolicy::Surface,
+    };
+    const HALF: TextureDesc = TextureDesc {
+        format: wgpu::TextureFormat::Rgba16Float,
+        size: SizePolicy::SurfaceScaled(0.5),
+    };
+
+    fn names(compiled: &CompiledGraph) -> Vec<&str> {
+        compiled.passes.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_passes_by_dependencies() {
+        let compiled = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("scene", COLOUR)
+            .add_texture("bloom", HALF)
+            .add_pass("tonemap", &["scene", "bloom"], &["swapchain"])
+            .add_pass("bloom", &["scene"], &["bloom"])
+            .add_pass("scene", &[], &["scene"])
+            .compile()
+            .unwrap();
+        assert_eq!(names(&compiled), ["scene", "bloom", "tonemap"]);
+    }
+
+    #[test]
+    fn ties_keep_declaration_order() {
+        let compiled = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("a", COLOUR)
+            .add_texture("b", COLOUR)
+            .add_pass("second", &[], &["b"])
+            .add_pass("first", &[], &["a"])
+            .add_pass("combine", &["a", "b"], &["swapchain"])
+            .compile()
+            .unwrap();
+        assert_eq!(names(&compiled), ["second", "first", "combine"]);
+    }
+
+    #[test]
+    fn culls_passes_without_visible_output() {
+        let compiled = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("scene", COLOUR)
+            .add_texture("unused", COLOUR)
+            .add_pass("scene", &[], &["scene"])
+            .add_pass("debug", &["scene"], &["unused"])
+            .add_pass("present", &["scene"], &["swapchain"])
+            .compile()
+            .unwrap();
+        assert_eq!(names(&compiled), ["scene", "present"]);
+        assert_eq!(compiled.slot("unused"), None);
+
+        let error = RenderGraph::new()
+            .add_texture("scene", COLOUR)
+            .add_pass("scene", &[], &["scene"])
+            .compile();
+        assert_eq!(error, Err(GraphError::NoOutput));
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("t1", COLOUR)
+            .add_texture("t2", COLOUR)
+            .add_pass("a", &["t2"], &["t1"])
+            .add_pass("b", &["t1"], &["t2"])
+            .add_pass("present", &["t1"], &["swapchain"])
+            .compile();
+        assert_eq!(
+            error,
+            Err(GraphError::Cycle(vec!["a".to_owned(), "b".to_owned()]))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_producers() {
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("scene", COLOUR)
+            .add_pass("a", &[], &["scene"])
+            .add_pass("b", &[], &["scene"])
+            .add_pass("present", &["scene"], &["swapchain"])
+            .compile();
+        assert_eq!(
+            error,
+            Err(GraphError::MultipleWriters {
+                resource: "scene".to_owned(),
+                passes: vec!["a".to_owned(), "b".to_owned()],
+            })
+        );
+
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("scene", COLOUR)
+            .add_pass("present", &["scene"], &["swapchain"])
+            .compile();
+        assert_eq!(
+            error,
+            Err(GraphError::MissingProducer {
+                pass: "present".to_owned(),
+                resource: "scene".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_reading_and_writing_one_texture() {
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("scene", COLOUR)
+            .add_pass("scene", &[], &["scene"])
+            .add_pass("blur", &["scene"], &["scene"])
+            .add_pass("present", &["scene"], &["swapchain"])
+            .compile();
+        assert_eq!(
+            error,
+            Err(GraphError::ReadWriteHazard {
+                pass: "blur".to_owned(),
+                resource: "scene".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_duplicate_names() {
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_pass("present", &["missing"], &["swapchain"])
+            .compile();
+        assert_eq!(
+            error,
+            Err(GraphError::UnknownResource {
+                pass: "present".to_owned(),
+                resource: "missing".to_owned(),
+            })
+        );
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("swapchain", COLOUR)
+            .compile();
+        assert_eq!(
+            error,
+            Err(GraphError::DuplicateResource("swapchain".to_owned()))
+        );
+        let error = RenderGraph::new()
+            .import("swapchain")
+            .add_pass("present", &[], &["swapchain"])
+            .add_pass("present", &[], &["swapchain"])
+            .compile();
+        assert_eq!(error, Err(GraphError::DuplicatePass("present".to_owned())));
+    }
+
+    #[test]
+    fn reuses_slots_after_last_read() {
+        let compiled = RenderGraph::new()
+            .import("swapchain")
+            .add_texture("t1", COLOUR)
+            .add_texture("t2", COLOUR)
+            .add_texture("t3", COLOUR)
+            .add_texture("small", HALF)
+            .add_pass("p1", &[], &["t1"])
+            .add_pass("p2", &["t1"], &["t2"])
+            .add_pass("p3", &["t2"], &["t3"])
+            .add_pass("p4", &["t3"], &["small"])
+            .add_pass("p5", &["small"], &["swapchain"])
+            .compile()
+            .unwrap();
+        assert_eq!(compiled.lifetimes["t1"], (0, 1));
+        assert_eq!(compiled.lifetimes["t3"], (2, 3));
+        assert_eq!(compiled.slot("t1"), compiled.slot("t3"));
+        assert_ne!(compiled.slot("t1"), compiled.slot("t2"));
+        // A different size never shares a slot
+        assert_eq!(compiled.slots, vec![COLOUR, COLOUR, HALF]);
+        assert_eq!(compiled.slot("swapchain"), None);
+    }
+
+    #[test]
+    fn size_policy_never_resolves_to_zero() {
+        assert_eq!(SizePolicy::Surface.resolve((800, 600)), (800, 600));
+        assert_eq!(
+            SizePolicy::SurfaceScaled(0.5).resolve((800, 600)),
+            (400, 300)
+        );
+        assert_eq!(SizePolicy::SurfaceScaled(0.5).resolve((1, 1)), (1, 1));
+        assert_eq!(SizePolicy::Fixed(0, 16).resolve((800, 600)), (1, 16));
+    }
+}
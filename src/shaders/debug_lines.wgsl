@@ -0,0 +1,44 @@
+// Unlit debug lines in world space, drawn over the final image
+
+struct CameraUniform {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] colour: vec4<f32>;
+    // Distance from the camera at which the line has faded out, 0 never fades
+    [[location(2)]] fade_distance: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] colour: vec4<f32>;
+    [[location(1)]] world_position: vec3<f32>;
+    [[location(2)]] fade_distance: f32;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.colour = in.colour;
+    out.world_position = in.position;
+    out.fade_distance = in.fade_distance;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var colour = in.colour;
+    if (in.fade_distance > 0.0) {
+        // Fade across the ground so the edge of the grid is never seen
+        let distance = length(in.world_position.xz - camera.view_position.xz);
+        colour.a = colour.a * clamp(1.0 - distance / in.fade_distance, 0.0, 1.0);
+    }
+    return colour;
+}
@@ -0,0 +1,196 @@
+// Bakes the image based lighting inputs of pbr.wgsl from an equirectangular HDR environment:
+// an irradiance map for diffuse light, GGX prefiltered mips for reflections and the split sum
+// BRDF lookup table. Brian Karis, Real Shading in Unreal Engine 4 (2013)
+
+let PI: f32 = 3.14159265359;
+let IRRADIANCE_PHI_STEPS: u32 = 64u;
+let IRRADIANCE_THETA_STEPS: u32 = 16u;
+let PREFILTER_SAMPLES: u32 = 256u;
+let BRDF_SAMPLES: u32 = 512u;
+
+struct FullscreenOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// A single triangle covering the target, no vertex buffer required
+[[stage(vertex)]]
+fn vs_fullscreen([[builtin(vertex_index)]] index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Must match BakeUniform in environment.rs
+struct BakeUniform {
+    roughness: f32;
+    source_mip: f32;
+    texel_solid_angle: f32;
+    max_mip: f32;
+};
+
+[[group(0), binding(0)]]
+var t_source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_source: sampler;
+[[group(0), binding(2)]]
+var<uniform> bake: BakeUniform;
+
+// Matches equirectangular_uv in pbr.wgsl
+fn equirectangular_uv(dir: vec3<f32>) -> vec2<f32> {
+    let u = atan2(dir.z, dir.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(dir.y, -1.0, 1.0)) / PI;
+    return vec2<f32>(u, v);
+}
+
+// The direction a texel of an equirectangular map stands for
+fn equirectangular_direction(uv: vec2<f32>) -> vec3<f32> {
+    let phi = (uv.x - 0.5) * 2.0 * PI;
+    let theta = uv.y * PI;
+    return vec3<f32>(cos(phi) * sin(theta), cos(theta), sin(phi) * sin(theta));
+}
+
+fn sample_source(dir: vec3<f32>, mip: f32) -> vec3<f32> {
+    return textureSampleLevel(t_source, s_source, equirectangular_uv(dir), mip).rgb;
+}
+
+// Rotates a vector about z onto the hemisphere about n
+fn tangent_to_world(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(n.y) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+// Van der Corput sequence, the bits of i mirrored about the binary point
+fn radical_inverse(i: u32) -> f32 {
+    var bits = (i << 16u) | (i >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xaaaaaaaau) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xccccccccu) >> 2u);
+    bits = ((bits & 0x0f0f0f0fu) << 4u) | ((bits & 0xf0f0f0f0u) >> 4u);
+    bits = ((bits & 0x00ff00ffu) << 8u) | ((bits & 0xff00ff00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), radical_inverse(i));
+}
+
+// A half vector distributed like the GGX lobe about n
+fn importance_sample_ggx(xi: vec2<f32>, n: vec3<f32>, alpha: f32) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return tangent_to_world(vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta), n);
+}
+
+// Matches distribution_ggx in pbr.wgsl
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith geometry term with the k of image based lighting
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    let k = alpha / 2.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+// The cosine weighted radiance over the hemisphere about the texel's direction, divided by pi
+// so the shader only multiplies it by the albedo. The source mip is about as coarse as the
+// spacing of the samples so none of it is skipped.
+[[stage(fragment)]]
+fn fs_irradiance(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let n = equirectangular_direction(in.uv);
+    var sum = vec3<f32>(0.0);
+    var i: u32 = 0u;
+    loop {
+        if (i >= IRRADIANCE_PHI_STEPS) { break; }
+        let phi = (f32(i) + 0.5) / f32(IRRADIANCE_PHI_STEPS) * 2.0 * PI;
+        var j: u32 = 0u;
+        loop {
+            if (j >= IRRADIANCE_THETA_STEPS) { break; }
+            let theta = (f32(j) + 0.5) / f32(IRRADIANCE_THETA_STEPS) * 0.5 * PI;
+            let local = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let radiance = sample_source(tangent_to_world(local, n), bake.source_mip);
+            sum = sum + radiance * cos(theta) * sin(theta);
+            j = j + 1u;
+        }
+        i = i + 1u;
+    }
+    let count = f32(IRRADIANCE_PHI_STEPS * IRRADIANCE_THETA_STEPS);
+    return vec4<f32>(PI * sum / count, 1.0);
+}
+
+// The radiance a GGX lobe of the mip's roughness reflects towards the texel's direction, with
+// n = v = r as the split sum assumes. Each sample reads the source mip whose texels cover the
+// solid angle the sample stands for, which hides the noise of so few samples
+// (GPU Gems 3, chapter 20)
+[[stage(fragment)]]
+fn fs_prefilter(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let n = equirectangular_direction(in.uv);
+    if (bake.roughness <= 0.0) {
+        return vec4<f32>(sample_source(n, bake.source_mip), 1.0);
+    }
+    let alpha = bake.roughness * bake.roughness;
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= PREFILTER_SAMPLES) { break; }
+        let h = importance_sample_ggx(hammersley(i, PREFILTER_SAMPLES), n, alpha);
+        let l = normalize(2.0 * dot(n, h) * h - n);
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            // With n = v the pdf of l is D n.h / (4 v.h) = D / 4
+            let pdf = distribution_ggx(max(dot(n, h), 0.0), alpha) / 4.0;
+            let sample_solid_angle = 1.0 / (f32(PREFILTER_SAMPLES) * pdf + 0.0001);
+            let mip = clamp(
+                0.5 * log2(sample_solid_angle / bake.texel_solid_angle) + 1.0,
+                0.0,
+                bake.max_mip
+            );
+            sum = sum + sample_source(l, mip) * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+// The scale (red) and bias (green) the specular BRDF integrated over the hemisphere applies to
+// f0, by n.v along u and roughness along v
+[[stage(fragment)]]
+fn fs_brdf_lut(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let n_dot_v = max(in.uv.x, 0.001);
+    let alpha = in.uv.y * in.uv.y;
+    let n = vec3<f32>(0.0, 0.0, 1.0);
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    var scale = 0.0;
+    var bias = 0.0;
+    var i: u32 = 0u;
+    loop {
+        if (i >= BRDF_SAMPLES) { break; }
+        let h = importance_sample_ggx(hammersley(i, BRDF_SAMPLES), n, alpha);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+        let n_dot_l = max(l.z, 0.0);
+        if (n_dot_l > 0.0) {
+            let n_dot_h = max(h.z, 0.0001);
+            let v_dot_h = max(dot(v, h), 0.0);
+            let visibility = geometry_smith(n_dot_v, n_dot_l, alpha) * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale = scale + (1.0 - fresnel) * visibility;
+            bias = bias + fresnel * visibility;
+        }
+        i = i + 1u;
+    }
+    return vec4<f32>(scale, bias, 0.0, 1.0) / vec4<f32>(f32(BRDF_SAMPLES), f32(BRDF_SAMPLES), 1.0, 1.0);
+}
@@ -0,0 +1,24 @@
+// Flat shaded 2D geometry drawn over the final image, positions are already in clip space
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] colour: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] colour: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.colour = in.colour;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.colour;
+}
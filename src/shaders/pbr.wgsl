@@ -0,0 +1,254 @@
+// Metallic-roughness PBR shader, follows the glTF 2.0 BRDF reference:
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#appendix-b-brdf-implementation
+
+let PI: f32 = 3.14159265359;
+
+// Vertex shader
+
+struct CameraUniform {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] tangent: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] world_position: vec3<f32>;
+    [[location(2)]] world_normal: vec3<f32>;
+    [[location(3)]] world_tangent: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    // Node transforms are baked into the vertices when loading
+    out.tex_coords = model.tex_coords;
+    out.world_position = model.position;
+    out.world_normal = model.normal;
+    out.world_tangent = model.tangent;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+// Must match PbrMaterialUniform in material.rs
+struct MaterialUniform {
+    base_colour_factor: vec4<f32>;
+    emissive_factor: vec3<f32>;
+    metallic_factor: f32;
+    roughness_factor: f32;
+    normal_scale: f32;
+    occlusion_strength: f32;
+    alpha_cutoff: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> material: MaterialUniform;
+[[group(0), binding(1)]]
+var s_material: sampler;
+[[group(0), binding(2)]]
+var t_base_colour: texture_2d<f32>;
+[[group(0), binding(3)]]
+var t_metallic_roughness: texture_2d<f32>;
+[[group(0), binding(4)]]
+var t_normal: texture_2d<f32>;
+[[group(0), binding(5)]]
+var t_occlusion: texture_2d<f32>;
+[[group(0), binding(6)]]
+var t_emissive: texture_2d<f32>;
+
+// Must match the layouts in light.rs
+struct DirectionalLight {
+    direction: vec3<f32>;
+    intensity: f32;
+    colour: vec3<f32>;
+};
+
+struct PointLight {
+    position: vec3<f32>;
+    intensity: f32;
+    colour: vec3<f32>;
+    range: f32;
+    attenuation: vec3<f32>;
+};
+
+struct LightUniform {
+    ambient: vec4<f32>;
+    counts: vec4<u32>;
+    directional: array<DirectionalLight, 4>;
+    point: array<PointLight, 16>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> lights: LightUniform;
+
+// Must match EnvironmentUniform in environment.rs
+struct EnvironmentUniform {
+    intensity: f32;
+    max_mip: f32;
+};
+
+// Baked in environment.rs
+[[group(3), binding(0)]]
+var t_prefiltered: texture_2d<f32>;
+[[group(3), binding(1)]]
+var s_environment: sampler;
+[[group(3), binding(2)]]
+var<uniform> environment: EnvironmentUniform;
+[[group(3), binding(3)]]
+var t_irradiance: texture_2d<f32>;
+[[group(3), binding(4)]]
+var t_brdf_lut: texture_2d<f32>;
+[[group(3), binding(5)]]
+var s_brdf_lut: sampler;
+
+struct Surface {
+    albedo: vec3<f32>;
+    metallic: f32;
+    roughness: f32;
+    f0: vec3<f32>;
+};
+
+fn fresnel_schlick(f0: vec3<f32>, v_dot_h: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+// Trowbridge-Reitz (GGX) normal distribution
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Height correlated Smith visibility, includes the 1 / (4 n.l n.v) term
+fn visibility_smith_ggx(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - a2) + a2);
+    let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - a2) + a2);
+    let ggx = ggx_v + ggx_l;
+    if (ggx > 0.0) {
+        return 0.5 / ggx;
+    }
+    return 0.0;
+}
+
+// Outgoing radiance from one light
+fn brdf(surface: Surface, n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, radiance: vec3<f32>) -> vec3<f32> {
+    let h = normalize(v + l);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let n_dot_v = max(dot(n, v), 0.0001);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let v_dot_h = max(dot(v, h), 0.0);
+    let alpha = surface.roughness * surface.roughness;
+
+    let f = fresnel_schlick(surface.f0, v_dot_h);
+    let specular = f * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - surface.metallic) * surface.albedo / PI;
+
+    return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// Maps a direction onto the equirectangular environment texture
+fn equirectangular_uv(dir: vec3<f32>) -> vec2<f32> {
+    let u = atan2(dir.z, dir.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(dir.y, -1.0, 1.0)) / PI;
+    return vec2<f32>(u, v);
+}
+
+// Fresnel averaged over the rough lobe, which reflects less at grazing angles than a mirror
+fn fresnel_schlick_roughness(f0: vec3<f32>, n_dot_v: f32, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+}
+
+// Split sum image based lighting (Karis 2013). The irradiance map holds the diffuse light
+// around each normal, the prefiltered map's mips hold reflections for roughness 0 to 1 and the
+// lookup table the scale and bias of f0 by n.v and roughness.
+fn image_based_lighting(surface: Surface, n: vec3<f32>, v: vec3<f32>) -> vec3<f32> {
+    let n_dot_v = max(dot(n, v), 0.0001);
+    let irradiance = textureSampleLevel(t_irradiance, s_environment, equirectangular_uv(n), 0.0).rgb;
+    let r = reflect(-v, n);
+    let prefiltered = textureSampleLevel(
+        t_prefiltered,
+        s_environment,
+        equirectangular_uv(r),
+        surface.roughness * environment.max_mip
+    ).rgb;
+    let brdf = textureSampleLevel(t_brdf_lut, s_brdf_lut, vec2<f32>(n_dot_v, surface.roughness), 0.0).rg;
+
+    let f = fresnel_schlick_roughness(surface.f0, n_dot_v, surface.roughness);
+    let diffuse = irradiance * surface.albedo * (vec3<f32>(1.0) - f) * (1.0 - surface.metallic);
+    let specular = prefiltered * (surface.f0 * brdf.x + brdf.y);
+    return (diffuse + specular) * environment.intensity;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // All textures are sampled up front, sampling after a discard is non-uniform control flow
+    let base_colour = textureSample(t_base_colour, s_material, in.tex_coords) * material.base_colour_factor;
+    let mr = textureSample(t_metallic_roughness, s_material, in.tex_coords);
+    let normal_sample = textureSample(t_normal, s_material, in.tex_coords).xyz;
+    let occlusion = textureSample(t_occlusion, s_material, in.tex_coords).r;
+    let emissive = textureSample(t_emissive, s_material, in.tex_coords).rgb * material.emissive_factor;
+
+    var surface: Surface;
+    surface.albedo = base_colour.rgb;
+    surface.metallic = clamp(mr.b * material.metallic_factor, 0.0, 1.0);
+    surface.roughness = clamp(mr.g * material.roughness_factor, 0.04, 1.0);
+    surface.f0 = mix(vec3<f32>(0.04), surface.albedo, surface.metallic);
+
+    // Tangent space normal mapping
+    let geometric_normal = normalize(in.world_normal);
+    let tangent = normalize(in.world_tangent.xyz - geometric_normal * dot(geometric_normal, in.world_tangent.xyz));
+    let bitangent = cross(geometric_normal, tangent) * in.world_tangent.w;
+    var tangent_normal = normal_sample * 2.0 - 1.0;
+    tangent_normal = vec3<f32>(tangent_normal.xy * material.normal_scale, tangent_normal.z);
+    let n = normalize(mat3x3<f32>(tangent, bitangent, geometric_normal) * tangent_normal);
+
+    let v = normalize(camera.view_position.xyz - in.world_position);
+
+    var colour = vec3<f32>(0.0);
+
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.counts.x) { break; }
+        let light = lights.directional[i];
+        colour = colour + brdf(surface, n, v, -light.direction, light.colour * light.intensity);
+        i = i + 1u;
+    }
+
+    i = 0u;
+    loop {
+        if (i >= lights.counts.y) { break; }
+        let light = lights.point[i];
+        let to_light = light.position - in.world_position;
+        let distance = length(to_light);
+        if (distance <= light.range) {
+            let a = light.attenuation;
+            let attenuation = 1.0 / (a.x + a.y * distance + a.z * distance * distance);
+            colour = colour + brdf(surface, n, v, to_light / distance, light.colour * light.intensity * attenuation);
+        }
+        i = i + 1u;
+    }
+
+    let ambient = lights.ambient.rgb * lights.ambient.a * surface.albedo;
+    var indirect = ambient + image_based_lighting(surface, n, v);
+
+    indirect = indirect * (1.0 + material.occlusion_strength * (occlusion - 1.0));
+
+    if (base_colour.a < material.alpha_cutoff) {
+        discard;
+    }
+
+    return vec4<f32>(colour + indirect + emissive, base_colour.a);
+}
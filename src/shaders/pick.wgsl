@@ -0,0 +1,39 @@
+// Writes the id of the object covering each pixel, along with its depth
+
+// Must match PickUniform in picking.rs
+struct PickUniform {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> pick: PickUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    // Object ids are passed as the instance index so no per object buffer is needed
+    [[location(0), interpolate(flat)]] id: u32;
+};
+
+struct PickOutput {
+    [[location(0)]] id: u32;
+    [[location(1)]] depth: f32;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+    [[builtin(instance_index)]] id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = pick.view_proj * vec4<f32>(position, 1.0);
+    out.id = id;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> PickOutput {
+    var out: PickOutput;
+    out.id = in.id;
+    out.depth = in.clip_position.z;
+    return out;
+}
@@ -0,0 +1,62 @@
+// Bloom: extracts the bright parts of the scene and blurs them with a separable gaussian.
+// Runs at a reduced resolution, the result is added back to the scene by the tonemap pass.
+
+struct FullscreenOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// A single triangle covering the screen, no vertex buffer required
+[[stage(vertex)]]
+fn vs_fullscreen([[builtin(vertex_index)]] index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Must match PostUniform in post.rs
+struct PostUniform {
+    exposure: f32;
+    tonemapper: u32;
+    bloom_threshold: f32;
+    bloom_intensity: f32;
+    lut_size: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> post: PostUniform;
+[[group(0), binding(1)]]
+var s_post: sampler;
+[[group(0), binding(2)]]
+var t_input: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn fs_extract(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let colour = textureSample(t_input, s_post, in.uv).rgb;
+    let brightness = max(colour.r, max(colour.g, colour.b));
+    let contribution = max(brightness - post.bloom_threshold, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(colour * contribution, 1.0);
+}
+
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let texel = direction / vec2<f32>(textureDimensions(t_input));
+    // 9 tap gaussian using linear filtering to sample between texels
+    var colour = textureSample(t_input, s_post, uv).rgb * 0.2270270270;
+    colour = colour + textureSample(t_input, s_post, uv + texel * 1.3846153846).rgb * 0.3162162162;
+    colour = colour + textureSample(t_input, s_post, uv - texel * 1.3846153846).rgb * 0.3162162162;
+    colour = colour + textureSample(t_input, s_post, uv + texel * 3.2307692308).rgb * 0.0702702703;
+    colour = colour + textureSample(t_input, s_post, uv - texel * 3.2307692308).rgb * 0.0702702703;
+    return vec4<f32>(colour, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_blur_horizontal(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    return blur(in.uv, vec2<f32>(1.0, 0.0));
+}
+
+[[stage(fragment)]]
+fn fs_blur_vertical(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    return blur(in.uv, vec2<f32>(0.0, 1.0));
+}
@@ -0,0 +1,59 @@
+// Colour grading through a 3D lookup table stored as a horizontal strip of blue slices,
+// the strip is lut_size * lut_size wide and lut_size high
+
+struct FullscreenOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// A single triangle covering the screen, no vertex buffer required
+[[stage(vertex)]]
+fn vs_fullscreen([[builtin(vertex_index)]] index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Must match PostUniform in post.rs
+struct PostUniform {
+    exposure: f32;
+    tonemapper: u32;
+    bloom_threshold: f32;
+    bloom_intensity: f32;
+    lut_size: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> post: PostUniform;
+[[group(0), binding(1)]]
+var s_post: sampler;
+[[group(0), binding(2)]]
+var t_input: texture_2d<f32>;
+[[group(0), binding(3)]]
+var t_lut: texture_2d<f32>;
+
+fn lut_uv(colour: vec3<f32>, slice: f32) -> vec2<f32> {
+    let size = post.lut_size;
+    let u = (slice * size + colour.r * (size - 1.0) + 0.5) / (size * size);
+    let v = (colour.g * (size - 1.0) + 0.5) / size;
+    return vec2<f32>(u, v);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    // Graded as straight colour and premultiplied again, like tonemapping
+    let input = textureSample(t_input, s_post, in.uv);
+    let alpha = clamp(input.a, 0.0, 1.0);
+    let colour = clamp(input.rgb / max(alpha, 0.0001), vec3<f32>(0.0), vec3<f32>(1.0));
+
+    // Interpolate between the two nearest blue slices, red and green are filtered by the sampler
+    let blue = colour.b * (post.lut_size - 1.0);
+    let slice = floor(blue);
+    let next_slice = min(slice + 1.0, post.lut_size - 1.0);
+    let a = textureSample(t_lut, s_post, lut_uv(colour, slice)).rgb;
+    let b = textureSample(t_lut, s_post, lut_uv(colour, next_slice)).rgb;
+
+    return vec4<f32>(mix(a, b, blue - slice) * alpha, alpha);
+}
@@ -0,0 +1,66 @@
+// Fast approximate anti-aliasing, based on the FXAA 3.11 console variant by Timothy Lottes
+
+let FXAA_REDUCE_MIN: f32 = 0.0078125;
+let FXAA_REDUCE_MUL: f32 = 0.125;
+let FXAA_SPAN_MAX: f32 = 8.0;
+
+struct FullscreenOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// A single triangle covering the screen, no vertex buffer required
+[[stage(vertex)]]
+fn vs_fullscreen([[builtin(vertex_index)]] index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+[[group(0), binding(1)]]
+var s_post: sampler;
+[[group(0), binding(2)]]
+var t_input: texture_2d<f32>;
+
+fn luma(colour: vec3<f32>) -> f32 {
+    // Perceptual luma is computed on gamma encoded values, approximated with a square root
+    return dot(sqrt(colour), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+
+    let centre = textureSample(t_input, s_post, in.uv);
+    let luma_nw = luma(textureSample(t_input, s_post, in.uv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let luma_ne = luma(textureSample(t_input, s_post, in.uv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let luma_sw = luma(textureSample(t_input, s_post, in.uv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let luma_se = luma(textureSample(t_input, s_post, in.uv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let luma_m = luma(centre.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (
+        textureSample(t_input, s_post, in.uv + dir * (1.0 / 3.0 - 0.5)).rgb
+        + textureSample(t_input, s_post, in.uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        textureSample(t_input, s_post, in.uv + dir * -0.5).rgb
+        + textureSample(t_input, s_post, in.uv + dir * 0.5).rgb
+    );
+
+    let luma_b = luma(rgb_b);
+    let outside = luma_b < luma_min || luma_b > luma_max;
+    return vec4<f32>(select(rgb_b, rgb_a, outside), centre.a);
+}
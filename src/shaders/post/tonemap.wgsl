@@ -0,0 +1,67 @@
+// Maps the HDR scene, plus bloom, into the displayable range
+
+struct FullscreenOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// A single triangle covering the screen, no vertex buffer required
+[[stage(vertex)]]
+fn vs_fullscreen([[builtin(vertex_index)]] index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Must match PostUniform in post.rs
+struct PostUniform {
+    exposure: f32;
+    tonemapper: u32;
+    bloom_threshold: f32;
+    bloom_intensity: f32;
+    lut_size: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> post: PostUniform;
+[[group(0), binding(1)]]
+var s_post: sampler;
+[[group(0), binding(2)]]
+var t_scene: texture_2d<f32>;
+[[group(0), binding(3)]]
+var t_bloom: texture_2d<f32>;
+
+fn reinhard(colour: vec3<f32>) -> vec3<f32> {
+    return colour / (colour + vec3<f32>(1.0));
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(colour: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((colour * (a * colour + b)) / (colour * (c * colour + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// The scene is premultiplied, so it is tonemapped as straight colour and multiplied by its
+// alpha again for a transparent window. Opaque scenes have an alpha of 1 and are unaffected.
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let scene = textureSample(t_scene, s_post, in.uv);
+    let alpha = clamp(scene.a, 0.0, 1.0);
+    let straight = scene.rgb / max(alpha, 0.0001);
+    let bloom = textureSample(t_bloom, s_post, in.uv).rgb;
+    let colour = (straight + bloom * post.bloom_intensity) * post.exposure;
+
+    var mapped: vec3<f32>;
+    switch (post.tonemapper) {
+        case 0u: { mapped = reinhard(colour); }
+        case 1u: { mapped = aces(colour); }
+        default: { mapped = clamp(colour, vec3<f32>(0.0), vec3<f32>(1.0)); }
+    }
+    return vec4<f32>(mapped * alpha, alpha);
+}
@@ -1,6 +1,7 @@
 // Vertex shader
 
 struct CameraUniform {
+    view_position: vec4<f32>;
     view_proj: mat4x4<f32>;
 };
 
@@ -11,12 +12,15 @@ var<uniform> camera: CameraUniform;
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
     // [[location(1)]] colour: vec3<f32>;
 };
 
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] world_position: vec3<f32>;
+    [[location(2)]] world_normal: vec3<f32>;
     // [[location(0)]] colour: vec3<f32>;
 };
 
@@ -32,20 +36,102 @@ fn vs_main(
     // out.position = vec2<f32>(x, y);
     // out.colour = model.colour;
     out.tex_coords = model.tex_coords;
+    // No model matrix yet so model space is world space
+    out.world_position = model.position;
+    out.world_normal = model.normal;
     out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
     return out;
 }
 
 // Fragment shader
 
+struct MaterialUniform {
+    specular_colour: vec3<f32>;
+    shininess: f32;
+};
+
 [[group(0), binding(0)]]
 var t_diffuse: texture_2d<f32>;
 [[group(0), binding(1)]]
 var s_diffuse: sampler;
+[[group(0), binding(2)]]
+var<uniform> material: MaterialUniform;
+
+// Must match the layouts in light.rs
+struct DirectionalLight {
+    direction: vec3<f32>;
+    intensity: f32;
+    colour: vec3<f32>;
+};
+
+struct PointLight {
+    position: vec3<f32>;
+    intensity: f32;
+    colour: vec3<f32>;
+    range: f32;
+    attenuation: vec3<f32>;
+};
+
+struct LightUniform {
+    ambient: vec4<f32>;
+    counts: vec4<u32>;
+    directional: array<DirectionalLight, 4>;
+    point: array<PointLight, 16>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> lights: LightUniform;
+
+// Blinn-Phong diffuse and specular contribution of a single light
+fn blinn_phong(
+    light_dir: vec3<f32>,
+    light_colour: vec3<f32>,
+    normal: vec3<f32>,
+    view_dir: vec3<f32>,
+) -> vec3<f32> {
+    let half_dir = normalize(view_dir + light_dir);
+    let diffuse = max(dot(normal, light_dir), 0.0) * light_colour;
+    let specular = pow(max(dot(normal, half_dir), 0.0), material.shininess)
+        * material.specular_colour * light_colour;
+    return diffuse + specular;
+}
 
 // Entry point set in the render pipeline
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let object_colour = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let normal = normalize(in.world_normal);
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+
+    var result = lights.ambient.rgb * lights.ambient.a;
+
+    var i: u32 = 0u;
+    loop {
+        if (i >= lights.counts.x) { break; }
+        let light = lights.directional[i];
+        result = result + blinn_phong(-light.direction, light.colour * light.intensity, normal, view_dir);
+        i = i + 1u;
+    }
+
+    i = 0u;
+    loop {
+        if (i >= lights.counts.y) { break; }
+        let light = lights.point[i];
+        let to_light = light.position - in.world_position;
+        let distance = length(to_light);
+        if (distance <= light.range) {
+            let a = light.attenuation;
+            let attenuation = 1.0 / (a.x + a.y * distance + a.z * distance * distance);
+            result = result + blinn_phong(
+                to_light / distance,
+                light.colour * light.intensity * attenuation,
+                normal,
+                view_dir,
+            );
+        }
+        i = i + 1u;
+    }
+
+    return vec4<f32>(result * object_colour.rgb, object_colour.a);
     // return vec4<f32>(in.colour, 1.0);
 }
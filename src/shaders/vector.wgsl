@@ -0,0 +1,34 @@
+// Tessellated 2D paths drawn over the final image, positions are in pixels from the top left
+
+struct ScreenUniform {
+    size: vec2<f32>;
+    padding: vec2<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> screen: ScreenUniform;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    // Alpha already includes the anti-aliasing coverage
+    [[location(1)]] colour: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] colour: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = in.position / screen.size * 2.0 - vec2<f32>(1.0, 1.0);
+    out.clip_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.colour = in.colour;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.colour;
+}
@@ -0,0 +1,307 @@
+#![allow(dead_code)]
+//! Recovery from surface errors.
+//! Acquiring the next swapchain texture fails routinely: the surface is lost after a driver reset,
+//! outdated after the window changes size and times out when the compositor is busy. None of
+//! those should take the application down, so the engine asks `SurfaceRecovery` what to do before
+//! each frame and after each failed acquire. Only running out of memory is escalated.
+//! https://docs.rs/wgpu/0.12.0/wgpu/enum.SurfaceError.html
+
+use std::fmt;
+
+/// Number of reconfigure attempts in a row before the surface is considered unrecoverable
+pub const MAX_RECONFIGURE_ATTEMPTS: u32 = 3;
+
+/// Errors that rendering cannot recover from on its own
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RenderError {
+    /// The gpu ran out of memory acquiring the next frame
+    OutOfMemory,
+    /// Reconfiguring did not bring the surface back
+    SurfaceUnrecoverable { attempts: u32 },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfMemory => write!(f, "out of memory while acquiring the next frame"),
+            Self::SurfaceUnrecoverable { attempts } => write!(
+                f,
+                "surface could not be recovered after {} reconfigure attempts",
+                attempts
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SurfaceState {
+    /// Configured and ready to render
+    Ready,
+    /// The window has no area, nothing can or needs to be drawn
+    Minimized,
+    /// The surface has to be configured again before it can be used
+    NeedsReconfigure,
+}
+
+/// What the engine should do next
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrameAction {
+    Render,
+    /// Configure the surface, then render
+    Reconfigure,
+    /// Drop this frame
+    Skip,
+}
+
+#[derive(Clone, Debug)]
+pub struct SurfaceRecovery {
+    state: SurfaceState,
+    failed_attempts: u32,
+}
+
+impl Default for SurfaceRecovery {
+    fn default() -> Self {
+        Self {
+            state: SurfaceState::Ready,
+            failed_attempts: 0,
+        }
+    }
+}
+
+impl SurfaceRecovery {
+    pub fn state(&self) -> SurfaceState {
+        self.state
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.state == SurfaceState::Minimized
+    }
+
+    /// Call whenever the window size changes.
+    /// Returns true if the surface should be configured with the new size
+    pub fn resized(&mut self, size: (u32, u32)) -> bool {
+        if size.0 == 0 || size.1 == 0 {
+            self.state = SurfaceState::Minimized;
+            false
+        } else {
+            // Configuring now is the same as any pending reconfigure
+            self.state = SurfaceState::Ready;
+            self.failed_attempts = 0;
+            true
+        }
+    }
+
+    /// Call before acquiring the next frame
+    pub fn begin_frame(&self) -> FrameAction {
+        match self.state {
+            SurfaceState::Ready => FrameAction::Render,
+            SurfaceState::Minimized => FrameAction::Skip,
+            SurfaceState::NeedsReconfigure => FrameAction::Reconfigure,
+        }
+    }
+
+    /// Call after the surface has been configured again
+    pub fn reconfigured(&mut self) {
+        if self.state == SurfaceState::NeedsReconfigure {
+            self.state = SurfaceState::Ready;
+        }
+    }
+
+    /// Call after a frame was acquired successfully
+    pub fn acquired(&mut self) {
+        self.failed_attempts = 0;
+    }
+
+    /// Call when acquiring a frame failed, returns the next action or the error to escalate
+    pub fn acquire_failed(
+        &mut self,
+        error: &wgpu::SurfaceError,
+    ) -> Result<FrameAction, RenderError> {
+        match error {
+            wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => {
+                self.failed_attempts += 1;
+                if self.failed_attempts > MAX_RECONFIGURE_ATTEMPTS {
+                    return Err(RenderError::SurfaceUnrecoverable {
+                        attempts: MAX_RECONFIGURE_ATTEMPTS,
+                    });
+                }
+                self.state = SurfaceState::NeedsReconfigure;
+                Ok(FrameAction::Reconfigure)
+            }
+            // The next frame will most likely succeed
+            wgpu::SurfaceError::Timeout => Ok(FrameAction::Skip),
+            wgpu::SurfaceError::OutOfMemory => Err(RenderError::OutOfMemory),
+        }
+    }
+}
+
+/// Whether the next frame differs from the last presented one, and so whether a WM_PAINT has
+/// to be asked for. Kept separate from the engine so skipped frames cannot leave it stuck dirty
+/// with no paint on its way.
+#[derive(Clone, Debug)]
+pub struct Redraw {
+    dirty: bool,
+}
+
+impl Default for Redraw {
+    /// Dirty, the window paints once when shown so the first frame is already scheduled
+    fn default() -> Self {
+        Self { dirty: true }
+    }
+}
+
+impl Redraw {
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Marks the next frame as changed.
+    /// Returns true if a redraw has to be requested, earlier requests still being pending
+    pub fn invalidate(&mut self) -> bool {
+        !std::mem::replace(&mut self.dirty, true)
+    }
+
+    /// Call before drawing a frame, returns whether anything changed since the last one.
+    /// Cleared before drawing so anything invalidated during the frame gets another one
+    pub fn begin_draw(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    /// Call when a frame was skipped. Returns true if a redraw has to be requested to try again.
+    /// Nothing is drawn while minimized, so the frame is dropped instead and the resize that
+    /// restores the window invalidates it again
+    pub fn skipped(&mut self, minimized: bool) -> bool {
+        if minimized {
+            self.dirty = false;
+        }
+        self.dirty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimizing_skips_until_resized() {
+        let mut recovery = SurfaceRecovery::default();
+        assert_eq!(recovery.begin_frame(), FrameAction::Render);
+        assert!(!recovery.resized((0, 600)));
+        assert!(recovery.is_minimized());
+        assert_eq!(recovery.begin_frame(), FrameAction::Skip);
+        assert!(recovery.resized((800, 600)));
+        assert_eq!(recovery.begin_frame(), FrameAction::Render);
+    }
+
+    #[test]
+    fn lost_surface_is_reconfigured() {
+        let mut recovery = SurfaceRecovery::default();
+        assert_eq!(
+            recovery.acquire_failed(&wgpu::SurfaceError::Lost),
+            Ok(FrameAction::Reconfigure)
+        );
+        assert_eq!(recovery.state(), SurfaceState::NeedsReconfigure);
+        assert_eq!(recovery.begin_frame(), FrameAction::Reconfigure);
+        recovery.reconfigured();
+        assert_eq!(recovery.begin_frame(), FrameAction::Render);
+    }
+
+    #[test]
+    fn reconfigured_does_not_leave_minimized() {
+        let mut recovery = SurfaceRecovery::default();
+        recovery.resized((0, 0));
+        recovery.reconfigured();
+        assert!(recovery.is_minimized());
+    }
+
+    #[test]
+    fn repeated_failures_escalate() {
+        let mut recovery = SurfaceRecovery::default();
+        for _ in 0..MAX_RECONFIGURE_ATTEMPTS {
+            assert_eq!(
+                recovery.acquire_failed(&wgpu::SurfaceError::Outdated),
+                Ok(FrameAction::Reconfigure)
+            );
+            recovery.reconfigured();
+        }
+        assert_eq!(
+            recovery.acquire_failed(&wgpu::SurfaceError::Outdated),
+            Err(RenderError::SurfaceUnrecoverable {
+                attempts: MAX_RECONFIGURE_ATTEMPTS
+            })
+        );
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let mut recovery = SurfaceRecovery::default();
+        for _ in 0..MAX_RECONFIGURE_ATTEMPTS * 2 {
+            assert!(recovery.acquire_failed(&wgpu::SurfaceError::Lost).is_ok());
+            recovery.reconfigured();
+            recovery.acquired();
+        }
+        // A resize also counts as a fresh configure
+        for _ in 0..MAX_RECONFIGURE_ATTEMPTS {
+            assert!(recovery.acquire_failed(&wgpu::SurfaceError::Lost).is_ok());
+        }
+        recovery.resized((640, 480));
+        assert!(recovery.acquire_failed(&wgpu::SurfaceError::Lost).is_ok());
+    }
+
+    #[test]
+    fn timeout_skips_and_out_of_memory_escalates() {
+        let mut recovery = SurfaceRecovery::default();
+        assert_eq!(
+            recovery.acquire_failed(&wgpu::SurfaceError::Timeout),
+            Ok(FrameAction::Skip)
+        );
+        assert_eq!(recovery.state(), SurfaceState::Ready);
+        assert_eq!(
+            recovery.acquire_failed(&wgpu::SurfaceError::OutOfMemory),
+            Err(RenderError::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn invalidating_requests_one_redraw() {
+        let mut redraw = Redraw::default();
+        assert!(redraw.begin_draw());
+        assert!(!redraw.is_dirty());
+        assert!(redraw.invalidate());
+        assert!(!redraw.invalidate());
+        assert!(redraw.begin_draw());
+        assert!(!redraw.begin_draw());
+    }
+
+    #[test]
+    fn timed_out_frames_are_retried() {
+        let mut redraw = Redraw::default();
+        redraw.begin_draw();
+        assert!(redraw.invalidate());
+        assert!(redraw.skipped(false));
+        assert!(redraw.is_dirty());
+        // Frames drawn without changes are not retried
+        redraw.begin_draw();
+        assert!(!redraw.skipped(false));
+    }
+
+    #[test]
+    fn minimized_frames_are_dropped_until_restored() {
+        let mut recovery = SurfaceRecovery::default();
+        let mut redraw = Redraw::default();
+        recovery.resized((0, 0));
+        assert_eq!(recovery.begin_frame(), FrameAction::Skip);
+        assert!(!redraw.skipped(recovery.is_minimized()));
+        assert!(!redraw.is_dirty());
+        // Changes while minimized still schedule a paint, which is skipped again
+        assert!(redraw.invalidate());
+        assert!(!redraw.skipped(recovery.is_minimized()));
+        // Restoring invalidates, which has to request a redraw
+        assert!(recovery.resized((800, 600)));
+        assert!(redraw.invalidate());
+        assert_eq!(recovery.begin_frame(), FrameAction::Render);
+        assert!(redraw.begin_draw());
+    }
+}
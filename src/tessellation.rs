@@ -0,0 +1,901 @@
+#![allow(dead_code)]
+//! Triangles for filling and stroking 2D paths, on the CPU so the output can be checked without
+//! a device.
+//!
+//! Fills are split into horizontal trapezoids: every vertex and every crossing of two edges
+//! starts a new band, and within a band the edges no longer cross so the spans inside the path
+//! can be read off by counting windings from the left. Strokes are turned into polygons for each
+//! segment, join and cap, which are then filled as one non-zero shape so overlaps are not drawn
+//! twice. Anti-aliasing adds a fringe along the outside of each edge that fades from fully
+//! covered to empty over one pixel.
+
+use crate::path::{arc_points, Path, Polyline};
+use cgmath::{EuclideanSpace, InnerSpace, Point2, Vector2};
+
+/// Width of the anti-aliased fringe in pixels
+pub const AA_WIDTH: f32 = 1.0;
+/// Bands thinner than this are dropped
+const EPSILON: f32 = 1e-4;
+/// How far either side of an edge is checked for being filled
+const PROBE: f32 = 0.01;
+
+/// Which areas enclosed by a path are filled
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Filled where the path winds around more times one way than the other
+    #[default]
+    NonZero,
+    /// Filled where the path winds around an odd number of times
+    EvenOdd,
+}
+
+impl FillRule {
+    fn is_inside(self, winding: i32) -> bool {
+        match self {
+            Self::NonZero => winding != 0,
+            Self::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FillOptions {
+    pub rule: FillRule,
+    /// Furthest a flattened curve may be from the real one, in pixels
+    pub tolerance: f32,
+    pub anti_alias: bool,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            rule: FillRule::default(),
+            tolerance: 0.25,
+            anti_alias: true,
+        }
+    }
+}
+
+/// The shape drawn where two segments of a stroke meet
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet, falls back to a bevel past the miter limit
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// The shape drawn at the ends of an open stroke
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// Ends exactly at the end point
+    #[default]
+    Butt,
+    /// Extends half the width past the end point
+    Square,
+    Round,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrokeOptions {
+    /// Width in pixels
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Longest a miter can be as a multiple of the width, as in SVG
+    pub miter_limit: f32,
+    /// Alternating lengths of dashes and gaps, empty for a solid line.
+    /// A pattern of odd length is repeated twice, as in SVG
+    pub dashes: Vec<f32>,
+    /// Distance into the dash pattern at which the stroke starts
+    pub dash_offset: f32,
+    /// Furthest a flattened curve may be from the real one, in pixels
+    pub tolerance: f32,
+    pub anti_alias: bool,
+}
+
+impl Default for StrokeOptions {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
+            miter_limit: 4.0,
+            dashes: Vec::new(),
+            dash_offset: 0.0,
+            tolerance: 0.25,
+            anti_alias: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GeometryVertex {
+    pub position: [f32; 2],
+    /// 1 inside the shape, falling to 0 across the anti-aliased fringe
+    pub coverage: f32,
+}
+
+/// An indexed triangle list
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Geometry {
+    pub vertices: Vec<GeometryVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// The area covered by the triangles, each weighted by the average coverage of its corners
+    pub fn area(&self) -> f32 {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] =
+                    [triangle[0], triangle[1], triangle[2]].map(|i| self.vertices[i as usize]);
+                let ab = Vector2::from(b.position) - Vector2::from(a.position);
+                let ac = Vector2::from(c.position) - Vector2::from(a.position);
+                ab.perp_dot(ac).abs() * 0.5 * (a.coverage + b.coverage + c.coverage) / 3.0
+            })
+            .sum()
+    }
+
+    fn push_polygon(&mut self, corners: &[(Point2<f32>, f32)]) {
+        let base = self.vertices.len() as u32;
+        self.vertices
+            .extend(corners.iter().map(|&(position, coverage)| GeometryVertex {
+                position: position.into(),
+                coverage,
+            }));
+        // A fan, the polygons pushed are all convex
+        for i in 1..corners.len().saturating_sub(1) as u32 {
+            self.indices.extend([base, base + i, base + i + 1]);
+        }
+    }
+}
+
+/// Triangles covering the inside of every sub path, open sub paths are closed
+pub fn fill(path: &Path, options: &FillOptions) -> Geometry {
+    let polygons: Vec<Vec<Point2<f32>>> = path
+        .flatten(options.tolerance)
+        .into_iter()
+        .map(|polyline| polyline.points)
+        .collect();
+    fill_polygons(&polygons, options.rule, options.anti_alias)
+}
+
+/// Triangles covering a line of the given width along every sub path
+pub fn stroke(path: &Path, options: &StrokeOptions) -> Geometry {
+    if options.width <= 0.0 {
+        return Geometry::default();
+    }
+    let mut polygons = Vec::new();
+    for polyline in path.flatten(options.tolerance) {
+        let polyline = remove_duplicates(polyline);
+        for piece in dash(&polyline, &options.dashes, options.dash_offset) {
+            stroke_polyline(&piece, options, &mut polygons);
+        }
+    }
+    fill_polygons(&polygons, FillRule::NonZero, options.anti_alias)
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Edge {
+    top: Point2<f32>,
+    bottom: Point2<f32>,
+    // +1 for edges going down the screen, -1 for edges going up
+    winding: i32,
+}
+
+impl Edge {
+    fn x_at(&self, y: f32) -> f32 {
+        let t = (y - self.top.y) / (self.bottom.y - self.top.y);
+        self.top.x + (self.bottom.x - self.top.x) * t
+    }
+}
+
+/// Triangles covering the polygons under `rule`, each polygon is implicitly closed
+pub fn fill_polygons(polygons: &[Vec<Point2<f32>>], rule: FillRule, anti_alias: bool) -> Geometry {
+    let mut edges: Vec<Edge> = polygon_edges(polygons)
+        .filter(|(a, b)| a.y != b.y)
+        .map(|(a, b)| {
+            if a.y < b.y {
+                Edge {
+                    top: a,
+                    bottom: b,
+                    winding: 1,
+                }
+            } else {
+                Edge {
+                    top: b,
+                    bottom: a,
+                    winding: -1,
+                }
+            }
+        })
+        .collect();
+    // Both sweeps below take edges in order of their tops
+    edges.sort_by(|a, b| a.top.y.total_cmp(&b.top.y));
+
+    // Band boundaries at every vertex and every crossing. Only edges that overlap vertically can
+    // cross, so each edge is checked against those still active when it starts
+    let mut ys: Vec<f32> = edges.iter().flat_map(|e| [e.top.y, e.bottom.y]).collect();
+    let mut active: Vec<&Edge> = Vec::new();
+    for edge in &edges {
+        active.retain(|other| other.bottom.y > edge.top.y);
+        ys.extend(active.iter().filter_map(|other| crossing_y(other, edge)));
+        active.push(edge);
+    }
+    ys.sort_by(f32::total_cmp);
+    ys.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+
+    let mut geometry = Geometry::default();
+    let mut crossings: Vec<(f32, f32, f32, i32)> = Vec::new();
+    let mut active: Vec<&Edge> = Vec::new();
+    let mut next_edge = 0;
+    for band in ys.windows(2) {
+        let (y0, y1) = (band[0], band[1]);
+        let middle = (y0 + y1) * 0.5;
+        while next_edge < edges.len() && edges[next_edge].top.y < middle {
+            active.push(&edges[next_edge]);
+            next_edge += 1;
+        }
+        active.retain(|e| e.bottom.y > middle);
+        crossings.clear();
+        crossings.extend(
+            active
+                .iter()
+                .map(|e| (e.x_at(middle), e.x_at(y0), e.x_at(y1), e.winding)),
+        );
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        let mut left = None;
+        for &(_, x0, x1, edge_winding) in &crossings {
+            let was_inside = rule.is_inside(winding);
+            winding += edge_winding;
+            match (was_inside, rule.is_inside(winding)) {
+                (false, true) => left = Some((x0, x1)),
+                (true, false) => {
+                    if let Some((left0, left1)) = left.take() {
+                        geometry.push_polygon(&[
+                            (Point2::new(left0, y0), 1.0),
+                            (Point2::new(x0, y0), 1.0),
+                            (Point2::new(x1, y1), 1.0),
+                            (Point2::new(left1, y1), 1.0),
+                        ]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if anti_alias {
+        push_fringe(&mut geometry, polygons, rule);
+    }
+    geometry
+}
+
+/// Fades out from every edge that has the fill on one side only
+fn push_fringe(geometry: &mut Geometry, polygons: &[Vec<Point2<f32>>], rule: FillRule) {
+    let buckets = EdgeBuckets::new(polygons);
+    for polygon in polygons {
+        let count = polygon.len();
+        if count < 2 {
+            continue;
+        }
+        // The direction away from the fill for each edge, None if it is not on the boundary
+        let normals: Vec<Option<Vector2<f32>>> = (0..count)
+            .map(|i| {
+                let (a, b) = (polygon[i], polygon[(i + 1) % count]);
+                let direction = b - a;
+                if direction.magnitude2() < EPSILON * EPSILON {
+                    return None;
+                }
+                let normal = Vector2::new(direction.y, -direction.x).normalize();
+                let middle = a.midpoint(b);
+                let inside = |point: Point2<f32>| rule.is_inside(buckets.winding(point));
+                match (
+                    inside(middle - normal * PROBE),
+                    inside(middle + normal * PROBE),
+                ) {
+                    (true, false) => Some(normal),
+                    (false, true) => Some(-normal),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        for i in 0..count {
+            let (a, b) = (polygon[i], polygon[(i + 1) % count]);
+            if let Some(normal) = normals[i] {
+                let offset = normal * AA_WIDTH;
+                geometry.push_polygon(&[(a, 1.0), (b, 1.0), (b + offset, 0.0), (a + offset, 0.0)]);
+            }
+            // Fill the gap between the fringes where the outline turns away from the fill
+            let next = (i + 1) % count;
+            if let (Some(normal), Some(next_normal)) = (normals[i], normals[next]) {
+                let outgoing = polygon[(next + 1) % count] - b;
+                if outgoing.dot(normal) < 0.0 {
+                    geometry.push_polygon(&[
+                        (b, 1.0),
+                        (b + normal * AA_WIDTH, 0.0),
+                        (b + next_normal * AA_WIDTH, 0.0),
+                    ]);
+                }
+            }
+        }
+    }
+}
+
+/// Each pair of consecutive points, including the last back to the first
+fn polygon_edges(
+    polygons: &[Vec<Point2<f32>>],
+) -> impl Iterator<Item = (Point2<f32>, Point2<f32>)> + '_ {
+    polygons
+        .iter()
+        .filter(|polygon| polygon.len() > 1)
+        .flat_map(|polygon| {
+            (0..polygon.len()).map(move |i| (polygon[i], polygon[(i + 1) % polygon.len()]))
+        })
+}
+
+/// The edges of some polygons sorted into horizontal strips, so finding the winding number at a
+/// point only looks at the edges near its height instead of all of them
+struct EdgeBuckets {
+    top: f32,
+    height: f32,
+    buckets: Vec<Vec<(Point2<f32>, Point2<f32>)>>,
+}
+
+impl EdgeBuckets {
+    /// At most this many strips, an edge is stored once for every strip it passes through
+    const MAX_BUCKETS: usize = 1024;
+
+    fn new(polygons: &[Vec<Point2<f32>>]) -> Self {
+        // Horizontal edges never change the winding
+        let edges: Vec<_> = polygon_edges(polygons)
+            .filter(|(a, b)| a.y != b.y)
+            .collect();
+        let top = edges
+            .iter()
+            .map(|(a, b)| a.y.min(b.y))
+            .fold(f32::INFINITY, f32::min);
+        let bottom = edges
+            .iter()
+            .map(|(a, b)| a.y.max(b.y))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let count = edges.len().clamp(1, Self::MAX_BUCKETS);
+        let mut buckets = Self {
+            top,
+            height: ((bottom - top) / count as f32).max(EPSILON),
+            buckets: vec![Vec::new(); count],
+        };
+        for (a, b) in edges {
+            let (first, last) = (buckets.index(a.y.min(b.y)), buckets.index(a.y.max(b.y)));
+            for bucket in &mut buckets.buckets[first..=last] {
+                bucket.push((a, b));
+            }
+        }
+        buckets
+    }
+
+    fn index(&self, y: f32) -> usize {
+        let index = ((y - self.top) / self.height).floor();
+        (index.max(0.0) as usize).min(self.buckets.len() - 1)
+    }
+
+    /// The winding number of a point, counting edges crossed by a ray to its right
+    fn winding(&self, point: Point2<f32>) -> i32 {
+        let mut winding = 0;
+        for &(a, b) in &self.buckets[self.index(point.y)] {
+            let side = (b - a).perp_dot(point - a);
+            if a.y <= point.y && b.y > point.y && side > 0.0 {
+                winding += 1;
+            } else if b.y <= point.y && a.y > point.y && side < 0.0 {
+                winding -= 1;
+            }
+        }
+        winding
+    }
+}
+
+/// Where two edges cross strictly between their ends
+fn crossing_y(a: &Edge, b: &Edge) -> Option<f32> {
+    if a.bottom.y <= b.top.y || b.bottom.y <= a.top.y {
+        return None;
+    }
+    let da = a.bottom - a.top;
+    let db = b.bottom - b.top;
+    let denominator = da.perp_dot(db);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let offset = b.top - a.top;
+    let t = offset.perp_dot(db) / denominator;
+    let u = offset.perp_dot(da) / denominator;
+    (t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0).then_some(a.top.y + da.y * t)
+}
+
+/// Drops points that repeat the previous one, including a closing point that repeats the first
+fn remove_duplicates(mut polyline: Polyline) -> Polyline {
+    polyline
+        .points
+        .dedup_by(|b, a| (*b - *a).magnitude2() < EPSILON * EPSILON);
+    if polyline.closed && polyline.points.len() > 2 {
+        let (first, last) = (
+            polyline.points[0],
+            polyline.points[polyline.points.len() - 1],
+        );
+        if (last - first).magnitude2() < EPSILON * EPSILON {
+            polyline.points.pop();
+        }
+    }
+    polyline
+}
+
+/// The pieces of a polyline that fall on dashes of the pattern
+fn dash(polyline: &Polyline, pattern: &[f32], offset: f32) -> Vec<Polyline> {
+    let total: f32 = pattern.iter().sum();
+    if pattern.is_empty() || total <= 0.0 || pattern.iter().any(|length| *length < 0.0) {
+        return vec![polyline.clone()];
+    }
+    let pattern = if pattern.len() % 2 == 1 {
+        pattern.repeat(2)
+    } else {
+        pattern.to_vec()
+    };
+    let total: f32 = pattern.iter().sum();
+
+    let mut points = polyline.points.clone();
+    if polyline.closed {
+        points.push(points[0]);
+    }
+
+    // Find where in the pattern the stroke starts
+    let mut index = 0;
+    let mut position = offset.rem_euclid(total);
+    while position >= pattern[index] {
+        position -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    let mut remaining = pattern[index] - position;
+    let mut on = index % 2 == 0;
+
+    let mut pieces = Vec::new();
+    let mut current = if on { vec![points[0]] } else { Vec::new() };
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = (b - a).magnitude();
+        if length <= 0.0 {
+            continue;
+        }
+        let direction = (b - a) / length;
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            let split = a + direction * travelled;
+            if on {
+                current.push(split);
+                pieces.push(Polyline {
+                    points: std::mem::take(&mut current),
+                    closed: false,
+                });
+            } else {
+                current = vec![split];
+            }
+            on = !on;
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= length - travelled;
+        if on {
+            current.push(b);
+        }
+    }
+    if on && current.len() > 1 {
+        pieces.push(Polyline {
+            points: current,
+            closed: false,
+        });
+    }
+    pieces
+}
+
+/// Polygons for the segments, joins and caps of one polyline
+fn stroke_polyline(
+    polyline: &Polyline,
+    options: &StrokeOptions,
+    polygons: &mut Vec<Vec<Point2<f32>>>,
+) {
+    let points = &polyline.points;
+    let count = points.len();
+    if count < 2 {
+        return;
+    }
+    let half_width = options.width * 0.5;
+    let closed = polyline.closed && count > 2;
+
+    let segments = if closed { count } else { count - 1 };
+    for i in 0..segments {
+        let (a, b) = (points[i], points[(i + 1) % count]);
+        let normal = perpendicular(b - a) * half_width;
+        push_stroke_polygon(
+            polygons,
+            vec![a + normal, b + normal, b - normal, a - normal],
+        );
+    }
+
+    let joints = if closed { 0..count } else { 1..count - 1 };
+    for i in joints {
+        let previous = points[(i + count - 1) % count];
+        let next = points[(i + 1) % count];
+        stroke_join(previous, points[i], next, half_width, options, polygons);
+    }
+
+    if !closed {
+        stroke_cap(points[1], points[0], half_width, options, polygons);
+        stroke_cap(
+            points[count - 2],
+            points[count - 1],
+            half_width,
+            options,
+            polygons,
+        );
+    }
+}
+
+fn stroke_join(
+    previous: Point2<f32>,
+    point: Point2<f32>,
+    next: Point2<f32>,
+    half_width: f32,
+    options: &StrokeOptions,
+    polygons: &mut Vec<Vec<Point2<f32>>>,
+) {
+    let incoming = (point - previous).normalize();
+    let outgoing = (next - point).normalize();
+    let turn = incoming.perp_dot(outgoing);
+    if turn.abs() < 1e-6 && incoming.dot(outgoing) > 0.0 {
+        // Straight on, the segments already meet
+        return;
+    }
+    // Normals on the outside of the turn
+    let mut outer_in = perpendicular(incoming);
+    if outer_in.dot(outgoing) > 0.0 {
+        outer_in = -outer_in;
+    }
+    let mut outer_out = perpendicular(outgoing);
+    if outer_out.dot(incoming) < 0.0 {
+        outer_out = -outer_out;
+    }
+    let start = point + outer_in * half_width;
+    let end = point + outer_out * half_width;
+
+    match options.join {
+        LineJoin::Bevel => push_stroke_polygon(polygons, vec![point, start, end]),
+        LineJoin::Miter => {
+            let bisector = outer_in + outer_out;
+            let cos_half_angle = if bisector.magnitude2() > EPSILON {
+                bisector.normalize().dot(outer_in)
+            } else {
+                0.0
+            };
+            // The miter is 1 / sin(angle / 2) times the width, where angle is between the segments
+            if cos_half_angle > 0.0 && 1.0 / cos_half_angle <= options.miter_limit {
+                let tip = point + bisector.normalize() * (half_width / cos_half_angle);
+                push_stroke_polygon(polygons, vec![point, start, tip, end]);
+            } else {
+                push_stroke_polygon(polygons, vec![point, start, end]);
+            }
+        }
+        LineJoin::Round => {
+            let start_angle = outer_in.y.atan2(outer_in.x);
+            let sweep = outer_in.perp_dot(outer_out).atan2(outer_in.dot(outer_out));
+            let mut polygon = vec![point];
+            polygon.extend(arc_points(
+                point,
+                half_width,
+                start_angle,
+                sweep,
+                options.tolerance,
+            ));
+            push_stroke_polygon(polygons, polygon);
+        }
+    }
+}
+
+/// The cap at `end` of a stroke arriving from `from`
+fn stroke_cap(
+    from: Point2<f32>,
+    end: Point2<f32>,
+    half_width: f32,
+    options: &StrokeOptions,
+    polygons: &mut Vec<Vec<Point2<f32>>>,
+) {
+    let direction = (end - from).normalize();
+    let normal = perpendicular(direction) * half_width;
+    match options.cap {
+        LineCap::Butt => {}
+        LineCap::Square => {
+            let extension = direction * half_width;
+            push_stroke_polygon(
+                polygons,
+                vec![
+                    end + normal,
+                    end + normal + extension,
+                    end - normal + extension,
+                    end - normal,
+                ],
+            );
+        }
+        LineCap::Round => {
+            let start_angle = normal.y.atan2(normal.x);
+            // Half a turn from one side of the stroke to the other, through the direction
+            let polygon = arc_points(
+                end,
+                half_width,
+                start_angle,
+                -std::f32::consts::PI,
+                options.tolerance,
+            );
+            push_stroke_polygon(polygons, polygon);
+        }
+    }
+}
+
+/// Adds a polygon wound the same way as every other, so the union can be filled non-zero
+fn push_stroke_polygon(polygons: &mut Vec<Vec<Point2<f32>>>, mut polygon: Vec<Point2<f32>>) {
+    let area = signed_area(&polygon);
+    if area.abs() < EPSILON {
+        return;
+    }
+    if area < 0.0 {
+        polygon.reverse();
+    }
+    polygons.push(polygon);
+}
+
+/// Twice the signed area, positive for polygons wound clockwise on screen
+fn signed_area(polygon: &[Point2<f32>]) -> f32 {
+    (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            a.to_vec().perp_dot(b.to_vec())
+        })
+        .sum()
+}
+
+/// The unit vector a quarter turn from `direction`
+fn perpendicular(direction: Vector2<f32>) -> Vector2<f32> {
+    Vector2::new(-direction.y, direction.x).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> Vec<Point2<f32>> {
+        vec![
+            Point2::new(x, y),
+            Point2::new(x + size, y),
+            Point2::new(x + size, y + size),
+            Point2::new(x, y + size),
+        ]
+    }
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    fn stroke_area(points: &[(f32, f32)], options: StrokeOptions) -> f32 {
+        let mut path = Path::new();
+        path.move_to(points[0].into());
+        for &point in &points[1..] {
+            path.line_to(point.into());
+        }
+        stroke(
+            &path,
+            &StrokeOptions {
+                anti_alias: false,
+                ..options
+            },
+        )
+        .area()
+    }
+
+    #[test]
+    fn overlapping_squares_by_rule() {
+        let polygons = [square(0.0, 0.0, 10.0), square(5.0, 0.0, 10.0)];
+        let non_zero = fill_polygons(&polygons, FillRule::NonZero, false);
+        let even_odd = fill_polygons(&polygons, FillRule::EvenOdd, false);
+        assert!(close(non_zero.area(), 150.0, 1e-3));
+        assert!(close(even_odd.area(), 100.0, 1e-3));
+    }
+
+    #[test]
+    fn holes_by_winding() {
+        let mut inner = square(2.0, 2.0, 6.0);
+        let polygons = [square(0.0, 0.0, 10.0), inner.clone()];
+        assert!(close(
+            fill_polygons(&polygons, FillRule::NonZero, false).area(),
+            100.0,
+            1e-3
+        ));
+        assert!(close(
+            fill_polygons(&polygons, FillRule::EvenOdd, false).area(),
+            64.0,
+            1e-3
+        ));
+        // Wound the other way the inner square is a hole under either rule
+        inner.reverse();
+        let polygons = [square(0.0, 0.0, 10.0), inner];
+        assert!(close(
+            fill_polygons(&polygons, FillRule::NonZero, false).area(),
+            64.0,
+            1e-3
+        ));
+    }
+
+    #[test]
+    fn crossing_edges_split_bands() {
+        // A bow tie, its two triangles meet where the edges cross
+        let polygons = [vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(10.0, 10.0),
+            Point2::new(10.0, 0.0),
+            Point2::new(0.0, 10.0),
+        ]];
+        let geometry = fill_polygons(&polygons, FillRule::NonZero, false);
+        assert!(close(geometry.area(), 50.0, 1e-3));
+    }
+
+    #[test]
+    fn anti_aliasing_adds_fringe_outside() {
+        let polygons = [square(0.0, 0.0, 10.0)];
+        let geometry = fill_polygons(&polygons, FillRule::NonZero, true);
+        // Half of every fringe quad counts, plus the corner triangles
+        let fringe = geometry.area() - 100.0;
+        assert!(fringe > 4.0 * 10.0 * AA_WIDTH * 0.5 - 1e-3);
+        assert!(geometry
+            .vertices
+            .iter()
+            .filter(|v| v.coverage == 0.0)
+            .all(|v| {
+                let [x, y] = v.position;
+                !(0.0..=10.0).contains(&x) || !(0.0..=10.0).contains(&y)
+            }));
+        // Edges shared by two touching squares are inside, so get no fringe between them
+        let touching = [square(0.0, 0.0, 10.0), square(10.0, 0.0, 10.0)];
+        let geometry = fill_polygons(&touching, FillRule::NonZero, true);
+        assert!(geometry
+            .vertices
+            .iter()
+            .filter(|v| v.coverage == 0.0)
+            .all(|v| v.position[0] <= 0.0
+                || v.position[0] >= 20.0
+                || v.position[1] <= 0.0
+                || v.position[1] >= 10.0));
+    }
+
+    #[test]
+    fn caps_extend_the_stroke() {
+        let line = [(0.0, 0.0), (10.0, 0.0)];
+        let stroke_with = |cap| {
+            stroke_area(
+                &line,
+                StrokeOptions {
+                    width: 2.0,
+                    cap,
+                    tolerance: 0.01,
+                    ..StrokeOptions::default()
+                },
+            )
+        };
+        assert!(close(stroke_with(LineCap::Butt), 20.0, 1e-3));
+        assert!(close(stroke_with(LineCap::Square), 24.0, 1e-3));
+        assert!(close(
+            stroke_with(LineCap::Round),
+            20.0 + std::f32::consts::PI,
+            0.05
+        ));
+    }
+
+    #[test]
+    fn joins_fill_the_outer_corner() {
+        let corner = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let stroke_with = |join, miter_limit| {
+            stroke_area(
+                &corner,
+                StrokeOptions {
+                    width: 2.0,
+                    join,
+                    miter_limit,
+                    tolerance: 0.01,
+                    ..StrokeOptions::default()
+                },
+            )
+        };
+        assert!(close(stroke_with(LineJoin::Miter, 4.0), 40.0, 1e-3));
+        assert!(close(stroke_with(LineJoin::Bevel, 4.0), 39.5, 1e-3));
+        // A right angle has a miter of sqrt(2) times the width
+        assert!(close(stroke_with(LineJoin::Miter, 1.4), 39.5, 1e-3));
+        assert!(close(
+            stroke_with(LineJoin::Round, 4.0),
+            39.0 + std::f32::consts::FRAC_PI_4,
+            0.02
+        ));
+    }
+
+    #[test]
+    fn dashes_split_the_line() {
+        let line = Polyline {
+            points: vec![Point2::new(0.0, 0.0), Point2::new(10.0, 0.0)],
+            closed: false,
+        };
+        let spans = |pattern: &[f32], offset| -> Vec<(f32, f32)> {
+            dash(&line, pattern, offset)
+                .iter()
+                .map(|piece| (piece.points[0].x, piece.points.last().unwrap().x))
+                .collect()
+        };
+        assert_eq!(spans(&[2.0, 3.0], 0.0), [(0.0, 2.0), (5.0, 7.0)]);
+        // An odd pattern is repeated, so dashes and gaps swap each time round
+        assert_eq!(spans(&[2.0], 0.0), [(0.0, 2.0), (4.0, 6.0), (8.0, 10.0)]);
+        assert_eq!(
+            spans(&[2.0, 3.0], 1.0),
+            [(0.0, 1.0), (4.0, 6.0), (9.0, 10.0)]
+        );
+        // Patterns that cannot be drawn leave the line solid
+        assert_eq!(spans(&[], 0.0), [(0.0, 10.0)]);
+        assert_eq!(spans(&[0.0, 0.0], 0.0), [(0.0, 10.0)]);
+        assert_eq!(spans(&[1.0, -1.0], 0.0), [(0.0, 10.0)]);
+    }
+
+    #[test]
+    fn dashes_follow_corners() {
+        let corner = Polyline {
+            points: vec![
+                Point2::new(0.0, 0.0),
+                Point2::new(4.0, 0.0),
+                Point2::new(4.0, 4.0),
+            ],
+            closed: false,
+        };
+        let pieces = dash(&corner, &[6.0, 1.0], 0.0);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(
+            pieces[0].points,
+            [
+                Point2::new(0.0, 0.0),
+                Point2::new(4.0, 0.0),
+                Point2::new(4.0, 2.0)
+            ]
+        );
+        assert_eq!(
+            pieces[1].points,
+            [Point2::new(4.0, 3.0), Point2::new(4.0, 4.0)]
+        );
+    }
+
+    #[test]
+    fn empty_input_gives_empty_geometry() {
+        assert!(fill_polygons(&[], FillRule::NonZero, true).is_empty());
+        assert!(stroke(
+            &Path::rect(Point2::new(0.0, 0.0), Vector2::new(5.0, 5.0)),
+            &StrokeOptions {
+                width: 0.0,
+                ..StrokeOptions::default()
+            }
+        )
+        .is_empty());
+    }
+}
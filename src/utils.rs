@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+#[cfg(windows)]
 use windows::core::PCWSTR;
 
 /// Converts rgb values into a single unsigned 32bit integer
@@ -24,6 +25,7 @@ pub fn str_to_utf16(s: &str) -> Vec<u16> {
 }
 
 /// Creates a wide-pointer-string
+#[cfg(windows)]
 #[inline]
 pub fn str_to_pcwstr(s: &str) -> PCWSTR {
     PCWSTR(str_to_utf16(s).as_mut_ptr())
@@ -56,3 +58,85 @@ pub fn GET_X_LPARAM(dword: u32) -> i32 {
 pub fn GET_Y_LPARAM(dword: u32) -> i32 {
     HIWORD(dword as u32) as i16 as i32
 }
+
+/// A little endian `u16` at `offset`, None past the end of `bytes`
+pub fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// A little endian `u32` at `offset`, None past the end of `bytes`
+pub fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// A little endian `i32` at `offset`, None past the end of `bytes`
+pub fn read_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    read_u32(bytes, offset).map(|value| value as i32)
+}
+
+/// A little endian pointer sized integer at `offset`, None past the end of `bytes`
+pub fn read_isize(bytes: &[u8], offset: usize) -> Option<isize> {
+    const SIZE: usize = std::mem::size_of::<isize>();
+    let bytes = bytes.get(offset..offset.checked_add(SIZE)?)?;
+    Some(isize::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// The map futures of wgpu 0.12 are completed by `Device::poll` rather than by waking, so they
+/// are polled with a waker that does nothing
+pub fn noop_waker() -> std::task::Waker {
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
+
+/// Creates a single mip 2D texture from tightly packed 8 bit rgba data
+pub fn create_rgba_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    dimensions: (u32, u32),
+    rgba_data: &[u8],
+    format: wgpu::TextureFormat,
+    label: Option<&str>,
+) -> wgpu::Texture {
+    let texture_size = wgpu::Extent3d {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth_or_array_layers: 1,
+    };
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label,
+        size: texture_size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba_data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
+            rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+        },
+        texture_size,
+    );
+
+    texture
+}
@@ -0,0 +1,245 @@
+#![allow(dead_code)]
+//! Filled and stroked 2D paths drawn over the final image, for charts and diagrams.
+//! Shapes are tessellated once when added and kept until cleared, the vertex and index buffers
+//! are only rewritten after a change.
+
+use crate::path::Path;
+use crate::tessellation::{fill, stroke, FillOptions, Geometry, StrokeOptions};
+use wgpu::include_wgsl;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VectorVertex {
+    position: [f32; 2],
+    colour: [f32; 4],
+}
+
+impl VectorVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    padding: [f32; 2],
+}
+
+pub struct VectorLayer {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    index_capacity: usize,
+    // Every shape so far, in drawing order
+    vertices: Vec<VectorVertex>,
+    indices: Vec<u32>,
+    // Set when the shapes have changed since the buffers were written
+    changed: bool,
+}
+
+impl VectorLayer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, size: (u32, u32)) -> Self {
+        let shader = device.create_shader_module(&include_wgsl!("shaders/vector.wgsl"));
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("vector_bind_group_layout"),
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vector Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[screen_uniform(size)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("vector_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Vector Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Vector Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VectorVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            // Tessellated triangles are wound either way
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let (vertex_capacity, index_capacity) = (1024, 4096);
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            vertex_buffer: create_vertex_buffer(device, vertex_capacity),
+            index_buffer: create_index_buffer(device, index_capacity),
+            vertex_capacity,
+            index_capacity,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            changed: false,
+        }
+    }
+
+    /// Adds the inside of a path, drawn over the shapes already added
+    pub fn fill(&mut self, path: &Path, options: &FillOptions, colour: [f32; 4]) {
+        self.add(&fill(path, options), colour);
+    }
+
+    /// Adds a line along a path, drawn over the shapes already added
+    pub fn stroke(&mut self, path: &Path, options: &StrokeOptions, colour: [f32; 4]) {
+        self.add(&stroke(path, options), colour);
+    }
+
+    /// Adds triangles that have already been tessellated
+    pub fn add(&mut self, geometry: &Geometry, colour: [f32; 4]) {
+        if geometry.is_empty() {
+            return;
+        }
+        let base = self.vertices.len() as u32;
+        self.vertices
+            .extend(geometry.vertices.iter().map(|vertex| VectorVertex {
+                position: vertex.position,
+                colour: [colour[0], colour[1], colour[2], colour[3] * vertex.coverage],
+            }));
+        self.indices
+            .extend(geometry.indices.iter().map(|index| base + index));
+        self.changed = true;
+    }
+
+    /// Removes every shape
+    pub fn clear(&mut self) {
+        self.changed |= !self.indices.is_empty();
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, size: (u32, u32)) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[screen_uniform(size)]),
+        );
+    }
+
+    /// Draws the shapes over whatever is in `view`
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        if self.indices.is_empty() {
+            return;
+        }
+        if self.changed {
+            self.upload(device, queue);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Vector Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.indices.len() as u32, 0, 0..1);
+    }
+
+    /// Writes the shapes to the buffers, growing them if they are too small
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(device, self.vertex_capacity);
+        }
+        if self.indices.len() > self.index_capacity {
+            self.index_capacity = self.indices.len().next_power_of_two();
+            self.index_buffer = create_index_buffer(device, self.index_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&self.indices));
+        self.changed = false;
+    }
+}
+
+fn screen_uniform(size: (u32, u32)) -> ScreenUniform {
+    ScreenUniform {
+        size: [size.0.max(1) as f32, size.1.max(1) as f32],
+        padding: [0.0; 2],
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Vector Vertex Buffer"),
+        size: (capacity * std::mem::size_of::<VectorVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_index_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Vector Index Buffer"),
+        size: (capacity * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -3,15 +3,47 @@
 //! documentation and windows-rs docs.
 
 use std::ffi::c_void;
+use std::path::PathBuf;
 use windows::core::*;
 
 use windows::Win32::{
     Foundation::*,
-    Graphics::Dwm::DwmExtendFrameIntoClientArea,
+    Graphics::Dwm::{
+        DwmEnableBlurBehindWindow, DwmExtendFrameIntoClientArea, DWM_BB_BLURREGION, DWM_BB_ENABLE,
+        DWM_BLURBEHIND,
+    },
     Graphics::Gdi::ValidateRect,
     Graphics::Gdi::*,
+    Media::{timeBeginPeriod, timeEndPeriod, TIMERR_NOERROR},
+    System::Com::{IDataObject, DVASPECT_CONTENT, FORMATETC, TYMED_HGLOBAL},
+    System::DataExchange::{
+        AddClipboardFormatListener, CloseClipboard, EmptyClipboard, GetClipboardData,
+        IsClipboardFormatAvailable, OpenClipboard, RegisterClipboardFormatW,
+        RemoveClipboardFormatListener, SetClipboardData,
+    },
     System::LibraryLoader::GetModuleHandleW,
-    UI::Controls::{GetThemeSysSize, MARGINS},
+    System::Memory::{
+        GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+    },
+    System::Ole::{
+        IDropTarget, OleInitialize, OleUninitialize, RegisterDragDrop, ReleaseStgMedium,
+        RevokeDragDrop,
+    },
+    System::SystemServices::CF_HDROP,
+    UI::Controls::MARGINS,
+    UI::HiDpi::{
+        GetDpiForMonitor, GetDpiForWindow, GetSystemMetricsForDpi, SetProcessDpiAwarenessContext,
+        DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, MDT_EFFECTIVE_DPI,
+    },
+    UI::Input::{
+        GetRawInputData, GetRawInputDeviceInfoW, GetRawInputDeviceList, RegisterRawInputDevices,
+        HRAWINPUT, RAWINPUTDEVICE, RAWINPUTDEVICELIST, RAWINPUTHEADER, RIDEV_DEVNOTIFY,
+        RIDEV_REMOVE, RIDI_DEVICENAME, RID_INPUT,
+    },
+    UI::Shell::{
+        DragAcceptFiles, DragFinish, DragQueryFileW, DragQueryPoint, SHAppBarMessage,
+        ABM_GETAUTOHIDEBAREX, ABM_GETSTATE, ABS_AUTOHIDE, APPBARDATA, HDROP,
+    },
     UI::WindowsAndMessaging::*,
     // Foundation::LRESULT
 };
@@ -158,24 +190,87 @@ pub fn get_window_rect(hwnd: HWND) -> Result<RECT> {
     Ok(rect)
 }
 
+/// The client area, whose top left is always 0, 0
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getclientrect
+pub fn get_client_rect(hwnd: HWND) -> Result<RECT> {
+    let mut rect = RECT::default();
+    unsafe { GetClientRect(hwnd, &mut rect).ok()? };
+    Ok(rect)
+}
+
+/// Converts a point in client coordinates to screen coordinates
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-clienttoscreen
+pub fn client_to_screen(hwnd: HWND, point: POINT) -> Result<POINT> {
+    let mut point = point;
+    unsafe { ClientToScreen(hwnd, &mut point).ok()? };
+    Ok(point)
+}
+
+/// Converts a point in screen coordinates to client coordinates
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-screentoclient
+pub fn screen_to_client(hwnd: HWND, point: POINT) -> Result<POINT> {
+    let mut point = point;
+    unsafe { ScreenToClient(hwnd, &mut point).ok()? };
+    Ok(point)
+}
+
+/// Titlebar height in pixels for a window at `dpi`
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getsystemmetricsfordpi
 #[inline]
-pub fn get_titlebar_height() -> i32 {
+pub fn get_titlebar_height(dpi: u32) -> i32 {
     unsafe {
-        GetThemeSysSize(0, SM_CYSIZE.0 as i32) + GetThemeSysSize(0, SM_CXPADDEDBORDER.0 as i32) * 2
+        GetSystemMetricsForDpi(SM_CYSIZE.0 as i32, dpi)
+            + GetSystemMetricsForDpi(SM_CXPADDEDBORDER.0 as i32, dpi) * 2
     }
 }
 
-/// Function get get the system border width and height
+/// Function get get the system border width and height for a window at `dpi`
 #[inline]
-pub fn get_border() -> POINT {
+pub fn get_border(dpi: u32) -> POINT {
     unsafe {
+        let padding = GetSystemMetricsForDpi(SM_CXPADDEDBORDER.0 as i32, dpi);
         POINT {
-            x: GetSystemMetrics(SM_CXFRAME) + GetSystemMetrics(SM_CXPADDEDBORDER),
-            y: GetSystemMetrics(SM_CYFRAME) + GetSystemMetrics(SM_CXPADDEDBORDER),
+            x: GetSystemMetricsForDpi(SM_CXFRAME.0 as i32, dpi) + padding,
+            y: GetSystemMetricsForDpi(SM_CYFRAME.0 as i32, dpi) + padding,
         }
     }
 }
 
+/// Makes the process per monitor DPI aware (v2), so windows are told when they move to a
+/// monitor with a different DPI instead of being stretched by the system.
+/// Fails if the awareness was already set, such as by the application manifest.
+/// https://docs.microsoft.com/en-us/windows/win32/hidpi/dpi-awareness-context
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setprocessdpiawarenesscontext
+pub fn enable_per_monitor_dpi_awareness() -> Result<()> {
+    unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2).ok() }
+}
+
+/// The DPI of the monitor a window is on, 96 if the handle is invalid
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getdpiforwindow
+pub fn get_dpi_for_window(handle: HWND) -> u32 {
+    match unsafe { GetDpiForWindow(handle) } {
+        0 => USER_DEFAULT_SCREEN_DPI,
+        dpi => dpi,
+    }
+}
+
+/// Moves and resizes a window to a rect in screen coordinates without changing its z order
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setwindowpos
+pub fn set_window_rect(handle: HWND, rect: &RECT) -> Result<()> {
+    unsafe {
+        SetWindowPos(
+            handle,
+            HWND(0),
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )
+        .ok()
+    }
+}
+
 /// General window style description
 pub enum WindowStyle {
     /// No titlebar, border is overridden by backend
@@ -200,6 +295,71 @@ pub fn extend_frame_into_client_area(handle: HWND, style: &WindowStyle) -> Resul
     unsafe { DwmExtendFrameIntoClientArea(handle, &margins) }
 }
 
+/// Lets DWM compose the window using the alpha channel of its contents instead of treating it as
+/// opaque. Blur behind is enabled with an empty region, which turns on per-pixel alpha without
+/// blurring anything (the blur itself only ever showed on Windows 7).
+/// https://docs.microsoft.com/en-us/windows/win32/api/dwmapi/nf-dwmapi-dwmenableblurbehindwindow
+pub fn enable_per_pixel_alpha(handle: HWND, enable: bool) -> Result<()> {
+    // A region with no area, (0, 0) to (-1, -1)
+    let region = unsafe { CreateRectRgn(0, 0, -1, -1) };
+    if region.is_invalid() {
+        return Err(get_last_error("Failed to create blur region"));
+    }
+    let blur_behind = DWM_BLURBEHIND {
+        dwFlags: DWM_BB_ENABLE | DWM_BB_BLURREGION,
+        fEnable: enable.into(),
+        hRgnBlur: region,
+        fTransitionOnMaximized: false.into(),
+    };
+    let result = unsafe { DwmEnableBlurBehindWindow(handle, &blur_behind) };
+    // DWM keeps its own copy of the region
+    unsafe { DeleteObject(region) };
+    result
+}
+
+/// Makes the whole window ignore the mouse so clicks fall through to whatever is underneath.
+/// Requires the window to be layered, which is made fully opaque so it keeps drawing as before.
+/// https://docs.microsoft.com/en-us/windows/win32/winmsg/extended-window-styles
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setlayeredwindowattributes
+pub fn set_click_through(handle: HWND, click_through: bool) -> Result<()> {
+    let flags = (WS_EX_LAYERED.0 | WS_EX_TRANSPARENT.0) as isize;
+    unsafe {
+        let style = GetWindowLongPtrW(handle, GWL_EXSTYLE);
+        let style = if click_through {
+            style | flags
+        } else {
+            style & !flags
+        };
+        SetWindowLongPtrW(handle, GWL_EXSTYLE, style);
+        if click_through {
+            SetLayeredWindowAttributes(handle, 0, 255, LWA_ALPHA).ok()?;
+        }
+    }
+    Ok(())
+}
+
+/// The cursor position in screen coordinates
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getcursorpos
+pub fn get_cursor_pos() -> Result<POINT> {
+    let mut point = POINT::default();
+    unsafe { GetCursorPos(&mut point).ok()? };
+    Ok(point)
+}
+
+/// Starts or resets a timer that posts WM_TIMER with `id` as the wparam
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-settimer
+pub fn set_timer(handle: HWND, id: usize, period_ms: u32) -> Result<()> {
+    if unsafe { SetTimer(handle, id, period_ms, None) } == 0 {
+        return Err(get_last_error("Failed to set timer"));
+    }
+    Ok(())
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-killtimer
+pub fn kill_timer(handle: HWND, id: usize) -> Result<()> {
+    unsafe { KillTimer(handle, id).ok() }
+}
+
 /// post a quit message with an exit code.
 /// Simply Requests that the thread closes
 /// Convention states to post 0 to indicate success.
@@ -213,6 +373,47 @@ pub fn post_quit_message(nexitcode: i32) {
     unsafe { PostQuitMessage(nexitcode) };
 }
 
+/// Blocks until a message is retrieved from the calling thread's queue.
+/// Returns false once WM_QUIT is retrieved.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getmessagew
+pub fn get_message(message: &mut MSG) -> bool {
+    unsafe { GetMessageW(message, HWND(0), 0, 0).into() }
+}
+
+/// Removes the next message from the queue if there is one, without blocking
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-peekmessagew
+pub fn peek_message(message: &mut MSG) -> bool {
+    unsafe { PeekMessageW(message, HWND(0), 0, 0, PM_REMOVE).into() }
+}
+
+/// Translates virtual key messages into character messages and sends the message to the
+/// window procedure
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-dispatchmessagew
+pub fn dispatch_message(message: &MSG) {
+    unsafe {
+        TranslateMessage(message);
+        DispatchMessageW(message);
+    }
+}
+
+/// Blocks until any input is available in the queue or the timeout has elapsed
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-msgwaitformultipleobjects
+pub fn wait_for_message(timeout: std::time::Duration) {
+    // Round up, waiting for 0ms returns straight away and the loop would spin
+    let ms = timeout.as_micros().div_ceil(1000).min(u32::MAX as u128 - 1) as u32;
+    unsafe { MsgWaitForMultipleObjects(0, std::ptr::null(), false, ms, QS_ALLINPUT) };
+}
+
+/// Adds the entire client area to the update region so that a WM_PAINT message is sent once the
+/// message queue is empty. Any number of calls before then result in a single WM_PAINT.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-invalidaterect
+pub fn invalidate_rect(handle: HWND) -> Result<()> {
+    // Null for the entire client area, false to leave the background alone as the whole area is
+    // drawn over by the swapchain
+    unsafe { InvalidateRect(handle, std::ptr::null(), false).ok()? };
+    Ok(())
+}
+
 /// Used to redraw the window
 /// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-validaterect
 /// https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Graphics/Gdi/fn.ValidateRect.html
@@ -224,3 +425,570 @@ pub fn validate_rect(handle: HWND) -> Result<()> {
     unsafe { ValidateRect(handle, std::ptr::null()).ok()? };
     Ok(())
 }
+
+/// Raises the resolution of the system timer for as long as it lives.
+/// The default resolution of ~15.6ms makes `std::thread::sleep` far too coarse for frame pacing.
+/// https://docs.microsoft.com/en-us/windows/win32/api/timeapi/nf-timeapi-timebeginperiod
+#[derive(Debug)]
+pub struct TimerResolution {
+    period_ms: u32,
+}
+
+impl TimerResolution {
+    /// Requests a timer resolution in milliseconds, returns None if the request was refused
+    pub fn new(period_ms: u32) -> Option<Self> {
+        // Every successful call must be matched by timeEndPeriod with the same value, which the
+        // Drop implementation guarantees
+        if unsafe { timeBeginPeriod(period_ms) } == TIMERR_NOERROR {
+            Some(Self { period_ms })
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for TimerResolution {
+    fn drop(&mut self) {
+        unsafe { timeEndPeriod(self.period_ms) };
+    }
+}
+
+/// Every monitor that is part of the desktop
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-enumdisplaymonitors
+pub fn enum_display_monitors() -> Result<Vec<HMONITOR>> {
+    unsafe extern "system" fn callback(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        data: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(data.0 as *mut Vec<HMONITOR>);
+        monitors.push(monitor);
+        true.into()
+    }
+
+    let mut monitors: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            None,
+            std::ptr::null(),
+            Some(callback),
+            LPARAM(&mut monitors as *mut _ as isize),
+        )
+        .ok()?
+    };
+    Ok(monitors)
+}
+
+/// Bounds, work area, flags and device name of a monitor
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getmonitorinfow
+pub fn get_monitor_info(monitor: HMONITOR) -> Result<MONITORINFOEXW> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    unsafe { GetMonitorInfoW(monitor, &mut info as *mut _ as *mut MONITORINFO).ok()? };
+    Ok(info)
+}
+
+/// The monitor with the largest part of the window, or the nearest if it is on none
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-monitorfromwindow
+pub fn monitor_from_window(handle: HWND) -> HMONITOR {
+    unsafe { MonitorFromWindow(handle, MONITOR_DEFAULTTONEAREST) }
+}
+
+/// The monitor with the largest part of a rectangle, or the nearest if it is on none
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-monitorfromrect
+pub fn monitor_from_rect(rect: &RECT) -> HMONITOR {
+    unsafe { MonitorFromRect(rect, MONITOR_DEFAULTTONEAREST) }
+}
+
+/// The effective DPI of a monitor, 96 if it cannot be read
+/// https://docs.microsoft.com/en-us/windows/win32/api/shellscalingapi/nf-shellscalingapi-getdpiformonitor
+pub fn get_dpi_for_monitor(monitor: HMONITOR) -> u32 {
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    match unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) } {
+        Ok(()) if dpi_x != 0 => dpi_x,
+        _ => USER_DEFAULT_SCREEN_DPI,
+    }
+}
+
+/// A display mode of a display device, `ENUM_CURRENT_SETTINGS` for the one in use.
+/// Modes are numbered from 0 and None is returned past the last one.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-enumdisplaysettingsw
+pub fn enum_display_settings(device: &[u16], mode: ENUM_DISPLAY_SETTINGS_MODE) -> Option<DEVMODEW> {
+    let mut devmode = DEVMODEW {
+        dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+        ..Default::default()
+    };
+    unsafe { EnumDisplaySettingsW(PCWSTR(device.as_ptr()), mode, &mut devmode) }
+        .as_bool()
+        .then_some(devmode)
+}
+
+/// Switches a display device to a mode for as long as the application runs, or back to the
+/// mode saved in the registry when `mode` is None
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-changedisplaysettingsexw
+pub fn change_display_settings(device: &[u16], mode: Option<&DEVMODEW>) -> Result<()> {
+    let (devmode, flags) = match mode {
+        Some(mode) => (mode as *const DEVMODEW, CDS_FULLSCREEN),
+        None => (std::ptr::null(), CDS_TYPE(0)),
+    };
+    let result = unsafe {
+        ChangeDisplaySettingsExW(
+            PCWSTR(device.as_ptr()),
+            devmode,
+            None,
+            flags,
+            std::ptr::null(),
+        )
+    };
+    if result == DISP_CHANGE_SUCCESSFUL {
+        Ok(())
+    } else {
+        Err(Error::new(
+            E_FAIL,
+            format!("Failed to change the display mode ({})", result.0)
+                .as_str()
+                .into(),
+        ))
+    }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/winmsg/window-styles
+pub fn get_window_style(handle: HWND) -> WINDOW_STYLE {
+    WINDOW_STYLE(unsafe { GetWindowLongPtrW(handle, GWL_STYLE) } as u32)
+}
+
+/// Changing the style only takes effect once the frame is recalculated, see `set_window_frame`
+pub fn set_window_style(handle: HWND, style: WINDOW_STYLE) {
+    unsafe { SetWindowLongPtrW(handle, GWL_STYLE, style.0 as isize) };
+}
+
+/// Moves a window to the top of the z order and recalculates its frame after a style change
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setwindowpos
+pub fn set_window_frame(handle: HWND, rect: &RECT) -> Result<()> {
+    unsafe {
+        SetWindowPos(
+            handle,
+            HWND_TOP,
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_FRAMECHANGED | SWP_NOACTIVATE,
+        )
+        .ok()
+    }
+}
+
+/// True if the window is maximized
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-iszoomed
+pub fn is_zoomed(handle: HWND) -> bool {
+    unsafe { IsZoomed(handle).as_bool() }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-showwindow
+pub fn show_window(handle: HWND, command: SHOW_WINDOW_CMD) {
+    unsafe { ShowWindow(handle, command) };
+}
+
+/// True if the window is minimized
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-isiconic
+pub fn is_iconic(handle: HWND) -> bool {
+    unsafe { IsIconic(handle).as_bool() }
+}
+
+/// True if the user set the taskbar to hide automatically
+/// https://docs.microsoft.com/en-us/windows/win32/shell/abm-getstate
+pub fn is_taskbar_autohide() -> bool {
+    let mut data = APPBARDATA {
+        cbSize: std::mem::size_of::<APPBARDATA>() as u32,
+        ..Default::default()
+    };
+    unsafe { SHAppBarMessage(ABM_GETSTATE, &mut data) as u32 & ABS_AUTOHIDE != 0 }
+}
+
+/// True if an auto hidden app bar is on an edge (`ABE_LEFT` etc.) of the monitor at `monitor`
+/// https://docs.microsoft.com/en-us/windows/win32/shell/abm-getautohidebarex
+pub fn has_autohide_bar(edge: u32, monitor: &RECT) -> bool {
+    let mut data = APPBARDATA {
+        cbSize: std::mem::size_of::<APPBARDATA>() as u32,
+        uEdge: edge,
+        rc: *monitor,
+        ..Default::default()
+    };
+    unsafe { SHAppBarMessage(ABM_GETAUTOHIDEBAREX, &mut data) != 0 }
+}
+
+/// One of the system cursors, such as `IDC_IBEAM`
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-loadcursorw
+pub fn system_cursor(name: PCWSTR) -> Result<HCURSOR> {
+    unsafe { LoadCursorW(None, name).ok() }
+}
+
+/// Builds an icon, or a cursor when given a hotspot, from 32 bit BGRA pixels with straight
+/// alpha. The alpha channel makes the AND mask unnecessary, so it is left empty.
+/// Destroy it with `DestroyIcon` (or `DestroyCursor`) once it is no longer used.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-createiconindirect
+pub fn create_icon_from_bgra(
+    width: u32,
+    height: u32,
+    bgra: &[u8],
+    hotspot: Option<(u32, u32)>,
+) -> Result<HICON> {
+    debug_assert_eq!(bgra.len(), (width * height * 4) as usize);
+    // Mask rows are padded to 16 bits
+    let mask = vec![0u8; (width.div_ceil(16) * 2 * height) as usize];
+    unsafe {
+        let colour = CreateBitmap(width as i32, height as i32, 1, 32, bgra.as_ptr() as _);
+        let mask = CreateBitmap(width as i32, height as i32, 1, 1, mask.as_ptr() as _);
+        let (x, y) = hotspot.unwrap_or((0, 0));
+        let info = ICONINFO {
+            fIcon: hotspot.is_none().into(),
+            xHotspot: x,
+            yHotspot: y,
+            hbmMask: mask,
+            hbmColor: colour,
+        };
+        let icon = CreateIconIndirect(&info);
+        // The icon keeps its own copies of the bitmaps
+        DeleteObject(colour);
+        DeleteObject(mask);
+        if icon.is_invalid() {
+            return Err(get_last_error("Failed to create icon"));
+        }
+        Ok(icon)
+    }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-destroycursor
+pub fn destroy_cursor(cursor: HCURSOR) {
+    unsafe { DestroyCursor(cursor) };
+}
+
+/// Sets the cursor shape until the cursor moves, HCURSOR(0) hides it
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setcursor
+pub fn set_cursor(cursor: HCURSOR) {
+    unsafe { SetCursor(cursor) };
+}
+
+/// Moves the cursor to a point in screen coordinates
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setcursorpos
+pub fn set_cursor_pos(point: POINT) -> Result<()> {
+    unsafe { SetCursorPos(point.x, point.y).ok() }
+}
+
+/// Keeps the cursor inside a rect in screen coordinates, or frees it with None.
+/// The confinement is shared by every application, so it has to be released on deactivation.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-clipcursor
+pub fn clip_cursor(rect: Option<&RECT>) -> Result<()> {
+    let rect = rect.map_or(std::ptr::null(), |rect| rect as *const RECT);
+    unsafe { ClipCursor(rect).ok() }
+}
+
+/// HID usage page of generic desktop controls, with the usages for mice and keyboards
+const HID_USAGE_PAGE_GENERIC: u16 = 0x01;
+const HID_USAGE_GENERIC_MOUSE: u16 = 0x02;
+const HID_USAGE_GENERIC_KEYBOARD: u16 = 0x06;
+
+/// Sends WM_INPUT for mice and keyboards to a window while it is in the foreground, and
+/// WM_INPUT_DEVICE_CHANGE as they are connected and disconnected. The legacy mouse and
+/// keyboard messages keep coming as well.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-registerrawinputdevices
+pub fn register_raw_input(handle: HWND, enable: bool) -> Result<()> {
+    let (flags, target) = if enable {
+        (RIDEV_DEVNOTIFY, handle)
+    } else {
+        // Removing requires no target
+        (RIDEV_REMOVE, HWND(0))
+    };
+    let devices =
+        [HID_USAGE_GENERIC_MOUSE, HID_USAGE_GENERIC_KEYBOARD].map(|usage| RAWINPUTDEVICE {
+            usUsagePage: HID_USAGE_PAGE_GENERIC,
+            usUsage: usage,
+            dwFlags: flags,
+            hwndTarget: target,
+        });
+    unsafe {
+        RegisterRawInputDevices(
+            devices.as_ptr(),
+            devices.len() as u32,
+            std::mem::size_of::<RAWINPUTDEVICE>() as u32,
+        )
+        .ok()
+    }
+}
+
+/// The RAWINPUT of a WM_INPUT as bytes
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getrawinputdata
+pub fn get_raw_input_data(input: HRAWINPUT) -> Result<Vec<u8>> {
+    let header_size = std::mem::size_of::<RAWINPUTHEADER>() as u32;
+    let mut size = 0;
+    unsafe {
+        if GetRawInputData(
+            input,
+            RID_INPUT,
+            std::ptr::null_mut(),
+            &mut size,
+            header_size,
+        ) == u32::MAX
+        {
+            return Err(get_last_error("Failed to get the raw input size"));
+        }
+        // Kept 8 byte aligned, as the RAWINPUT it holds would be
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        let written = GetRawInputData(
+            input,
+            RID_INPUT,
+            buffer.as_mut_ptr() as *mut c_void,
+            &mut size,
+            header_size,
+        );
+        if written == u32::MAX {
+            return Err(get_last_error("Failed to get the raw input"));
+        }
+        let mut bytes = bytemuck::cast_slice::<u64, u8>(&buffer).to_vec();
+        bytes.truncate(written as usize);
+        Ok(bytes)
+    }
+}
+
+/// Every raw input device that is connected
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getrawinputdevicelist
+pub fn get_raw_input_device_list() -> Result<Vec<RAWINPUTDEVICELIST>> {
+    let item_size = std::mem::size_of::<RAWINPUTDEVICELIST>() as u32;
+    let mut count = 0;
+    unsafe {
+        if GetRawInputDeviceList(std::ptr::null_mut(), &mut count, item_size) == u32::MAX {
+            return Err(get_last_error("Failed to count raw input devices"));
+        }
+        let mut devices = vec![RAWINPUTDEVICELIST::default(); count as usize];
+        let found = GetRawInputDeviceList(devices.as_mut_ptr(), &mut count, item_size);
+        if found == u32::MAX {
+            return Err(get_last_error("Failed to list raw input devices"));
+        }
+        devices.truncate(found as usize);
+        Ok(devices)
+    }
+}
+
+/// The device interface path of a raw input device, which stays the same across reconnects
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getrawinputdeviceinfow
+pub fn get_raw_input_device_name(device: HANDLE) -> Result<String> {
+    let mut length = 0;
+    unsafe {
+        GetRawInputDeviceInfoW(device, RIDI_DEVICENAME, std::ptr::null_mut(), &mut length);
+        let mut name = vec![0u16; length as usize];
+        if GetRawInputDeviceInfoW(
+            device,
+            RIDI_DEVICENAME,
+            name.as_mut_ptr() as *mut c_void,
+            &mut length,
+        ) == u32::MAX
+        {
+            return Err(get_last_error("Failed to get the raw input device name"));
+        }
+        let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        Ok(String::from_utf16_lossy(&name[..end]))
+    }
+}
+
+/// Width of the title bar icon and of the taskbar icon in pixels at `dpi`
+/// https://docs.microsoft.com/en-us/windows/win32/menurc/about-icons
+pub fn get_icon_sizes(dpi: u32) -> (u32, u32) {
+    unsafe {
+        (
+            GetSystemMetricsForDpi(SM_CXSMICON.0 as i32, dpi).max(1) as u32,
+            GetSystemMetricsForDpi(SM_CXICON.0 as i32, dpi).max(1) as u32,
+        )
+    }
+}
+
+/// Sets the small (title bar) or big (taskbar and Alt+Tab) icon of a window.
+/// The window does not take ownership, the icon has to outlive its use.
+/// https://docs.microsoft.com/en-us/windows/win32/winmsg/wm-seticon
+pub fn set_window_icon(handle: HWND, small: bool, icon: HICON) {
+    let kind = if small { ICON_SMALL } else { ICON_BIG };
+    unsafe { SendMessageW(handle, WM_SETICON, WPARAM(kind as usize), LPARAM(icon.0)) };
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-destroyicon
+pub fn destroy_icon(icon: HICON) {
+    unsafe { DestroyIcon(icon) };
+}
+
+/// Initialises COM on the calling thread as a single threaded apartment with OLE on top, which
+/// drag and drop needs. Succeeds again on a thread that already called it.
+/// https://docs.microsoft.com/en-us/windows/win32/api/ole2/nf-ole2-oleinitialize
+pub fn ole_initialize() -> Result<()> {
+    unsafe { OleInitialize(std::ptr::null_mut()) }
+}
+
+/// Undoes one successful `ole_initialize` on the calling thread
+/// https://docs.microsoft.com/en-us/windows/win32/api/ole2/nf-ole2-oleuninitialize
+pub fn ole_uninitialize() {
+    unsafe { OleUninitialize() };
+}
+
+/// Makes the target receive OLE drags over a window, the window holds a reference to it until
+/// `revoke_drag_drop`. Fails unless `ole_initialize` was called on the thread.
+/// https://docs.microsoft.com/en-us/windows/win32/api/ole2/nf-ole2-registerdragdrop
+pub fn register_drag_drop(handle: HWND, target: &IDropTarget) -> Result<()> {
+    unsafe { RegisterDragDrop(handle, target) }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/ole2/nf-ole2-revokedragdrop
+pub fn revoke_drag_drop(handle: HWND) -> Result<()> {
+    unsafe { RevokeDragDrop(handle) }
+}
+
+/// Sends WM_DROPFILES when files are dropped on a window, without any hover messages
+/// https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-dragacceptfiles
+pub fn drag_accept_files(handle: HWND, accept: bool) {
+    unsafe { DragAcceptFiles(handle, accept) };
+}
+
+/// The paths in a list of dropped files
+/// https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-dragqueryfilew
+pub fn drag_query_files(drop: HDROP) -> Vec<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+
+    unsafe {
+        // An index of u32::MAX asks for the number of files
+        let count = DragQueryFileW(drop, u32::MAX, PWSTR::default(), 0);
+        (0..count)
+            .filter_map(|index| {
+                // The length excludes the terminating null
+                let length = DragQueryFileW(drop, index, PWSTR::default(), 0);
+                if length == 0 {
+                    return None;
+                }
+                let mut path = vec![0u16; length as usize + 1];
+                let copied = DragQueryFileW(drop, index, PWSTR(path.as_mut_ptr()), length + 1);
+                path.truncate(copied as usize);
+                Some(PathBuf::from(std::ffi::OsString::from_wide(&path)))
+            })
+            .collect()
+    }
+}
+
+/// Where the files of a WM_DROPFILES were dropped, in client coordinates
+/// https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-dragquerypoint
+pub fn drag_query_point(drop: HDROP) -> POINT {
+    let mut point = POINT::default();
+    unsafe { DragQueryPoint(drop, &mut point) };
+    point
+}
+
+/// Frees the file list of a WM_DROPFILES
+/// https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-dragfinish
+pub fn drag_finish(drop: HDROP) {
+    unsafe { DragFinish(drop) };
+}
+
+/// The paths of the files in dragged data, an error if it holds no files (e.g. dragged text)
+/// https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-idataobject-getdata
+pub fn get_drop_files(data: &IDataObject) -> Result<Vec<PathBuf>> {
+    let format = FORMATETC {
+        cfFormat: CF_HDROP.0 as u16,
+        ptd: std::ptr::null_mut(),
+        dwAspect: DVASPECT_CONTENT.0 as u32,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL.0 as u32,
+    };
+    unsafe {
+        // ReleaseStgMedium releases pUnkForRelease, so it must not be released again on drop
+        let mut medium = std::mem::ManuallyDrop::new(data.GetData(&format)?);
+        let paths = drag_query_files(HDROP(medium.Anonymous.hGlobal));
+        ReleaseStgMedium(&mut *medium);
+        Ok(paths)
+    }
+}
+
+/// Fails while another window has the clipboard open
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-openclipboard
+pub fn open_clipboard(owner: HWND) -> Result<()> {
+    unsafe { OpenClipboard(owner).ok() }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-closeclipboard
+pub fn close_clipboard() {
+    unsafe { CloseClipboard() };
+}
+
+/// Removes everything from the open clipboard and makes the window that opened it the owner
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-emptyclipboard
+pub fn empty_clipboard() -> Result<()> {
+    unsafe { EmptyClipboard().ok() }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-isclipboardformatavailable
+pub fn is_clipboard_format_available(format: u32) -> bool {
+    unsafe { IsClipboardFormatAvailable(format).as_bool() }
+}
+
+/// The id of a format by name, registering it if no process has yet
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-registerclipboardformatw
+pub fn register_clipboard_format(name: &str) -> Result<u32> {
+    let name: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    match unsafe { RegisterClipboardFormatW(PCWSTR(name.as_ptr())) } {
+        0 => Err(get_last_error("Failed to register the clipboard format")),
+        format => Ok(format),
+    }
+}
+
+/// A copy of the data in a format on the open clipboard. The memory is allocated in blocks,
+/// so there can be bytes past the end of the data.
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getclipboarddata
+pub fn get_clipboard_data(format: u32) -> Result<Vec<u8>> {
+    unsafe {
+        let memory = GetClipboardData(format);
+        if memory.is_invalid() {
+            return Err(get_last_error("Failed to get the clipboard data"));
+        }
+        // The clipboard keeps ownership of the memory
+        let data = GlobalLock(memory.0) as *const u8;
+        if data.is_null() {
+            return Err(get_last_error("Failed to lock the clipboard data"));
+        }
+        let bytes = std::slice::from_raw_parts(data, GlobalSize(memory.0)).to_vec();
+        GlobalUnlock(memory.0);
+        Ok(bytes)
+    }
+}
+
+/// Puts a copy of the data on the open clipboard, which must have been emptied by this window
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setclipboarddata
+pub fn set_clipboard_data(format: u32, bytes: &[u8]) -> Result<()> {
+    unsafe {
+        let memory = GlobalAlloc(GMEM_MOVEABLE, bytes.len().max(1));
+        if memory == 0 {
+            return Err(get_last_error("Failed to allocate the clipboard data"));
+        }
+        let data = GlobalLock(memory) as *mut u8;
+        if data.is_null() {
+            let error = get_last_error("Failed to lock the clipboard data");
+            GlobalFree(memory);
+            return Err(error);
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+        GlobalUnlock(memory);
+        // The clipboard owns the memory once it is set, and it is still ours to free otherwise
+        if SetClipboardData(format, HANDLE(memory)).is_invalid() {
+            let error = get_last_error("Failed to set the clipboard data");
+            GlobalFree(memory);
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+/// Sends WM_CLIPBOARDUPDATE to a window whenever the clipboard changes
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-addclipboardformatlistener
+pub fn add_clipboard_format_listener(handle: HWND) -> Result<()> {
+    unsafe { AddClipboardFormatListener(handle).ok() }
+}
+
+/// https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-removeclipboardformatlistener
+pub fn remove_clipboard_format_listener(handle: HWND) -> Result<()> {
+    unsafe { RemoveClipboardFormatListener(handle).ok() }
+}
@@ -1,16 +1,40 @@
 use windows::{core::*, Win32::Foundation::*, Win32::UI::WindowsAndMessaging::*};
 
+use crate::alpha_mask::AlphaMask;
+use crate::clipboard::{Clipboard, ClipboardError};
+use crate::coords::{ClientPoint, ScreenPoint, ScreenRect, WindowPoint};
+use crate::cursor::{Cursor, CursorGrab, CursorIcon};
+use crate::dpi::{scale_factor_from_dpi, LogicalSize, PhysicalSize};
+use crate::drag_drop::{DragEvent, DropEffect, DropTarget, FileDrop};
+use crate::fullscreen::{needs_video_mode_restore, Fullscreen, FullscreenState, SavedPlacement};
+use crate::game_loop::{FixedTimestep, LoopMode};
+use crate::icon::{to_bgra, IconSet};
 use crate::input::{Input, KeyCode};
+use crate::monitor::{available_monitors, screen_rect, win32_rect, Monitor};
+use crate::placement::{fit_to_monitors, MonitorArea, PlacementError, WindowPlacement};
+use crate::raw_input::{parse_device_change, parse_raw_input, DeviceId, DeviceKind, RawEvent};
 use crate::render::Engine;
+use crate::surface::RenderError;
 use crate::utils::{rgb, str_to_pcwstr, GET_X_LPARAM, GET_Y_LPARAM};
 use crate::winapi_utils::*;
+use crate::window_state::{maximized_rect, TaskbarEdges, WindowState};
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, Win32Handle};
 use std::ffi::c_void;
+use std::path::PathBuf;
+use std::time::Instant;
 use windows::Win32::Foundation::LRESULT;
+use windows::Win32::Graphics::Gdi::HBRUSH;
+use windows::Win32::UI::Input::{HRAWINPUT, RIM_TYPEKEYBOARD, RIM_TYPEMOUSE};
+use windows::Win32::UI::Shell::{ABE_BOTTOM, ABE_LEFT, ABE_RIGHT, ABE_TOP, HDROP};
 
 /// Default background colour
 const BGCOLOUR: u32 = rgb(52, 55, 60);
 
+/// Timer watching the cursor while clicks pass through transparent pixels
+const PASS_THROUGH_TIMER: usize = 1;
+/// How often the cursor is checked while clicks pass through, in milliseconds
+const PASS_THROUGH_INTERVAL: u32 = 16;
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct Window {
@@ -18,6 +42,53 @@ pub struct Window {
     engine: *mut Engine,
     instance: HINSTANCE,
     handle: HWND,
+    // True between requesting a redraw and the WM_PAINT that handles it
+    redraw_requested: bool,
+    loop_mode: LoopMode,
+    timestep: FixedTimestep,
+    transparent: bool,
+    // Clicks on pixels that are transparent in the mask fall through the window
+    hit_mask: Option<AlphaMask>,
+    // The whole window ignores the mouse
+    click_through: bool,
+    // True while the cursor is over a transparent pixel of the hit mask
+    passing_through: bool,
+    // DPI of the window's monitor over 96
+    scale_factor: f64,
+    fullscreen: FullscreenState,
+    // The last state reported to the engine
+    state: WindowState,
+    // Outer rect the last time the window was neither maximized, minimized nor fullscreen
+    normal_rect: ScreenRect,
+    // The rect `set_placement` is moving the window to, already scaled for the monitor's DPI
+    placing: Option<RECT>,
+    // Where the placement is saved when the window is destroyed
+    placement_file: Option<PathBuf>,
+    // Shown over the client area
+    cursor: Cursor,
+    cursor_visible: bool,
+    cursor_grab: CursorGrab,
+    // True while the window is the active window, the cursor is only grabbed while active
+    active: bool,
+    // WM_INPUT is registered for
+    raw_input: bool,
+    // Kept to make the icons again when the DPI changes
+    icon: Option<IconSet>,
+    // Small and large icons made from `icon`, owned by the window
+    icon_handles: Option<(HICON, HICON)>,
+    // None until dropping files is enabled
+    file_drop: Option<FileDrop>,
+    // WM_CLIPBOARDUPDATE is registered for
+    clipboard_listener: bool,
+}
+
+/// Options for creating a window
+#[derive(Clone, Debug, Default)]
+pub struct WindowSettings {
+    /// See `Window::new_transparent`
+    pub transparent: bool,
+    /// Title bar and taskbar icon, the default application icon if None
+    pub icon: Option<IconSet>,
 }
 
 impl Window {
@@ -31,15 +102,52 @@ impl Window {
     }
 
     pub fn new(title: &str, window_class_name: &str) -> Result<Box<Self>> {
+        Self::with_settings(title, window_class_name, WindowSettings::default())
+    }
+
+    /// A window composed by DWM using the alpha channel of what is drawn, for HUD overlays and
+    /// non-rectangular splash screens. The engine has to be created with
+    /// `RenderSettings::transparent` so that it clears to transparent.
+    #[allow(dead_code)]
+    pub fn new_transparent(title: &str, window_class_name: &str) -> Result<Box<Self>> {
+        let settings = WindowSettings {
+            transparent: true,
+            ..WindowSettings::default()
+        };
+        Self::with_settings(title, window_class_name, settings)
+    }
+
+    pub fn with_settings(
+        title: &str,
+        window_class_name: &str,
+        settings: WindowSettings,
+    ) -> Result<Box<Self>> {
+        let mut window = Self::create(title, window_class_name, settings.transparent)?;
+        if settings.icon.is_some() {
+            window.set_icon(settings.icon)?;
+        }
+        Ok(window)
+    }
+
+    fn create(title: &str, window_class_name: &str, transparent: bool) -> Result<Box<Self>> {
+        // Only the first window sets the awareness, later calls fail as it is already set
+        let _ = enable_per_monitor_dpi_awareness();
         let hinstance = get_current_module_handle()?;
 
+        // Without a brush the background is never erased, which would cover the alpha channel
+        let background = if transparent {
+            HBRUSH(0)
+        } else {
+            solid_brush(BGCOLOUR)?
+        };
+
         let wc = WNDCLASSEXW {
             cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
             hInstance: hinstance, // A handle to the process that contains the window procedure
             style: CS_HREDRAW | CS_VREDRAW | CS_OWNDC, // Styling (Nothing to do with aesthetics)
-            hIcon: warning_icon()?,
+            hIcon: default_app_icon()?,
             hCursor: default_cursor()?, // A handle to the class cursor
-            hbrBackground: solid_brush(BGCOLOUR)?,
+            hbrBackground: background,
             lpszClassName: str_to_pcwstr(window_class_name),
 
             lpfnWndProc: Some(Self::wnd_proc_sys), // A pointer to the window procedure - defined below
@@ -52,9 +160,31 @@ impl Window {
             engine: std::ptr::null_mut(),
             instance: hinstance,
             handle: HWND(0),
+            redraw_requested: false,
+            loop_mode: LoopMode::Wait,
+            timestep: FixedTimestep::from_rate(60),
+            transparent,
+            hit_mask: None,
+            click_through: false,
+            passing_through: false,
+            scale_factor: 1.0,
+            fullscreen: FullscreenState::default(),
+            state: WindowState::Normal,
+            normal_rect: ScreenRect::default(),
+            placing: None,
+            placement_file: None,
+            cursor: Cursor::default(),
+            cursor_visible: true,
+            cursor_grab: CursorGrab::None,
+            active: false,
+            raw_input: false,
+            icon: None,
+            icon_handles: None,
+            file_drop: None,
+            clipboard_listener: false,
         });
 
-        let _hwnd = create_window(
+        let hwnd = create_window(
             Default::default(),
             window_class_name,
             title,
@@ -67,6 +197,8 @@ impl Window {
             // &mut window as *mut _ as *mut c_void,
             window.as_mut() as *mut _ as _,
         )?;
+        window.scale_factor = scale_factor_from_dpi(get_dpi_for_window(hwnd));
+        window.normal_rect = screen_rect(&get_window_rect(hwnd)?);
 
         Ok(window)
     }
@@ -75,74 +207,901 @@ impl Window {
         self.engine = engine
     }
 
-    pub fn get_size(&self) -> Result<(u32, u32)> {
+    pub(crate) fn handle(&self) -> HWND {
+        self.handle
+    }
+
+    /// Size of the client area, which is what gets drawn to
+    pub fn inner_size(&self) -> Result<PhysicalSize> {
+        Ok(rect_size(&get_client_rect(self.handle)?))
+    }
+
+    /// Size of the whole window including any resize border
+    #[allow(dead_code)]
+    pub fn outer_size(&self) -> Result<PhysicalSize> {
+        Ok(rect_size(&get_window_rect(self.handle)?))
+    }
+
+    #[allow(dead_code)]
+    pub fn logical_inner_size(&self) -> Result<LogicalSize> {
+        Ok(self.inner_size()?.to_logical(self.scale_factor))
+    }
+
+    /// The client area size in pixels, the same as `inner_size`
+    #[allow(dead_code)]
+    pub fn physical_size(&self) -> Result<PhysicalSize> {
+        self.inner_size()
+    }
+
+    /// The client area size at 96 DPI, the same as `logical_inner_size`
+    #[allow(dead_code)]
+    pub fn logical_size(&self) -> Result<LogicalSize> {
+        self.logical_inner_size()
+    }
+
+    /// Top left of the client area on the screen
+    #[allow(dead_code)]
+    pub fn inner_position(&self) -> Result<ScreenPoint> {
+        self.client_to_screen(ClientPoint::default())
+    }
+
+    /// Top left of the whole window on the screen
+    pub fn outer_position(&self) -> Result<ScreenPoint> {
         let rect = get_window_rect(self.handle)?;
-        let width = rect.right - rect.left;
-        let height = rect.bottom - rect.top;
+        Ok(ScreenPoint::new(rect.left, rect.top))
+    }
+
+    pub fn screen_to_client(&self, point: ScreenPoint) -> Result<ClientPoint> {
+        let point = screen_to_client(
+            self.handle,
+            POINT {
+                x: point.x,
+                y: point.y,
+            },
+        )?;
+        Ok(ClientPoint::new(point.x, point.y))
+    }
 
-        Ok((width as u32, height as u32))
+    pub fn client_to_screen(&self, point: ClientPoint) -> Result<ScreenPoint> {
+        let point = client_to_screen(
+            self.handle,
+            POINT {
+                x: point.x,
+                y: point.y,
+            },
+        )?;
+        Ok(ScreenPoint::new(point.x, point.y))
+    }
+
+    pub fn screen_to_window(&self, point: ScreenPoint) -> Result<WindowPoint> {
+        let origin = self.outer_position()?;
+        Ok(WindowPoint::new(point.x - origin.x, point.y - origin.y))
+    }
+
+    #[allow(dead_code)]
+    pub fn window_to_screen(&self, point: WindowPoint) -> Result<ScreenPoint> {
+        let origin = self.outer_position()?;
+        Ok(ScreenPoint::new(point.x + origin.x, point.y + origin.y))
+    }
+
+    #[allow(dead_code)]
+    pub fn client_to_window(&self, point: ClientPoint) -> Result<WindowPoint> {
+        self.screen_to_window(self.client_to_screen(point)?)
+    }
+
+    #[allow(dead_code)]
+    pub fn window_to_client(&self, point: WindowPoint) -> Result<ClientPoint> {
+        self.screen_to_client(self.window_to_screen(point)?)
+    }
+
+    /// DPI of the monitor the window is on over 96
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// The monitor with the largest part of the window
+    pub fn current_monitor(&self) -> Result<Monitor> {
+        Monitor::from_window(self.handle)
+    }
+
+    #[allow(dead_code)]
+    pub fn fullscreen(&self) -> Option<&Fullscreen> {
+        self.fullscreen.mode()
+    }
+
+    /// Covers a monitor with the window, or with None puts the window back where it was before
+    /// the first fullscreen request
+    #[allow(dead_code)]
+    pub fn set_fullscreen(&mut self, fullscreen: Option<Fullscreen>) -> Result<()> {
+        if needs_video_mode_restore(self.fullscreen.mode(), fullscreen.as_ref()) {
+            if let Some(Fullscreen::DisplayMode(monitor, _)) = self.fullscreen.mode() {
+                monitor.restore_video_mode()?;
+            }
+        }
+
+        let mode = match fullscreen {
+            Some(mode) => mode,
+            None => {
+                if let Some((_, saved)) = self.fullscreen.exit() {
+                    set_window_style(self.handle, WINDOW_STYLE(saved.style));
+                    set_window_frame(self.handle, &win32_rect(&saved.rect))?;
+                    if saved.maximized {
+                        show_window(self.handle, SW_MAXIMIZE);
+                    }
+                }
+                return Ok(());
+            }
+        };
+
+        let monitor = match mode.monitor() {
+            Some(monitor) => monitor.clone(),
+            None => self.current_monitor()?,
+        };
+        if let Fullscreen::DisplayMode(_, video_mode) = &mode {
+            monitor.set_video_mode(video_mode)?;
+        }
+        // The bounds move with the display mode
+        let bounds = monitor.refresh()?.bounds;
+
+        let placement = self.fullscreen_placement()?;
+        let style = (placement.style & !WS_OVERLAPPEDWINDOW.0) | WS_POPUP.0;
+        self.fullscreen.enter(mode, placement);
+        set_window_style(self.handle, WINDOW_STYLE(style));
+        set_window_frame(self.handle, &win32_rect(&bounds))
+    }
+
+    /// Where the window is now, a maximized window is restored first so the rect is the one it
+    /// is restored to. Only the placement from before fullscreen is kept, so a window that is
+    /// already fullscreen is left as it is.
+    fn fullscreen_placement(&self) -> Result<SavedPlacement> {
+        let maximized = is_zoomed(self.handle);
+        if maximized && !self.fullscreen.is_fullscreen() {
+            show_window(self.handle, SW_RESTORE);
+        }
+        Ok(SavedPlacement {
+            rect: screen_rect(&get_window_rect(self.handle)?),
+            style: get_window_style(self.handle).0,
+            maximized,
+        })
+    }
+
+    /// Where the window is, to be saved and given to `set_placement` on the next launch
+    pub fn placement(&self) -> Result<WindowPlacement> {
+        let monitor = self.current_monitor()?;
+        let (rect, maximized) = match self.fullscreen.saved() {
+            Some(saved) => (saved.rect, saved.maximized),
+            None => (self.normal_rect, is_zoomed(self.handle)),
+        };
+        let mut placement = WindowPlacement {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+            maximized,
+            fullscreen: self.fullscreen.is_fullscreen(),
+            monitor: monitor.name,
+            dpi: monitor.dpi,
+        };
+        placement.set_rect(rect);
+        Ok(placement)
+    }
+
+    /// Puts the window where a placement says, fitted onto the monitors connected now
+    pub fn set_placement(&mut self, placement: &WindowPlacement) -> Result<()> {
+        let monitors = available_monitors()?;
+        let areas: Vec<MonitorArea> = monitors.iter().map(MonitorArea::from).collect();
+        let placement = match fit_to_monitors(placement, &areas) {
+            Some(placement) => placement,
+            None => return Ok(()),
+        };
+
+        self.set_fullscreen(None)?;
+        if is_zoomed(self.handle) || is_iconic(self.handle) {
+            self.restore();
+        }
+        // The fitted size is already scaled, so the rect suggested by WM_DPICHANGED is ignored
+        let rect = win32_rect(&placement.rect());
+        self.placing = Some(rect);
+        let result = set_window_frame(self.handle, &rect);
+        self.placing = None;
+        result?;
+        self.normal_rect = placement.rect();
+
+        if placement.fullscreen {
+            let monitor = monitors
+                .into_iter()
+                .find(|monitor| monitor.name == placement.monitor);
+            if placement.maximized {
+                // Leaving fullscreen goes back to maximized
+                self.maximize();
+            }
+            self.set_fullscreen(Some(Fullscreen::Borderless(monitor)))?;
+        } else if placement.maximized {
+            self.maximize();
+        }
+        Ok(())
+    }
+
+    /// Restores the placement saved in a file if there is one, and saves the placement there
+    /// when the window is destroyed
+    pub fn remember_placement<P: Into<PathBuf>>(
+        &mut self,
+        path: P,
+    ) -> std::result::Result<(), PlacementError> {
+        let path = path.into();
+        let restored = if path.exists() {
+            let placement = WindowPlacement::load(&path);
+            placement.and_then(|placement| Ok(self.set_placement(&placement)?))
+        } else {
+            Ok(())
+        };
+        // A bad file is replaced on close
+        self.placement_file = Some(path);
+        restored
+    }
+
+    fn save_placement(&self) {
+        if let Some(path) = &self.placement_file {
+            let saved = self
+                .placement()
+                .map_err(PlacementError::from)
+                .and_then(|placement| placement.save(path));
+            if let Err(error) = saved {
+                log::warn!("Could not save the window placement: {}", error);
+            }
+        }
+    }
+
+    /// Keeps the rect to save for a window that is maximized or fullscreen
+    fn update_normal_rect(&mut self) {
+        if is_zoomed(self.handle) || is_iconic(self.handle) || self.fullscreen.is_fullscreen() {
+            return;
+        }
+        if let Ok(rect) = get_window_rect(self.handle) {
+            self.normal_rect = screen_rect(&rect);
+        }
+    }
+
+    /// Sets the cursor shown over the client area
+    #[allow(dead_code)]
+    pub fn set_cursor<C: Into<Cursor>>(&mut self, cursor: C) {
+        // A custom cursor is destroyed when dropped, so the old one is only dropped once it is
+        // no longer the cursor shown
+        let old = std::mem::replace(&mut self.cursor, cursor.into());
+        self.refresh_cursor();
+        drop(old);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        self.refresh_cursor();
+    }
+
+    #[allow(dead_code)]
+    pub fn cursor_grab(&self) -> CursorGrab {
+        self.cursor_grab
+    }
+
+    #[allow(dead_code)]
+    pub fn set_cursor_grab(&mut self, grab: CursorGrab) -> Result<()> {
+        self.cursor_grab = grab;
+        self.apply_cursor_grab()?;
+        if grab == CursorGrab::Locked {
+            self.centre_cursor()?;
+        }
+        self.refresh_cursor();
+        Ok(())
+    }
+
+    /// Sends `Input::Raw` for every mouse and keyboard while the window is in the foreground,
+    /// and takes locked cursor movement from the mouse without acceleration
+    #[allow(dead_code)]
+    pub fn set_raw_input(&mut self, enabled: bool) -> Result<()> {
+        if enabled != self.raw_input {
+            register_raw_input(self.handle, enabled)?;
+            self.raw_input = enabled;
+        }
+        Ok(())
+    }
+
+    /// Mice, keyboards and other input devices connected now
+    #[allow(dead_code)]
+    pub fn raw_input_devices(&self) -> Result<Vec<(DeviceId, DeviceKind)>> {
+        Ok(get_raw_input_device_list()?
+            .into_iter()
+            .map(|device| {
+                let kind = match device.dwType {
+                    RIM_TYPEMOUSE => DeviceKind::Mouse,
+                    RIM_TYPEKEYBOARD => DeviceKind::Keyboard,
+                    _ => DeviceKind::Other,
+                };
+                (DeviceId(device.hDevice.0), kind)
+            })
+            .collect())
+    }
+
+    /// A name for a device that stays the same when it is reconnected
+    #[allow(dead_code)]
+    pub fn raw_input_device_name(&self, device: DeviceId) -> Result<String> {
+        get_raw_input_device_name(HANDLE(device.0))
+    }
+
+    /// Lets files be dragged onto the window, reported to the engine which decides whether to
+    /// accept them. Falls back to only reporting drops if the window cannot be an OLE drop
+    /// target.
+    pub fn set_file_drop(&mut self, enabled: bool) -> Result<()> {
+        match (enabled, self.file_drop) {
+            (true, None) => {
+                let target = DropTarget::create(self);
+                // Every successful initialise is matched by an uninitialise, here if registering
+                // fails and otherwise when dropping is disabled
+                let registered = ole_initialize().and_then(|_| {
+                    register_drag_drop(self.handle, &target).inspect_err(|_| ole_uninitialize())
+                });
+                self.file_drop = match registered {
+                    Ok(()) => Some(FileDrop::Ole),
+                    Err(error) => {
+                        log::warn!(
+                            "Dragged files will only be reported when dropped: {}",
+                            error
+                        );
+                        drag_accept_files(self.handle, true);
+                        Some(FileDrop::DropFiles)
+                    }
+                };
+            }
+            (false, Some(FileDrop::Ole)) => {
+                revoke_drag_drop(self.handle)?;
+                ole_uninitialize();
+                self.file_drop = None;
+            }
+            (false, Some(FileDrop::DropFiles)) => {
+                drag_accept_files(self.handle, false);
+                self.file_drop = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Opens the clipboard with the window as the owner of anything set on it
+    #[allow(dead_code)]
+    pub fn clipboard(&self) -> std::result::Result<Clipboard, ClipboardError> {
+        Clipboard::open(self.handle)
+    }
+
+    /// Sends `Input::ClipboardChanged` whenever anything is copied, such as to enable a paste
+    /// button only while there is something to paste
+    #[allow(dead_code)]
+    pub fn set_clipboard_listener(&mut self, enabled: bool) -> Result<()> {
+        if enabled != self.clipboard_listener {
+            if enabled {
+                add_clipboard_format_listener(self.handle)?;
+            } else {
+                remove_clipboard_format_listener(self.handle)?;
+            }
+            self.clipboard_listener = enabled;
+        }
+        Ok(())
+    }
+
+    /// Passes a drag to the engine, which answers whether it would take the files
+    pub(crate) fn drag(&mut self, event: DragEvent) -> DropEffect {
+        let state = match unsafe { self.engine.as_mut() } {
+            Some(state) => state,
+            None => return DropEffect::None,
+        };
+        let effect = state.drag(event);
+        if state.needs_redraw() {
+            self.request_redraw();
+        }
+        effect
+    }
+
+    /// Sets the title bar and taskbar icon, None goes back to the default application icon
+    pub fn set_icon(&mut self, icon: Option<IconSet>) -> Result<()> {
+        self.icon = icon;
+        self.update_icon()
+    }
+
+    /// Makes the icons at the sizes for the current DPI
+    fn update_icon(&mut self) -> Result<()> {
+        let handles = match &self.icon {
+            Some(icon) => {
+                let (small, large) = get_icon_sizes(get_dpi_for_window(self.handle));
+                let create = |size: u32| {
+                    let image = icon.image_for_size(size);
+                    create_icon_from_bgra(size, size, &to_bgra(&image), None)
+                };
+                let small = create(small)?;
+                let large = match create(large) {
+                    Ok(large) => large,
+                    Err(error) => {
+                        destroy_icon(small);
+                        return Err(error);
+                    }
+                };
+                Some((small, large))
+            }
+            None => None,
+        };
+        let (small, large) = handles.unwrap_or_default();
+        set_window_icon(self.handle, true, small);
+        set_window_icon(self.handle, false, large);
+        // The old icons are only destroyed once they are no longer in use
+        if let Some((small, large)) = std::mem::replace(&mut self.icon_handles, handles) {
+            destroy_icon(small);
+            destroy_icon(large);
+        }
+        Ok(())
+    }
+
+    /// The cursor for the client area, HCURSOR(0) when hidden
+    fn client_cursor(&self) -> HCURSOR {
+        if self.cursor_visible && self.cursor_grab != CursorGrab::Locked {
+            self.cursor.handle().unwrap_or_default()
+        } else {
+            HCURSOR(0)
+        }
+    }
+
+    /// Changes the cursor now instead of on the next mouse move, if it is over the client area
+    fn refresh_cursor(&self) {
+        let over_client = get_cursor_pos()
+            .ok()
+            .and_then(|cursor| {
+                self.screen_to_client(ScreenPoint::new(cursor.x, cursor.y))
+                    .ok()
+            })
+            .zip(self.inner_size().ok())
+            .is_some_and(|(point, size)| point.to_pixel(size).is_some());
+        if over_client {
+            set_cursor(self.client_cursor());
+        }
+    }
+
+    /// Confines the cursor to the client area while the window is active and grabs it.
+    /// The confinement is lost when the window moves or another window is activated, so this
+    /// runs again then.
+    fn apply_cursor_grab(&self) -> Result<()> {
+        if !self.active || self.cursor_grab == CursorGrab::None {
+            return clip_cursor(None);
+        }
+        let position = self.inner_position()?;
+        let size = self.inner_size()?;
+        let end = position.offset(size.width as i32, size.height as i32);
+        clip_cursor(Some(&RECT {
+            left: position.x,
+            top: position.y,
+            right: end.x,
+            bottom: end.y,
+        }))
+    }
+
+    fn client_centre(&self) -> Result<ClientPoint> {
+        let size = self.inner_size()?;
+        Ok(ClientPoint::new(
+            size.width as i32 / 2,
+            size.height as i32 / 2,
+        ))
+    }
+
+    fn centre_cursor(&self) -> Result<()> {
+        let centre = self.client_to_screen(self.client_centre()?)?;
+        set_cursor_pos(POINT {
+            x: centre.x,
+            y: centre.y,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn state(&self) -> WindowState {
+        self.state
+    }
+
+    #[allow(dead_code)]
+    pub fn is_maximized(&self) -> bool {
+        is_zoomed(self.handle)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_minimized(&self) -> bool {
+        is_iconic(self.handle)
+    }
+
+    #[allow(dead_code)]
+    pub fn maximize(&self) {
+        show_window(self.handle, SW_MAXIMIZE);
+    }
+
+    #[allow(dead_code)]
+    pub fn minimize(&self) {
+        show_window(self.handle, SW_MINIMIZE);
+    }
+
+    /// Undoes a maximize or minimize
+    #[allow(dead_code)]
+    pub fn restore(&self) {
+        show_window(self.handle, SW_RESTORE);
+    }
+
+    /// Tells the engine about a change of state, WM_SIZE repeats the state on every resize
+    fn update_state(&mut self, state: WindowState) {
+        if self.state != state {
+            self.state = state;
+            self.engine_input(Input::WindowStateChanged(state));
+        }
+    }
+
+    /// Where the window goes when maximized on the monitor under `proposed`
+    fn maximized_rect(&self, proposed: &RECT) -> Result<RECT> {
+        let monitor = Monitor::from_rect(proposed)?;
+        let mut edges = TaskbarEdges::default();
+        if is_taskbar_autohide() {
+            let bounds = win32_rect(&monitor.bounds);
+            edges = TaskbarEdges {
+                left: has_autohide_bar(ABE_LEFT, &bounds),
+                top: has_autohide_bar(ABE_TOP, &bounds),
+                right: has_autohide_bar(ABE_RIGHT, &bounds),
+                bottom: has_autohide_bar(ABE_BOTTOM, &bounds),
+            };
+        }
+        Ok(win32_rect(&maximized_rect(monitor.work_area, edges)))
+    }
+
+    #[allow(dead_code)]
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+
+    #[allow(dead_code)]
+    pub fn set_loop_mode(&mut self, mode: LoopMode) {
+        // Time spent blocked should not be caught up on
+        if self.loop_mode == LoopMode::Wait && mode != LoopMode::Wait {
+            self.timestep.reset();
+        }
+        self.loop_mode = mode;
+    }
+
+    #[allow(dead_code)]
+    /// Sets the rate fixed updates run at
+    pub fn set_update_rate(&mut self, updates_per_second: u32) {
+        self.timestep = FixedTimestep::from_rate(updates_per_second);
+    }
+
+    #[allow(dead_code)]
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Sets which pixels of the window can be clicked, None to make the whole window clickable
+    #[allow(dead_code)]
+    pub fn set_hit_mask(&mut self, mask: Option<AlphaMask>) {
+        self.hit_mask = mask;
+        if self.hit_mask.is_none() {
+            self.end_pass_through();
+        }
+    }
+
+    /// Makes the whole window ignore the mouse, such as for a HUD drawn over other applications
+    #[allow(dead_code)]
+    pub fn set_click_through(&mut self, click_through: bool) -> Result<()> {
+        self.end_pass_through();
+        set_click_through(self.handle, click_through)?;
+        self.click_through = click_through;
+        Ok(())
+    }
+
+    /// True if the mask, which covers the whole window, lets clicks at a point through
+    fn is_pass_through(&self, cursor: ScreenPoint) -> bool {
+        let mask = match &self.hit_mask {
+            Some(mask) => mask,
+            None => return false,
+        };
+        match (self.screen_to_window(cursor), self.outer_size()) {
+            (Ok(point), Ok(size)) => !mask.is_opaque((point.x, point.y), size.into()),
+            _ => false,
+        }
+    }
+
+    /// Clicks only reach other applications while the window is click through, so it is made
+    /// click through while the cursor is over a transparent pixel. A window that ignores the
+    /// mouse receives no more mouse messages, so a timer watches for the cursor leaving the
+    /// transparent pixels.
+    fn begin_pass_through(&mut self) {
+        if self.passing_through || self.click_through {
+            return;
+        }
+        if set_click_through(self.handle, true).is_ok()
+            && set_timer(self.handle, PASS_THROUGH_TIMER, PASS_THROUGH_INTERVAL).is_ok()
+        {
+            self.passing_through = true;
+        }
+    }
+
+    fn end_pass_through(&mut self) {
+        if !self.passing_through {
+            return;
+        }
+        let _ = kill_timer(self.handle, PASS_THROUGH_TIMER);
+        let _ = set_click_through(self.handle, self.click_through);
+        self.passing_through = false;
+    }
+
+    /// Asks for a WM_PAINT, requests made before it arrives are merged into one
+    pub fn request_redraw(&mut self) {
+        if !self.redraw_requested {
+            self.redraw_requested = invalidate_rect(self.handle).is_ok();
+        }
+    }
+
+    /// Passes input to the engine and redraws if it changed anything
+    fn engine_input(&mut self, input: Input) {
+        unsafe {
+            if let Some(state) = self.engine.as_mut() {
+                if state.input(input) {
+                    self.request_redraw();
+                }
+            }
+        }
     }
 
     fn wnd_proc(&mut self, message: u32, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
         match message {
             WM_CREATE => {
-                extend_frame_into_client_area(self.handle, &WindowStyle::Borderless).unwrap();
+                if self.transparent {
+                    extend_frame_into_client_area(self.handle, &WindowStyle::FlatBorderless)
+                        .unwrap();
+                    enable_per_pixel_alpha(self.handle, true).unwrap();
+                } else {
+                    extend_frame_into_client_area(self.handle, &WindowStyle::Borderless).unwrap();
+                }
                 None
             }
             WM_DESTROY => {
+                self.save_placement();
+                // The drop target points at the window, so it has to go first
+                if self.file_drop.is_some() {
+                    let _ = self.set_file_drop(false);
+                }
+                let _ = self.set_clipboard_listener(false);
+                if let Some((small, large)) = self.icon_handles.take() {
+                    destroy_icon(small);
+                    destroy_icon(large);
+                }
+                if self.cursor_grab != CursorGrab::None {
+                    let _ = clip_cursor(None);
+                }
+                // A changed display mode would otherwise last until the process exits
+                if let Some(Fullscreen::DisplayMode(monitor, _)) = self.fullscreen.mode() {
+                    let _ = monitor.restore_video_mode();
+                }
                 post_quit_message(0);
                 None
             }
             WM_PAINT => unsafe {
                 validate_rect(self.handle).unwrap();
+                self.redraw_requested = false;
 
+                // A tick may already have drawn the frame this paint was queued for
                 if let Some(state) = self.engine.as_mut() {
-                    state.render().unwrap();
+                    if state.needs_redraw() {
+                        if let Err(error) = state.render() {
+                            on_render_error(error);
+                        }
+                    }
                 }
 
                 None
             },
             WM_SIZE | WM_SIZING => unsafe {
-                let size = self.get_size().unwrap();
+                if message == WM_SIZE {
+                    self.update_normal_rect();
+                    let _ = self.apply_cursor_grab();
+                    if let Some(state) = WindowState::from_size_message(wparam.0 as u32) {
+                        self.update_state(state);
+                    }
+                }
+
+                // A minimized window still reports the size of its taskbar button
+                let size = if message == WM_SIZE && wparam.0 as u32 == SIZE_MINIMIZED {
+                    PhysicalSize::default()
+                } else {
+                    self.inner_size().unwrap()
+                };
 
                 if let Some(state) = self.engine.as_mut() {
-                    state.resize(size);
+                    state.resize(size.into());
+                    if state.needs_redraw() {
+                        self.request_redraw();
+                    }
                 }
                 None
             },
+            WM_DPICHANGED => {
+                // The new DPI is in both words of wparam, lparam points to the window rect the
+                // system suggests for it
+                let dpi = (wparam.0 & 0xffff) as u32;
+                let suggested = match self.placing {
+                    Some(rect) => rect,
+                    None => unsafe { *(lparam.0 as *const RECT) },
+                };
+                self.scale_factor = scale_factor_from_dpi(dpi);
+                self.engine_input(Input::ScaleFactorChanged {
+                    scale_factor: self.scale_factor,
+                    new_size: PhysicalSize::new(
+                        (suggested.right - suggested.left) as u32,
+                        (suggested.bottom - suggested.top) as u32,
+                    ),
+                });
+                if self.icon.is_some() {
+                    if let Err(error) = self.update_icon() {
+                        log::warn!("Could not make the icons for the new DPI: {}", error);
+                    }
+                }
+                // Resizing sends a WM_SIZE, which resizes the engine. A fullscreen window keeps
+                // covering its monitor rather than taking the scaled rect, and a window being
+                // placed is already on its way to the right size
+                if !self.fullscreen.is_fullscreen() && self.placing.is_none() {
+                    if let Err(error) = set_window_rect(self.handle, &suggested) {
+                        log::warn!("Could not apply the size for the new DPI: {}", error);
+                    }
+                }
+                Some(LRESULT(0))
+            }
+            WM_MOVE => {
+                self.update_normal_rect();
+                let _ = self.apply_cursor_grab();
+                None
+            }
+            WM_ACTIVATE => {
+                self.active = (wparam.0 & 0xffff) as u32 != WA_INACTIVE;
+                let _ = self.apply_cursor_grab();
+                if self.active && self.cursor_grab == CursorGrab::Locked {
+                    let _ = self.centre_cursor();
+                }
+                None
+            }
+            WM_INPUT => {
+                if let Ok(bytes) = get_raw_input_data(HRAWINPUT(lparam.0)) {
+                    for event in parse_raw_input(&bytes) {
+                        if let RawEvent::MouseMotion { delta, .. } = event {
+                            if self.cursor_grab == CursorGrab::Locked && self.active {
+                                self.engine_input(Input::MouseDelta(delta));
+                            }
+                        }
+                        self.engine_input(Input::Raw(event));
+                    }
+                }
+                // The default procedure frees the input
+                None
+            }
+            WM_INPUT_DEVICE_CHANGE => {
+                if let Some(event) = parse_device_change(wparam.0, lparam.0) {
+                    self.engine_input(Input::Raw(event));
+                }
+                Some(LRESULT(0))
+            }
+            WM_CLIPBOARDUPDATE => {
+                self.engine_input(Input::ClipboardChanged);
+                Some(LRESULT(0))
+            }
+            WM_DROPFILES => {
+                let drop = HDROP(wparam.0 as isize);
+                let paths = drag_query_files(drop);
+                let point = drag_query_point(drop);
+                drag_finish(drop);
+                self.drag(DragEvent::Drop {
+                    paths,
+                    position: ClientPoint::new(point.x, point.y),
+                });
+                Some(LRESULT(0))
+            }
+            WM_SETCURSOR => {
+                // The low word of lparam is the hit test result
+                let hit = (lparam.0 & 0xffff) as u32;
+                let cursor = if hit == HTCLIENT {
+                    Some(self.client_cursor())
+                } else {
+                    CursorIcon::from_hit_test(hit).and_then(|icon| icon.handle().ok())
+                };
+                match cursor {
+                    Some(cursor) => {
+                        set_cursor(cursor);
+                        Some(LRESULT(1))
+                    }
+                    None => None,
+                }
+            }
             WM_NCCALCSIZE => {
+                // Without a frame to hide off screen a maximized window would overhang the
+                // monitor by the resize border, so the client area is fitted to the work area.
+                // The proposed rect decides the monitor, as the window may not have moved yet
+                if wparam.0 != 0 && is_zoomed(self.handle) && !self.fullscreen.is_fullscreen() {
+                    let params = unsafe { &mut *(lparam.0 as *mut NCCALCSIZE_PARAMS) };
+                    if let Ok(rect) = self.maximized_rect(&params.rgrc[0]) {
+                        params.rgrc[0] = rect;
+                    }
+                }
                 // Stop this msg passing to the default procedure as it screws up borderless
                 Some(LRESULT(0))
             }
+            WM_NCLBUTTONDBLCLK if wparam.0 == HTCAPTION as usize => {
+                if is_zoomed(self.handle) {
+                    self.restore();
+                } else {
+                    self.maximize();
+                }
+                Some(LRESULT(0))
+            }
             // Non-client hit test
-            WM_NCHITTEST => Some(Region::hit_test(
-                self.handle,
-                POINT {
-                    x: GET_X_LPARAM(lparam.0 as u32),
-                    y: GET_Y_LPARAM(lparam.0 as u32),
-                },
-            )),
+            WM_NCHITTEST => {
+                let cursor =
+                    ScreenPoint::new(GET_X_LPARAM(lparam.0 as u32), GET_Y_LPARAM(lparam.0 as u32));
+                // A fullscreen window cannot be moved or resized
+                if self.fullscreen.is_fullscreen() {
+                    return Some(LRESULT(HTCLIENT as isize));
+                }
+                if self.is_pass_through(cursor) {
+                    self.begin_pass_through();
+                    return Some(LRESULT(HTTRANSPARENT as isize));
+                }
+                Some(Region::hit_test(
+                    self.handle,
+                    cursor,
+                    is_zoomed(self.handle),
+                ))
+            }
+            WM_TIMER if wparam.0 == PASS_THROUGH_TIMER => {
+                // Stop once the cursor is over an opaque pixel or has left the window
+                let over_transparent = get_cursor_pos()
+                    .map(|cursor| {
+                        let cursor = ScreenPoint::new(cursor.x, cursor.y);
+                        let rect = get_window_rect(self.handle).unwrap_or_default();
+                        let inside = cursor.x >= rect.left
+                            && cursor.x < rect.right
+                            && cursor.y >= rect.top
+                            && cursor.y < rect.bottom;
+                        inside && self.is_pass_through(cursor)
+                    })
+                    .unwrap_or(false);
+                if !over_transparent {
+                    self.end_pass_through();
+                }
+                Some(LRESULT(0))
+            }
+            WM_ERASEBKGND if self.transparent => {
+                // Erasing would paint over the alpha channel
+                Some(LRESULT(1))
+            }
             WM_LBUTTONDOWN => {
                 // println!("WM_LBUTTONDOWN {:?}, {:?}", wparam, lparam);
                 let x = GET_X_LPARAM(lparam.0 as u32);
                 let y = GET_Y_LPARAM(lparam.0 as u32);
-                println!("Mouse Clicked at x: {:?}, y: {:?}", x, y);
-                unsafe {
-                    if let Some(state) = self.engine.as_mut() {
-                        state.input(Input::LeftClick((x as u32, y as u32)));
-                    }
-                }
+                log::trace!("Mouse clicked at x: {}, y: {}", x, y);
+                self.engine_input(Input::LeftClick(ClientPoint::new(x, y)));
                 None
             }
             WM_MOUSEMOVE => {
                 let x = GET_X_LPARAM(lparam.0 as u32);
                 let y = GET_Y_LPARAM(lparam.0 as u32);
-                // println!("Mouse Moved x: {:?}, y: {:?}", x, y);
-                unsafe {
-                    if let Some(state) = self.engine.as_mut() {
-                        state.input(Input::MouseMove((x as u32, y as u32)));
+                if self.cursor_grab == CursorGrab::Locked && self.active {
+                    // Moving the cursor back sends a move to the centre, which is not movement
+                    if let Ok(centre) = self.client_centre() {
+                        let delta = (x - centre.x, y - centre.y);
+                        if delta != (0, 0) {
+                            // Raw input reports the same movement without acceleration
+                            if !self.raw_input {
+                                self.engine_input(Input::MouseDelta(delta));
+                            }
+                            let _ = self.centre_cursor();
+                        }
                     }
+                    return None;
                 }
+                // println!("Mouse Moved x: {:?}, y: {:?}", x, y);
+                self.engine_input(Input::MouseMove(ClientPoint::new(x, y)));
                 None
             }
             WM_KEYDOWN => {
@@ -154,11 +1113,7 @@ impl Window {
                         post_quit_message(0);
                         return Some(LRESULT(0));
                     } else {
-                        unsafe {
-                            if let Some(state) = self.engine.as_mut() {
-                                state.input(Input::KeyDown(key));
-                            }
-                        }
+                        self.engine_input(Input::KeyDown(key));
                     }
                 }
                 None
@@ -204,21 +1159,76 @@ impl Window {
         DefWindowProcW(hwnd, message, wparam, lparam)
     }
 
-    pub fn start(&self) {
+    /// Runs the message loop until the application quits
+    pub fn start(&mut self) {
         let mut message = MSG::default();
-        unsafe {
-            // Note: Pass null because passing the window handle will not pick up all messages
-            while GetMessageW(&mut message, HWND(0), 0, 0).into() {
+        loop {
+            let timeout = self.loop_mode.timeout(Instant::now());
+            match timeout {
+                None => {
+                    // Note: Pass null because passing the window handle will not pick up all messages
+                    if !get_message(&mut message) {
+                        Self::exit(&message);
+                    }
+                    dispatch_message(&message);
+                }
+                Some(timeout) if !timeout.is_zero() => wait_for_message(timeout),
+                Some(_) => {}
+            }
+
+            while peek_message(&mut message) {
                 if message.message == WM_QUIT {
-                    // Where we receive a quit message we take the wParam and use that as an exit
-                    // code for our application
-                    std::process::exit(message.wParam.0 as i32);
+                    Self::exit(&message);
                 }
-                TranslateMessage(&message);
-                DispatchMessageW(&message);
+                dispatch_message(&message);
+            }
+
+            if self.loop_mode.should_tick(Instant::now()) {
+                if let LoopMode::WaitUntil(_) = self.loop_mode {
+                    self.loop_mode = LoopMode::Wait;
+                }
+                self.tick();
             }
         }
     }
+
+    /// Where we receive a quit message we take the wParam and use that as an exit code for our
+    /// application
+    fn exit(message: &MSG) -> ! {
+        std::process::exit(message.wParam.0 as i32);
+    }
+
+    /// Runs the fixed updates that are due and renders a frame
+    fn tick(&mut self) {
+        let updates = self.timestep.advance();
+        let step = self.timestep.step();
+        let alpha = self.timestep.alpha();
+
+        if let Some(state) = unsafe { self.engine.as_mut() } {
+            for _ in 0..updates {
+                state.fixed_update(step);
+            }
+            state.set_interpolation(alpha);
+            if state.needs_redraw() {
+                if let Err(error) = state.render() {
+                    on_render_error(error);
+                }
+            }
+        }
+    }
+}
+
+fn rect_size(rect: &RECT) -> PhysicalSize {
+    PhysicalSize::new(
+        (rect.right - rect.left).max(0) as u32,
+        (rect.bottom - rect.top).max(0) as u32,
+    )
+}
+
+/// Rendering errors the engine could not recover from close the application
+fn on_render_error(error: RenderError) {
+    log::error!("Rendering failed: {}", error);
+    post_quit_message(1);
 }
 
 unsafe impl HasRawWindowHandle for Window {
@@ -241,11 +1251,25 @@ pub(crate) enum Region {
 }
 
 impl Region {
-    /// A hit check for the location of the cursor and unification of the values
-    pub(crate) fn hit_test(hwnd: HWND, cursor: POINT) -> LRESULT {
-        let border = get_border();
+    /// A hit check for the location of the cursor and unification of the values.
+    /// A maximized window cannot be resized, so it has no border.
+    pub(crate) fn hit_test(hwnd: HWND, cursor: ScreenPoint, maximized: bool) -> LRESULT {
+        let dpi = get_dpi_for_window(hwnd);
+        let border = if maximized {
+            POINT::default()
+        } else {
+            get_border(dpi)
+        };
 
         let rect = get_window_rect(hwnd).unwrap();
+        // A maximized window overhangs the monitor and its client area starts at the work area
+        let caption_top = if maximized {
+            client_to_screen(hwnd, POINT::default())
+                .map(|origin| origin.y)
+                .unwrap_or(rect.top)
+        } else {
+            rect.top
+        };
 
         let result = {
             (Self::Left as u8 * (cursor.x < (rect.left + border.x)) as u8)
@@ -256,7 +1280,7 @@ impl Region {
 
         let hit = match result {
             0b0000 => {
-                if cursor.y < rect.top + get_titlebar_height() {
+                if cursor.y < caption_top + get_titlebar_height(dpi) {
                     HTCAPTION // TODO: This is a temporary solution to titlebar
                 } else {
                     HTCLIENT
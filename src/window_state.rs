@@ -0,0 +1,58 @@
+#![allow(dead_code)]
+//! Maximized, minimized and restored windows.
+//! The window draws its own frame, so a maximized window has to be kept inside the work area
+//! by hand instead of overhanging the monitor by the resize border as framed windows do.
+
+use crate::coords::ScreenRect;
+use crate::dpi::PhysicalSize;
+
+// WM_SIZE wparam
+const SIZE_RESTORED: u32 = 0;
+const SIZE_MINIMIZED: u32 = 1;
+const SIZE_MAXIMIZED: u32 = 2;
+
+/// Pixels left uncovered along the edge of an auto hidden taskbar, so moving the mouse there
+/// still reveals it
+pub const AUTOHIDE_GAP: i32 = 2;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WindowState {
+    #[default]
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+impl WindowState {
+    /// The state a WM_SIZE reports in its wparam, None for the messages about other windows
+    pub fn from_size_message(kind: u32) -> Option<Self> {
+        match kind {
+            SIZE_RESTORED => Some(Self::Normal),
+            SIZE_MINIMIZED => Some(Self::Minimized),
+            SIZE_MAXIMIZED => Some(Self::Maximized),
+            _ => None,
+        }
+    }
+}
+
+/// The edges of a monitor that have an auto hidden taskbar
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaskbarEdges {
+    pub left: bool,
+    pub top: bool,
+    pub right: bool,
+    pub bottom: bool,
+}
+
+/// Where a maximized window goes on a monitor. An auto hidden taskbar is not part of the work
+/// area, so the edge it hides behind is left uncovered by `AUTOHIDE_GAP`.
+pub fn maximized_rect(work_area: ScreenRect, autohide: TaskbarEdges) -> ScreenRect {
+    let gap = |hidden: bool| if hidden { AUTOHIDE_GAP } else { 0 };
+    let (left, top) = (gap(autohide.left), gap(autohide.top));
+    let width = work_area.size.width as i32 - left - gap(autohide.right);
+    let height = work_area.size.height as i32 - top - gap(autohide.bottom);
+    ScreenRect::new(
+        work_area.position.offset(left, top),
+        PhysicalSize::new(width.max(0) as u32, height.max(0) as u32),
+    )
+}